@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Units {
     Px(f32),
@@ -64,6 +66,55 @@ impl Units {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct ParseUnitsError(pub String);
+
+impl std::fmt::Display for ParseUnitsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid length: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseUnitsError {}
+
+impl FromStr for Units {
+    type Err = ParseUnitsError;
+
+    /// Parses a single CSS length such as `4px`, `50%`, `1.5em` or `auto`.
+    /// Unitless zero is accepted as `0px`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Self::Auto);
+        }
+
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number = number
+            .parse::<f32>()
+            .map_err(|_| ParseUnitsError(s.to_string()))?;
+
+        match unit.to_ascii_lowercase().as_str() {
+            "px" => Ok(Self::Px(number)),
+            "%" => Ok(Self::Perc(number)),
+            "vw" => Ok(Self::Vw(number)),
+            "vh" => Ok(Self::Vh(number)),
+            "vmin" => Ok(Self::Vmin(number)),
+            "vmax" => Ok(Self::Vmax(number)),
+            "in" => Ok(Self::In(number)),
+            "mm" => Ok(Self::Mm(number)),
+            "pt" => Ok(Self::Pt(number)),
+            "pc" => Ok(Self::Pc(number)),
+            "em" => Ok(Self::Em(number)),
+            "rem" => Ok(Self::Rem(number)),
+            "" if number == 0.0 => Ok(Self::Px(0.0)),
+            _ => Err(ParseUnitsError(s.to_string())),
+        }
+    }
+}
+
 pub struct Context {
     pub root_font_size: f32,
     pub reference_size: f32,
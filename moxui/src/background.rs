@@ -0,0 +1,324 @@
+use calc_units::{Context, Units};
+
+use crate::{gradient::Gradient, images::Image};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum BackgroundImage {
+    Gradient(Gradient),
+    Image(Image),
+}
+
+impl From<Gradient> for BackgroundImage {
+    fn from(gradient: Gradient) -> Self {
+        Self::Gradient(gradient)
+    }
+}
+
+impl From<Image> for BackgroundImage {
+    fn from(image: Image) -> Self {
+        Self::Image(image)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum BackgroundSize {
+    Cover,
+    Contain,
+    /// Width and height, either of which can be `Units::Auto`.
+    Size([Units; 2]),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BackgroundRepeat {
+    Repeat,
+    Space,
+    Round,
+    NoRepeat,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BackgroundBox {
+    BorderBox,
+    PaddingBox,
+    ContentBox,
+}
+
+/// The `background-*` properties. Like in CSS every property is a list with one entry per
+/// layer, the first layer painted on top, and shorter lists repeat to cover all images.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Background {
+    pub image: Vec<BackgroundImage>,
+    pub size: Vec<BackgroundSize>,
+    /// Offsets from the top left of the origin box. Percentages align that point of the image
+    /// with the same point of the box.
+    pub position: Vec<[Units; 2]>,
+    pub repeat: Vec<[BackgroundRepeat; 2]>,
+    pub origin: Vec<BackgroundBox>,
+    pub clip: Vec<BackgroundBox>,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self {
+            image: Vec::new(),
+            size: vec![BackgroundSize::Size([Units::Auto, Units::Auto])],
+            position: vec![[Units::Perc(0.0), Units::Perc(0.0)]],
+            repeat: vec![[BackgroundRepeat::Repeat; 2]],
+            origin: vec![BackgroundBox::PaddingBox],
+            clip: vec![BackgroundBox::BorderBox],
+        }
+    }
+}
+
+/// The properties of a single layer.
+pub(crate) struct Layer<'a> {
+    pub image: &'a BackgroundImage,
+    pub size: &'a BackgroundSize,
+    pub position: &'a [Units; 2],
+    pub repeat: [BackgroundRepeat; 2],
+    pub origin: BackgroundBox,
+    pub clip: BackgroundBox,
+}
+
+/// Where copies of a layer's image go: the first one at `rect`, then every `rect` size plus
+/// `spacing` along the repeated axes.
+pub(crate) struct Tile {
+    pub rect: [f32; 4],
+    pub spacing: [f32; 2],
+    pub repeat: [bool; 2],
+}
+
+pub(crate) fn cycle<T>(list: &[T], i: usize) -> Option<&T> {
+    match list.len() {
+        0 => None,
+        len => Some(&list[i % len]),
+    }
+}
+
+impl Background {
+    /// Layers from the bottom one up, in painting order.
+    pub(crate) fn layers(&self) -> impl Iterator<Item = Layer<'_>> {
+        static AUTO: BackgroundSize = BackgroundSize::Size([Units::Auto, Units::Auto]);
+        static ORIGIN: [Units; 2] = [Units::Perc(0.0), Units::Perc(0.0)];
+
+        self.image.iter().enumerate().rev().map(|(i, image)| Layer {
+            image,
+            size: cycle(&self.size, i).unwrap_or(&AUTO),
+            position: cycle(&self.position, i).unwrap_or(&ORIGIN),
+            repeat: cycle(&self.repeat, i)
+                .copied()
+                .unwrap_or([BackgroundRepeat::Repeat; 2]),
+            origin: cycle(&self.origin, i)
+                .copied()
+                .unwrap_or(BackgroundBox::PaddingBox),
+            clip: cycle(&self.clip, i)
+                .copied()
+                .unwrap_or(BackgroundBox::BorderBox),
+        })
+    }
+}
+
+impl Layer<'_> {
+    /// Sizes and positions the image within the origin box `area` (x, y, width, height).
+    pub(crate) fn tile(&self, area: [f32; 4], context: &Context) -> Tile {
+        let [x, y, width, height] = area;
+
+        let intrinsic = match self.image {
+            BackgroundImage::Image(image) => Some([image.width() as f32, image.height() as f32]),
+            BackgroundImage::Gradient(_) => None,
+        };
+
+        let length = |units: &Units, reference: f32| match units {
+            Units::Auto => None,
+            units => Some(units.to_px(&Context {
+                reference_size: reference,
+                ..*context
+            })),
+        };
+
+        let mut size = match (self.size, intrinsic) {
+            (BackgroundSize::Cover, Some([w, h])) => {
+                let scale = (width / w).max(height / h);
+                [w * scale, h * scale]
+            }
+            (BackgroundSize::Contain, Some([w, h])) => {
+                let scale = (width / w).min(height / h);
+                [w * scale, h * scale]
+            }
+            (BackgroundSize::Cover | BackgroundSize::Contain, None) => [width, height],
+            (BackgroundSize::Size([w, h]), intrinsic) => {
+                match (length(w, width), length(h, height), intrinsic) {
+                    (Some(w), Some(h), _) => [w, h],
+                    (Some(w), None, Some([iw, ih])) => [w, w * ih / iw],
+                    (None, Some(h), Some([iw, ih])) => [h * iw / ih, h],
+                    (None, None, Some(intrinsic)) => intrinsic,
+                    (w, h, None) => [w.unwrap_or(width), h.unwrap_or(height)],
+                }
+            }
+        };
+
+        // `round` rescales the image to fit a whole number of times, keeping the aspect ratio
+        // when the other axis was left to it.
+        let keeps_ratio = |axis: usize| match self.size {
+            BackgroundSize::Size(size) => {
+                size[axis] == Units::Auto && self.repeat[axis] != BackgroundRepeat::Round
+            }
+            _ => false,
+        };
+        let extent = [width, height];
+        (0..2).for_each(|axis| {
+            if self.repeat[axis] == BackgroundRepeat::Round && size[axis] > 0.0 {
+                let count = (extent[axis] / size[axis]).round().max(1.0);
+                let rounded = extent[axis] / count;
+                if keeps_ratio(1 - axis) {
+                    size[1 - axis] *= rounded / size[axis];
+                }
+                size[axis] = rounded;
+            }
+        });
+
+        let mut rect = [
+            x + length(&self.position[0], width - size[0]).unwrap_or(0.0),
+            y + length(&self.position[1], height - size[1]).unwrap_or(0.0),
+            size[0],
+            size[1],
+        ];
+        let mut spacing = [0.0; 2];
+        let mut repeat = [false; 2];
+
+        (0..2).for_each(|axis| match self.repeat[axis] {
+            BackgroundRepeat::Repeat | BackgroundRepeat::Round => repeat[axis] = true,
+            BackgroundRepeat::NoRepeat => {}
+            // Spreads as many whole copies as fit over the box, ignoring the position.
+            BackgroundRepeat::Space => {
+                let count = match size[axis] > 0.0 {
+                    true => (extent[axis] / size[axis]).floor(),
+                    false => 0.0,
+                };
+                if count >= 2.0 {
+                    spacing[axis] = (extent[axis] - count * size[axis]) / (count - 1.0);
+                    rect[axis] = area[axis];
+                    repeat[axis] = true;
+                }
+            }
+        });
+
+        Tile {
+            rect,
+            spacing,
+            repeat,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gradient::{GradientKind, LinearDirection};
+
+    const CONTEXT: Context = Context {
+        root_font_size: 16.0,
+        reference_size: 0.0,
+        parent_font_size: 16.0,
+        viewport: (800.0, 600.0),
+        auto: 0.0,
+        dpi: 96.0,
+    };
+
+    // A 40x20 image sized by `size`, repeated by `repeat` and positioned at `position` in a
+    // 100x70 box at 10, 10.
+    fn tile(size: BackgroundSize, repeat: [BackgroundRepeat; 2], position: [Units; 2]) -> Tile {
+        let image = BackgroundImage::Image(Image::from_rgba(image::RgbaImage::new(40, 20)));
+        let layer = Layer {
+            image: &image,
+            size: &size,
+            position: &position,
+            repeat,
+            origin: BackgroundBox::PaddingBox,
+            clip: BackgroundBox::BorderBox,
+        };
+        layer.tile([10.0, 10.0, 100.0, 70.0], &CONTEXT)
+    }
+
+    const NO_REPEAT: [BackgroundRepeat; 2] = [BackgroundRepeat::NoRepeat; 2];
+    const CENTER: [Units; 2] = [Units::Perc(50.0), Units::Perc(50.0)];
+    const TOP_LEFT: [Units; 2] = [Units::Perc(0.0), Units::Perc(0.0)];
+
+    #[test]
+    fn test_cover_contain() {
+        // Cover scales to the larger ratio and overflows, contain to the smaller one.
+        let cover = tile(BackgroundSize::Cover, NO_REPEAT, CENTER);
+        assert_eq!(cover.rect, [-10.0, 10.0, 140.0, 70.0]);
+        let cover = tile(BackgroundSize::Cover, NO_REPEAT, TOP_LEFT);
+        assert_eq!(cover.rect, [10.0, 10.0, 140.0, 70.0]);
+
+        let contain = tile(BackgroundSize::Contain, NO_REPEAT, CENTER);
+        assert_eq!(contain.rect, [10.0, 20.0, 100.0, 50.0]);
+        assert_eq!(contain.repeat, [false; 2]);
+    }
+
+    #[test]
+    fn test_sizes() {
+        let size = |w, h| BackgroundSize::Size([w, h]);
+        let auto = tile(size(Units::Auto, Units::Auto), NO_REPEAT, TOP_LEFT);
+        assert_eq!(auto.rect, [10.0, 10.0, 40.0, 20.0]);
+        // One length keeps the aspect ratio of the image.
+        let width = tile(size(Units::Px(80.0), Units::Auto), NO_REPEAT, TOP_LEFT);
+        assert_eq!(width.rect, [10.0, 10.0, 80.0, 40.0]);
+        let both = tile(
+            size(Units::Perc(50.0), Units::Perc(10.0)),
+            NO_REPEAT,
+            CENTER,
+        );
+        assert_eq!(both.rect, [35.0, 41.5, 50.0, 7.0]);
+    }
+
+    #[test]
+    fn test_round() {
+        let auto = BackgroundSize::Size([Units::Auto, Units::Auto]);
+        let round = [BackgroundRepeat::Round, BackgroundRepeat::NoRepeat];
+        // 2.5 copies round to 3, and the height follows the width.
+        let tile = tile(auto, round, TOP_LEFT);
+        let width = 100.0 / 3.0;
+        assert_eq!(tile.rect, [10.0, 10.0, width, 20.0 * width / 40.0]);
+        assert_eq!(tile.repeat, [true, false]);
+        assert_eq!(tile.spacing, [0.0; 2]);
+    }
+
+    #[test]
+    fn test_space() {
+        let auto = BackgroundSize::Size([Units::Auto, Units::Auto]);
+        let space = [BackgroundRepeat::Space; 2];
+        // Whole copies are spread to the edges, whatever the position.
+        let spaced = tile(auto.clone(), space, CENTER);
+        assert_eq!(spaced.rect, [10.0, 10.0, 40.0, 20.0]);
+        assert_eq!(spaced.spacing, [20.0, 5.0]);
+        assert_eq!(spaced.repeat, [true; 2]);
+
+        // A single copy isn't repeated and keeps its position.
+        let large = BackgroundSize::Size([Units::Px(60.0), Units::Px(40.0)]);
+        let single = tile(large, space, CENTER);
+        assert_eq!(single.rect, [30.0, 25.0, 60.0, 40.0]);
+        assert_eq!(single.repeat, [false; 2]);
+    }
+
+    #[test]
+    fn test_gradient_fills_box() {
+        let gradient = BackgroundImage::Gradient(Gradient {
+            kind: GradientKind::Linear(LinearDirection::Angle(0.0)),
+            stops: Vec::new(),
+            repeating: false,
+        });
+        let layer = Layer {
+            image: &gradient,
+            size: &BackgroundSize::Size([Units::Px(20.0), Units::Auto]),
+            position: &TOP_LEFT,
+            repeat: [BackgroundRepeat::Repeat; 2],
+            origin: BackgroundBox::PaddingBox,
+            clip: BackgroundBox::BorderBox,
+        };
+        let tile = layer.tile([0.0, 0.0, 100.0, 50.0], &CONTEXT);
+        assert_eq!(tile.rect, [0.0, 0.0, 20.0, 50.0]);
+        assert_eq!(tile.repeat, [true; 2]);
+    }
+}
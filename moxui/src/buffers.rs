@@ -1,6 +1,6 @@
 use crate::math::{self, Matrix};
 use std::{
-    ops::{Deref, RangeBounds},
+    ops::{Deref, Range, RangeBounds},
     rc::Rc,
 };
 use wgpu::{util::DeviceExt, BufferAddress};
@@ -160,53 +160,59 @@ impl ProjectionUniform {
     }
 }
 
+/// A storage buffer kept across frames. It grows to fit what is written to it, and only the
+/// elements that changed since the last write are uploaded.
 pub struct StorageBuffer<T> {
-    pub storage: Rc<[T]>,
+    pub storage: Vec<T>,
     pub buffer: wgpu::Buffer,
-    pub bind_group_layout: wgpu::BindGroupLayout,
-    pub bind_group: wgpu::BindGroup,
+    label: &'static str,
 }
 
-impl<T> StorageBuffer<T> {
-    pub fn new(device: &wgpu::Device, instance_data: Rc<[T]>) -> Self
-    where
-        T: bytemuck::Pod,
-    {
-        let storage_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Storage buffer"),
-            contents: bytemuck::cast_slice(&instance_data),
+impl<T> StorageBuffer<T>
+where
+    T: bytemuck::Pod,
+{
+    pub fn new(device: &wgpu::Device, label: &'static str) -> Self {
+        Self {
+            storage: Vec::new(),
+            buffer: Self::create_buffer(device, label, 64),
+            label,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, label: &'static str, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity * std::mem::size_of::<T>()) as BufferAddress,
             usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        });
+            mapped_at_creation: false,
+        })
+    }
 
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Bind Group Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
+    fn capacity(&self) -> usize {
+        self.buffer.size() as usize / std::mem::size_of::<T>()
+    }
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 1,
-                resource: storage_buffer.as_entire_binding(),
-            }],
-            label: Some("Instance data buffer"),
-        });
+    /// Replaces the contents with `data`, writing runs of elements that differ from before.
+    /// Returns whether the buffer had to be reallocated, which invalidates its bind groups.
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: Vec<T>) -> bool {
+        let reallocated = data.len() > self.capacity();
+        if reallocated {
+            self.buffer = Self::create_buffer(device, self.label, data.len().next_power_of_two());
+            self.storage.clear();
+        }
 
-        Self {
-            storage: instance_data,
-            buffer: storage_buffer,
-            bind_group_layout,
-            bind_group,
+        let size = std::mem::size_of::<T>();
+        for run in changed_runs(&self.storage, &data) {
+            queue.write_buffer(
+                &self.buffer,
+                (run.start * size) as BufferAddress,
+                bytemuck::cast_slice(&data[run]),
+            );
         }
+
+        self.storage = data;
+        reallocated
     }
 
     pub fn len(&self) -> u32 {
@@ -217,3 +223,53 @@ impl<T> StorageBuffer<T> {
         self.len() == 0
     }
 }
+
+// The runs of elements of `new` that differ from `old` byte for byte, or that `old` doesn't
+// have.
+fn changed_runs<T: bytemuck::Pod>(old: &[T], new: &[T]) -> Vec<Range<usize>> {
+    let unchanged = |i: usize| {
+        old.get(i)
+            .is_some_and(|old| bytemuck::bytes_of(old) == bytemuck::bytes_of(&new[i]))
+    };
+
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < new.len() {
+        if unchanged(i) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < new.len() && !unchanged(i) {
+            i += 1;
+        }
+        runs.push(start..i);
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The runs as start and end pairs.
+    fn runs(old: &[u32], new: &[u32]) -> Vec<(usize, usize)> {
+        changed_runs(old, new)
+            .into_iter()
+            .map(|run| (run.start, run.end))
+            .collect()
+    }
+
+    #[test]
+    fn test_changed_runs() {
+        let old = [1, 2, 3, 4, 5];
+        assert_eq!(runs(&old, &old), []);
+        assert_eq!(runs(&old, &[1, 0, 0, 4, 0]), [(1, 3), (4, 5)]);
+        // Elements past the old ones are always written, fewer ones leave nothing to write.
+        assert_eq!(runs(&old, &[1, 2, 3, 4, 5, 6, 7]), [(5, 7)]);
+        assert_eq!(runs(&old, &[0, 2, 3]), [(0, 1)]);
+        assert_eq!(runs(&[], &old), [(0, 5)]);
+        assert_eq!(runs(&old, &[]), []);
+    }
+}
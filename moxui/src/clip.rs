@@ -0,0 +1,439 @@
+use calc_units::{Context, Units};
+
+/// `clip-path`, clipping the node and its descendants to a shape in its border box.
+/// Lengths and percentages are relative to the border box, like CSS.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ClipPath {
+    /// Insets from the top, right, bottom and left edge, with `round` corner radii from the
+    /// top left corner on.
+    Inset {
+        insets: [Units; 4],
+        radius: [Units; 4],
+    },
+    Circle {
+        radius: ShapeRadius,
+        center: [Units; 2],
+    },
+    Ellipse {
+        radii: [ShapeRadius; 2],
+        center: [Units; 2],
+    },
+    Polygon {
+        fill_rule: FillRule,
+        points: Vec<[Units; 2]>,
+    },
+    /// SVG path data in pixels from the top left corner of the border box. Unlike the other
+    /// shapes, it is cut off at the border box.
+    Path { fill_rule: FillRule, path: String },
+}
+
+/// Radius of `circle()` and `ellipse()`. Percentages of circles refer to the diagonal of the
+/// border box divided by the square root of two.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ShapeRadius {
+    Length(Units),
+    ClosestSide,
+    FarthestSide,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+impl FillRule {
+    fn name(self) -> &'static str {
+        match self {
+            Self::NonZero => "nonzero",
+            Self::EvenOdd => "evenodd",
+        }
+    }
+}
+
+/// A `ClipPath` resolved to layout coordinates for one border box.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum ClipShape {
+    /// Rectangles with circular corners, which covers `inset()` and `circle()`.
+    Rounded { rect: [f32; 4], radius: [f32; 4] },
+    /// The ellipse inscribed in `rect`.
+    Ellipse { rect: [f32; 4] },
+    /// SVG markup of a filled shape within `rect`, rasterized into a mask.
+    Path {
+        rect: [f32; 4],
+        fill_rule: FillRule,
+        markup: String,
+    },
+}
+
+impl ClipPath {
+    pub(crate) fn resolve(&self, border_box: [f32; 4], context: &Context) -> ClipShape {
+        let [x, y, width, height] = border_box;
+        let length = |units: &Units, reference_size: f32| {
+            units.to_px(&Context {
+                root_font_size: context.root_font_size,
+                reference_size,
+                parent_font_size: context.parent_font_size,
+                viewport: context.viewport,
+                auto: 0.0,
+                dpi: context.dpi,
+            })
+        };
+        let point =
+            |point: &[Units; 2]| [x + length(&point[0], width), y + length(&point[1], height)];
+        // Distances from `center` to the closest and farthest vertical and horizontal sides.
+        let sides = |[cx, cy]: [f32; 2]| {
+            let horizontal = [(cx - x).abs(), (x + width - cx).abs()];
+            let vertical = [(cy - y).abs(), (y + height - cy).abs()];
+            (horizontal, vertical)
+        };
+        let radius = |radius: &ShapeRadius, distances: [f32; 2], reference_size: f32| match radius {
+            ShapeRadius::Length(units) => length(units, reference_size).max(0.0),
+            ShapeRadius::ClosestSide => distances[0].min(distances[1]),
+            ShapeRadius::FarthestSide => distances[0].max(distances[1]),
+        };
+
+        match self {
+            Self::Inset { insets, radius } => {
+                let [top, right, bottom, left] = [
+                    length(&insets[0], height),
+                    length(&insets[1], width),
+                    length(&insets[2], height),
+                    length(&insets[3], width),
+                ];
+                ClipShape::Rounded {
+                    rect: [
+                        x + left,
+                        y + top,
+                        (width - left - right).max(0.0),
+                        (height - top - bottom).max(0.0),
+                    ],
+                    radius: radius.each_ref().map(|radius| length(radius, width)),
+                }
+            }
+            Self::Circle { radius: r, center } => {
+                let [cx, cy] = point(center);
+                let (horizontal, vertical) = sides([cx, cy]);
+                let distances = [
+                    horizontal[0]
+                        .min(horizontal[1])
+                        .min(vertical[0].min(vertical[1])),
+                    horizontal[0]
+                        .max(horizontal[1])
+                        .max(vertical[0].max(vertical[1])),
+                ];
+                let diagonal = (width * width + height * height).sqrt() / std::f32::consts::SQRT_2;
+                let r = radius(r, distances, diagonal);
+                ClipShape::Rounded {
+                    rect: [cx - r, cy - r, r * 2.0, r * 2.0],
+                    radius: [r; 4],
+                }
+            }
+            Self::Ellipse { radii, center } => {
+                let [cx, cy] = point(center);
+                let (horizontal, vertical) = sides([cx, cy]);
+                let rx = radius(&radii[0], horizontal, width);
+                let ry = radius(&radii[1], vertical, height);
+                ClipShape::Ellipse {
+                    rect: [cx - rx, cy - ry, rx * 2.0, ry * 2.0],
+                }
+            }
+            Self::Polygon { fill_rule, points } => {
+                let points = points.iter().map(point).collect::<Vec<_>>();
+                let [mut min, mut max] = [[f32::MAX; 2], [f32::MIN; 2]];
+                for [px, py] in &points {
+                    min = [min[0].min(*px), min[1].min(*py)];
+                    max = [max[0].max(*px), max[1].max(*py)];
+                }
+
+                let data = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, [px, py])| match i {
+                        0 => format!("M {px} {py}"),
+                        _ => format!(" L {px} {py}"),
+                    })
+                    .collect::<String>();
+                ClipShape::Path {
+                    rect: match points.is_empty() {
+                        true => [x, y, 0.0, 0.0],
+                        false => [min[0], min[1], max[0] - min[0], max[1] - min[1]],
+                    },
+                    fill_rule: *fill_rule,
+                    markup: format!(r#"<path d="{data} Z"/>"#),
+                }
+            }
+            Self::Path { fill_rule, path } => ClipShape::Path {
+                rect: border_box,
+                fill_rule: *fill_rule,
+                markup: format!(r#"<path transform="translate({x} {y})" d="{path}"/>"#),
+            },
+        }
+    }
+}
+
+impl ClipShape {
+    /// The rectangle the shape is drawn over.
+    pub fn rect(&self) -> [f32; 4] {
+        match self {
+            Self::Rounded { rect, .. } | Self::Ellipse { rect } | Self::Path { rect, .. } => *rect,
+        }
+    }
+
+    /// An SVG document filling the shape in white, showing the `view_box` part of the layout.
+    pub fn svg(&self, view_box: [f32; 4]) -> Option<String> {
+        let Self::Path {
+            fill_rule, markup, ..
+        } = self
+        else {
+            return None;
+        };
+
+        let [x, y, width, height] = view_box;
+        Some(format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="{x} {y} {width} {height}"><g fill="white" fill-rule="{}">{markup}</g></svg>"#,
+            fill_rule.name(),
+        ))
+    }
+
+    /// Whether `point`, in layout coordinates, is inside the shape.
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        match self {
+            Self::Rounded { rect, radius } => in_rounded_rect(point, *rect, *radius),
+            Self::Ellipse { rect } => {
+                let [x, y, width, height] = *rect;
+                let [dx, dy] = [
+                    (point[0] - x - width / 2.0) / (width / 2.0),
+                    (point[1] - y - height / 2.0) / (height / 2.0),
+                ];
+                dx * dx + dy * dy <= 1.0
+            }
+            Self::Path { rect, .. } => {
+                let [x, y, width, height] = *rect;
+                if point[0] < x || point[1] < y || point[0] > x + width || point[1] > y + height {
+                    return false;
+                }
+
+                // Rasterizes the one pixel around the point, like the mask it is drawn with.
+                let view_box = [point[0] - 0.5, point[1] - 0.5, 1.0, 1.0];
+                self.svg(view_box)
+                    .and_then(|source| crate::svg::Svg::from_bytes(source.as_bytes()).ok())
+                    .and_then(|svg| svg.rasterize(1, 1, [255; 4]))
+                    .is_some_and(|image| image.rgba().get_pixel(0, 0)[3] >= 128)
+            }
+        }
+    }
+}
+
+/// Whether `point` is inside `rect` with `radius` corners from the top left on.
+pub(crate) fn in_rounded_rect(point: [f32; 2], rect: [f32; 4], radius: [f32; 4]) -> bool {
+    let [x, y, width, height] = rect;
+    let [px, py] = [point[0] - x, point[1] - y];
+    if px < 0.0 || py < 0.0 || px > width || py > height {
+        return false;
+    }
+
+    // Outside a corner's radius, the point has to be within its circle.
+    let [top_left, top_right, bottom_right, bottom_left] = radius;
+    let corner = match (px < width / 2.0, py < height / 2.0) {
+        (true, true) => (top_left, top_left, top_left),
+        (false, true) => (top_right, width - top_right, top_right),
+        (false, false) => (bottom_right, width - bottom_right, height - bottom_right),
+        (true, false) => (bottom_left, bottom_left, height - bottom_left),
+    };
+    let (radius, cx, cy) = corner;
+    let dx = match px < width / 2.0 {
+        true => (cx - px).max(0.0),
+        false => (px - cx).max(0.0),
+    };
+    let dy = match py < height / 2.0 {
+        true => (cy - py).max(0.0),
+        false => (py - cy).max(0.0),
+    };
+    dx * dx + dy * dy <= radius * radius
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTEXT: Context = Context {
+        root_font_size: 16.0,
+        reference_size: 0.0,
+        parent_font_size: 16.0,
+        viewport: (800.0, 600.0),
+        auto: 0.0,
+        dpi: 96.0,
+    };
+
+    const BORDER_BOX: [f32; 4] = [10.0, 20.0, 100.0, 50.0];
+
+    fn resolve(clip_path: ClipPath) -> ClipShape {
+        clip_path.resolve(BORDER_BOX, &CONTEXT)
+    }
+
+    fn assert_rect(shape: &ClipShape, expected: [f32; 4]) {
+        for (actual, expected) in shape.rect().iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-3,
+                "{:?} != {expected:?}",
+                shape.rect()
+            );
+        }
+    }
+
+    #[test]
+    fn test_inset() {
+        let inset = resolve(ClipPath::Inset {
+            insets: [
+                Units::Px(10.0),
+                Units::Perc(10.0),
+                Units::Perc(20.0),
+                Units::Px(0.0),
+            ],
+            radius: [
+                Units::Px(5.0),
+                Units::Perc(10.0),
+                Units::Px(0.0),
+                Units::Px(0.0),
+            ],
+        });
+        // Vertical insets are percentages of the height, horizontal ones of the width.
+        assert_eq!(
+            inset,
+            ClipShape::Rounded {
+                rect: [10.0, 30.0, 90.0, 30.0],
+                radius: [5.0, 10.0, 0.0, 0.0],
+            }
+        );
+        assert!(inset.contains([50.0, 40.0]));
+        assert!(!inset.contains([50.0, 25.0]));
+        assert!(!inset.contains([105.0, 40.0]));
+        // Rounded off the top left corner.
+        assert!(!inset.contains([10.5, 30.5]));
+        assert!(inset.contains([10.5, 59.5]));
+
+        // Insets meeting in the middle leave nothing.
+        let empty = resolve(ClipPath::Inset {
+            insets: [
+                Units::Perc(60.0),
+                Units::Px(0.0),
+                Units::Perc(60.0),
+                Units::Px(0.0),
+            ],
+            radius: [
+                Units::Px(0.0),
+                Units::Px(0.0),
+                Units::Px(0.0),
+                Units::Px(0.0),
+            ],
+        });
+        assert_rect(&empty, [10.0, 50.0, 100.0, 0.0]);
+    }
+
+    #[test]
+    fn test_circle() {
+        let circle = |radius| {
+            resolve(ClipPath::Circle {
+                radius,
+                center: [Units::Perc(25.0), Units::Perc(50.0)],
+            })
+        };
+        // The center is at (35, 45), 25 pixels from the closest sides, 75 from the farthest.
+        let closest = circle(ShapeRadius::ClosestSide);
+        assert_rect(&closest, [10.0, 20.0, 50.0, 50.0]);
+        assert!(closest.contains([35.0, 21.0]));
+        assert!(!closest.contains([12.0, 22.0]));
+        assert_rect(
+            &circle(ShapeRadius::FarthestSide),
+            [-40.0, -30.0, 150.0, 150.0],
+        );
+
+        // Percentages refer to the diagonal over the square root of two.
+        let radius = (100.0f32.hypot(50.0) / std::f32::consts::SQRT_2) / 2.0;
+        let percentage = circle(ShapeRadius::Length(Units::Perc(50.0)));
+        assert_rect(
+            &percentage,
+            [35.0 - radius, 45.0 - radius, radius * 2.0, radius * 2.0],
+        );
+    }
+
+    #[test]
+    fn test_ellipse() {
+        // The center is at (30, 30), 20 and 80 pixels from the sides, 10 and 40 from the top
+        // and bottom.
+        let ellipse = resolve(ClipPath::Ellipse {
+            radii: [ShapeRadius::ClosestSide, ShapeRadius::FarthestSide],
+            center: [Units::Px(20.0), Units::Px(10.0)],
+        });
+        assert_eq!(
+            ellipse,
+            ClipShape::Ellipse {
+                rect: [10.0, -10.0, 40.0, 80.0],
+            }
+        );
+        assert!(ellipse.contains([30.0, 65.0]));
+        assert!(ellipse.contains([49.0, 30.0]));
+        assert!(!ellipse.contains([45.0, 60.0]));
+
+        // Percentages of ellipses refer to the width and height.
+        let percentage = resolve(ClipPath::Ellipse {
+            radii: [
+                ShapeRadius::Length(Units::Perc(50.0)),
+                ShapeRadius::Length(Units::Perc(50.0)),
+            ],
+            center: [Units::Perc(50.0), Units::Perc(50.0)],
+        });
+        assert_rect(&percentage, BORDER_BOX);
+    }
+
+    #[test]
+    fn test_polygon() {
+        let triangle = resolve(ClipPath::Polygon {
+            fill_rule: FillRule::NonZero,
+            points: vec![
+                [Units::Px(0.0), Units::Px(0.0)],
+                [Units::Perc(100.0), Units::Px(0.0)],
+                [Units::Perc(50.0), Units::Perc(100.0)],
+            ],
+        });
+        assert_eq!(
+            triangle,
+            ClipShape::Path {
+                rect: BORDER_BOX,
+                fill_rule: FillRule::NonZero,
+                markup: r#"<path d="M 10 20 L 110 20 L 60 70 Z"/>"#.to_string(),
+            }
+        );
+        assert!(triangle.contains([60.0, 30.0]));
+        assert!(!triangle.contains([15.0, 65.0]));
+        assert!(!triangle.contains([200.0, 30.0]));
+
+        // Where a path crosses itself, the even-odd rule leaves a hole.
+        let square = |fill_rule| ClipPath::Path {
+            fill_rule,
+            path: "M 0 0 H 50 V 50 H 0 Z M 10 10 H 40 V 40 H 10 Z".to_string(),
+        };
+        let nonzero = resolve(square(FillRule::NonZero));
+        let evenodd = resolve(square(FillRule::EvenOdd));
+        assert_rect(&nonzero, BORDER_BOX);
+        assert!(nonzero.contains([35.0, 45.0]));
+        assert!(!evenodd.contains([35.0, 45.0]));
+        assert!(evenodd.contains([15.0, 25.0]));
+        assert_eq!(
+            evenodd.svg([0.0, 0.0, 20.0, 10.0]),
+            Some(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10" viewBox="0 0 20 10"><g fill="white" fill-rule="evenodd"><path transform="translate(10 20)" d="M 0 0 H 50 V 50 H 0 Z M 10 10 H 40 V 40 H 10 Z"/></g></svg>"#
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            resolve(ClipPath::Circle {
+                radius: ShapeRadius::ClosestSide,
+                center: [Units::Perc(50.0), Units::Perc(50.0)],
+            })
+            .svg(BORDER_BOX),
+            None
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use calc_units::{Context, Units};
+
+use crate::{
+    rectangle::{InstanceData, Rectangle},
+    tree::{DrawList, State},
+};
+
+/// One function of `filter`, applied in order to the node and its descendants drawn together.
+/// Amounts follow CSS: 1 keeps the colors for `brightness`, `contrast`, `opacity` and
+/// `saturate`, and applies `grayscale`, `invert` and `sepia` fully.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Filter {
+    /// Gaussian blur with the given standard deviation.
+    Blur(Units),
+    Brightness(f32),
+    Contrast(f32),
+    Grayscale(f32),
+    /// Rotation in degrees.
+    HueRotate(f32),
+    Invert(f32),
+    Opacity(f32),
+    Saturate(f32),
+    Sepia(f32),
+    /// A blurred, tinted copy of the drawn alpha below it. A `None` color uses the font color,
+    /// like `currentColor`.
+    DropShadow {
+        offset: [Units; 2],
+        blur: Units,
+        color: Option<[f32; 4]>,
+    },
+}
+
+/// An affine transform of straight alpha colors: a row per output channel weighing red,
+/// green, blue and alpha, then adding the last column.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct ColorMatrix([[f32; 5]; 4]);
+
+impl ColorMatrix {
+    pub const IDENTITY: Self = Self([
+        [1.0, 0.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0, 0.0],
+    ]);
+
+    fn rgb(rows: [[f32; 3]; 3]) -> Self {
+        let mut matrix = Self::IDENTITY;
+        for (row, weights) in matrix.0.iter_mut().zip(rows) {
+            row[..3].copy_from_slice(&weights);
+        }
+        matrix
+    }
+
+    // Channel wise `value * slope + intercept`, alpha scaled by `alpha`.
+    fn linear(slope: f32, intercept: f32, alpha: f32) -> Self {
+        Self([
+            [slope, 0.0, 0.0, 0.0, intercept],
+            [0.0, slope, 0.0, 0.0, intercept],
+            [0.0, 0.0, slope, 0.0, intercept],
+            [0.0, 0.0, 0.0, alpha, 0.0],
+        ])
+    }
+
+    /// The matrices of the Filter Effects spec, `None` for filters that aren't one.
+    pub fn from_filter(filter: &Filter) -> Option<Self> {
+        let matrix = match *filter {
+            Filter::Brightness(amount) => Self::linear(amount.max(0.0), 0.0, 1.0),
+            Filter::Contrast(amount) => {
+                let amount = amount.max(0.0);
+                Self::linear(amount, 0.5 - 0.5 * amount, 1.0)
+            }
+            Filter::Invert(amount) => {
+                let amount = amount.clamp(0.0, 1.0);
+                Self::linear(1.0 - 2.0 * amount, amount, 1.0)
+            }
+            Filter::Opacity(amount) => Self::linear(1.0, 0.0, amount.clamp(0.0, 1.0)),
+            Filter::Grayscale(amount) => {
+                let a = 1.0 - amount.clamp(0.0, 1.0);
+                Self::rgb([
+                    [
+                        0.2126 + 0.7874 * a,
+                        0.7152 - 0.7152 * a,
+                        0.0722 - 0.0722 * a,
+                    ],
+                    [
+                        0.2126 - 0.2126 * a,
+                        0.7152 + 0.2848 * a,
+                        0.0722 - 0.0722 * a,
+                    ],
+                    [
+                        0.2126 - 0.2126 * a,
+                        0.7152 - 0.7152 * a,
+                        0.0722 + 0.9278 * a,
+                    ],
+                ])
+            }
+            Filter::Sepia(amount) => {
+                let a = 1.0 - amount.clamp(0.0, 1.0);
+                Self::rgb([
+                    [0.393 + 0.607 * a, 0.769 - 0.769 * a, 0.189 - 0.189 * a],
+                    [0.349 - 0.349 * a, 0.686 + 0.314 * a, 0.168 - 0.168 * a],
+                    [0.272 - 0.272 * a, 0.534 - 0.534 * a, 0.131 + 0.869 * a],
+                ])
+            }
+            Filter::Saturate(amount) => {
+                let s = amount.max(0.0);
+                Self::rgb([
+                    [0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s],
+                    [0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s],
+                    [0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s],
+                ])
+            }
+            Filter::HueRotate(degrees) => {
+                let (sin, cos) = degrees.to_radians().sin_cos();
+                Self::rgb([
+                    [
+                        0.213 + cos * 0.787 - sin * 0.213,
+                        0.715 - cos * 0.715 - sin * 0.715,
+                        0.072 - cos * 0.072 + sin * 0.928,
+                    ],
+                    [
+                        0.213 - cos * 0.213 + sin * 0.143,
+                        0.715 + cos * 0.285 + sin * 0.140,
+                        0.072 - cos * 0.072 - sin * 0.283,
+                    ],
+                    [
+                        0.213 - cos * 0.213 - sin * 0.787,
+                        0.715 - cos * 0.715 + sin * 0.715,
+                        0.072 + cos * 0.928 + sin * 0.072,
+                    ],
+                ])
+            }
+            Filter::Blur(_) | Filter::DropShadow { .. } => return None,
+        };
+        Some(matrix)
+    }
+
+    /// `self` followed by `next`.
+    pub fn then(self, next: Self) -> Self {
+        let mut matrix = [[0.0; 5]; 4];
+        for (row, next_row) in matrix.iter_mut().zip(next.0) {
+            for (column, value) in row.iter_mut().enumerate() {
+                *value = (0..4)
+                    .map(|channel| next_row[channel] * self.0[channel][column])
+                    .sum();
+            }
+            row[4] += next_row[4];
+        }
+        Self(matrix)
+    }
+
+    /// The column major 4x4 part and the offset column, as the shader takes them.
+    pub fn columns(&self) -> ([[f32; 4]; 4], [f32; 4]) {
+        let rows = &self.0;
+        let column = |column: usize| [0, 1, 2, 3].map(|row| rows[row][column]);
+        ([column(0), column(1), column(2), column(3)], column(4))
+    }
+}
+
+// Passes cover the viewport, each layer keeps what lands within its bounds.
+fn viewport(state: &State) -> [f32; 4] {
+    [
+        state.scroll.0,
+        state.scroll.1,
+        state.viewport.0,
+        state.viewport.1,
+    ]
+}
+
+// Queues the passes drawing `layer` through `filters`, returning the last one and the color
+// filters left to apply when compositing it. Runs of color filters fold into a single
+// matrix, blurs take a horizontal and a vertical pass, which repeat the edges of the
+// viewport with `duplicate_edges`.
+fn apply(
+    draw_list: &mut DrawList,
+    mut layer: usize,
+    filters: &[Filter],
+    context: &Context,
+    current_color: [f32; 4],
+    viewport: [f32; 4],
+    duplicate_edges: bool,
+) -> (usize, ColorMatrix) {
+    let blur = |draw_list: &mut DrawList, layer: usize, sigma: f32| match sigma > 0.0 {
+        true => {
+            let [horizontal, vertical] = [false, true].map(|vertical| {
+                InstanceData::layer_blur(viewport, sigma, vertical, duplicate_edges)
+            });
+            let layer = draw_list.pass(&[(layer, horizontal)]);
+            draw_list.pass(&[(layer, vertical)])
+        }
+        false => layer,
+    };
+
+    let mut matrix = ColorMatrix::IDENTITY;
+    for filter in filters {
+        if let Some(next) = ColorMatrix::from_filter(filter) {
+            matrix = matrix.then(next);
+            continue;
+        }
+
+        if matrix != ColorMatrix::IDENTITY {
+            layer = draw_list.pass(&[(layer, InstanceData::layer(viewport, &matrix))]);
+            matrix = ColorMatrix::IDENTITY;
+        }
+
+        match filter {
+            Filter::Blur(radius) => layer = blur(draw_list, layer, radius.to_px(context)),
+            Filter::DropShadow {
+                offset,
+                blur: radius,
+                color,
+            } => {
+                let offset = offset.each_ref().map(|offset| offset.to_px(context));
+                let color = color.unwrap_or(current_color);
+                let shadow = InstanceData::layer_shadow(viewport, offset, color);
+                let shadow = draw_list.pass(&[(layer, shadow)]);
+                // Like `box-shadow`, the blur radius is twice the standard deviation.
+                let shadow = blur(draw_list, shadow, radius.to_px(context) / 2.0);
+
+                let identity = InstanceData::layer(viewport, &ColorMatrix::IDENTITY);
+                layer = draw_list.pass(&[(shadow, identity), (layer, identity)]);
+            }
+            _ => {}
+        }
+    }
+
+    (layer, matrix)
+}
+
+/// Queues the passes drawing `layer` through the node's `filter`, the `mask` layer if any and
+/// then `opacity`, and composites the result into the layer being collected, within the
+/// `clip` instance if any.
+pub(crate) fn composite(
+    draw_list: &mut DrawList,
+    layer: usize,
+    node: &Rectangle,
+    clip: Option<InstanceData>,
+    mask: Option<usize>,
+    state: &State,
+) {
+    let viewport = viewport(state);
+    let context = node.paint_context(state);
+    let (layer, matrix) = apply(
+        draw_list,
+        layer,
+        &node.filter,
+        &context,
+        node.current_color(),
+        viewport,
+        false,
+    );
+
+    // The color filters left in `matrix` scale alpha at most, so they can go after the mask.
+    let layer = match mask {
+        Some(mask) => draw_list.masked_pass(layer, mask, InstanceData::masked_pass(viewport)),
+        None => layer,
+    };
+
+    let matrix = matrix.then(ColorMatrix::linear(1.0, 0.0, node.opacity));
+    let clip = clip.unwrap_or_else(|| InstanceData::layer(viewport, &ColorMatrix::IDENTITY));
+    draw_list.composite(layer, clip.with_color_matrix(&matrix));
+}
+
+/// Draws a closed `layer` into the layer being collected as its luminance, which is how
+/// `mask-mode: luminance` layers go into a mask.
+pub(crate) fn luminance(draw_list: &mut DrawList, layer: usize, state: &State) {
+    draw_list.composite(layer, InstanceData::luminance_layer(viewport(state)));
+}
+
+/// Splits off what was drawn into the layer being collected so far, for `backdrop`.
+pub(crate) fn snapshot(draw_list: &mut DrawList, state: &State) -> Option<usize> {
+    draw_list.snapshot(InstanceData::layer(viewport(state), &ColorMatrix::IDENTITY))
+}
+
+/// Like `composite` for `backdrop-filter`: draws `snapshot`, what was painted behind the
+/// node, through its `backdrop-filter` and into the layer being collected with the `clip`
+/// instance.
+pub(crate) fn backdrop(
+    draw_list: &mut DrawList,
+    snapshot: usize,
+    node: &Rectangle,
+    clip: InstanceData,
+    state: &State,
+) {
+    let viewport = viewport(state);
+    let context = node.paint_context(state);
+    let (layer, matrix) = apply(
+        draw_list,
+        snapshot,
+        &node.backdrop_filter,
+        &context,
+        node.current_color(),
+        viewport,
+        // What is behind the node goes on past the viewport.
+        true,
+    );
+
+    draw_list.composite(layer, clip.with_color_matrix(&matrix));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::tests::{items, quad, state};
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-3,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    fn filter(color: [f32; 4], filters: &[Filter]) -> [f32; 4] {
+        let matrix = filters
+            .iter()
+            .fold(ColorMatrix::IDENTITY, |matrix, filter| {
+                matrix.then(ColorMatrix::from_filter(filter).unwrap())
+            });
+        matrix
+            .0
+            .map(|row| (0..4).map(|i| row[i] * color[i]).sum::<f32>() + row[4])
+    }
+
+    #[test]
+    fn test_color_filters() {
+        let color = [0.2, 0.4, 0.6, 0.8];
+
+        // Amounts that keep the colors.
+        for keep in [
+            Filter::Brightness(1.0),
+            Filter::Contrast(1.0),
+            Filter::Grayscale(0.0),
+            Filter::HueRotate(360.0),
+            Filter::Invert(0.0),
+            Filter::Opacity(1.0),
+            Filter::Saturate(1.0),
+            Filter::Sepia(0.0),
+        ] {
+            assert_close(filter(color, &[keep]), color);
+        }
+
+        assert_close(
+            filter(color, &[Filter::Brightness(2.0)]),
+            [0.4, 0.8, 1.2, 0.8],
+        );
+        assert_close(
+            filter(color, &[Filter::Contrast(0.0)]),
+            [0.5, 0.5, 0.5, 0.8],
+        );
+        assert_close(filter(color, &[Filter::Invert(1.0)]), [0.8, 0.6, 0.4, 0.8]);
+        assert_close(filter(color, &[Filter::Opacity(0.5)]), [0.2, 0.4, 0.6, 0.4]);
+        let gray = 0.2126 * 0.2 + 0.7152 * 0.4 + 0.0722 * 0.6;
+        assert_close(
+            filter(color, &[Filter::Grayscale(1.0)]),
+            [gray, gray, gray, 0.8],
+        );
+        // Amounts past the end of the range are clamped.
+        assert_close(
+            filter(color, &[Filter::Invert(2.0)]),
+            filter(color, &[Filter::Invert(1.0)]),
+        );
+        assert!(ColorMatrix::from_filter(&Filter::Blur(Units::Px(1.0))).is_none());
+    }
+
+    #[test]
+    fn test_color_filter_order() {
+        let color = [0.2, 0.4, 0.6, 1.0];
+        // Each filter applies to the result of the one before.
+        let brighter = [Filter::Brightness(2.0), Filter::Invert(1.0)];
+        assert_close(filter(color, &brighter), [0.6, 0.2, -0.2, 1.0]);
+        let inverted = [Filter::Invert(1.0), Filter::Brightness(2.0)];
+        assert_close(filter(color, &inverted), [1.6, 1.2, 0.8, 1.0]);
+    }
+
+    // The passes drawing a layer through `filters`, as the layers each one reads.
+    fn passes(filters: Vec<Filter>) -> Vec<Vec<String>> {
+        let node = Rectangle {
+            filter: filters,
+            ..Default::default()
+        };
+        let state = state();
+        let mut draw_list = DrawList::default();
+        let parent = draw_list.begin_layer();
+        draw_list.extend_instances([quad([0.0; 4])]);
+        let layer = draw_list.end_layer(parent).unwrap();
+        composite(&mut draw_list, layer, &node, None, None, &state);
+
+        (2..draw_list.layers.len())
+            .map(|pass| items(&draw_list, pass))
+            .chain([items(&draw_list, 0)])
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_passes() {
+        // Color filters fold into the composite.
+        let color = vec![Filter::Grayscale(1.0), Filter::Opacity(0.5)];
+        assert_eq!(passes(color), [["layer 1"]]);
+
+        // A blur takes two passes, and color filters before it one more.
+        let blur = vec![
+            Filter::Sepia(1.0),
+            Filter::Blur(Units::Px(2.0)),
+            Filter::Invert(1.0),
+        ];
+        assert_eq!(
+            passes(blur),
+            [["layer 1"], ["layer 2"], ["layer 3"], ["layer 4"]]
+        );
+        assert_eq!(passes(vec![Filter::Blur(Units::Px(0.0))]), [["layer 1"]]);
+
+        // A drop shadow is drawn, blurred and then drawn under the layer.
+        let shadow = vec![Filter::DropShadow {
+            offset: [Units::Px(2.0), Units::Px(2.0)],
+            blur: Units::Px(4.0),
+            color: None,
+        }];
+        assert_eq!(
+            passes(shadow),
+            [
+                vec!["layer 1"],
+                vec!["layer 2"],
+                vec!["layer 3"],
+                vec!["layer 4", "layer 1"],
+                vec!["layer 5"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_opacity_composite() {
+        let node = Rectangle {
+            opacity: 0.5,
+            ..Default::default()
+        };
+        let mut draw_list = DrawList::default();
+        let parent = draw_list.begin_layer();
+        draw_list.extend_instances([quad([0.0; 4])]);
+        let layer = draw_list.end_layer(parent).unwrap();
+
+        // Opacity alone takes no pass, the composite applies it.
+        composite(&mut draw_list, layer, &node, None, None, &state());
+        assert_eq!(draw_list.layers.len(), 2);
+        assert_eq!(draw_list.order, [1]);
+        assert_eq!(items(&draw_list, 0), ["layer 1"]);
+    }
+
+    #[test]
+    fn test_backdrop_passes() {
+        let node = Rectangle {
+            backdrop_filter: vec![Filter::Blur(Units::Px(4.0)), Filter::Opacity(0.5)],
+            ..Default::default()
+        };
+        let state = state();
+        let mut draw_list = DrawList::default();
+        assert_eq!(snapshot(&mut draw_list, &state), None);
+
+        // What was drawn so far moves to a layer that is drawn back first, then blurred and
+        // drawn again within the node's clip.
+        draw_list.extend_instances([quad([0.0; 4]), quad([0.0; 4])]);
+        let behind = snapshot(&mut draw_list, &state).unwrap();
+        assert_eq!(items(&draw_list, behind), ["0..2"]);
+        backdrop(&mut draw_list, behind, &node, quad([0.0; 4]), &state);
+        assert_eq!(draw_list.order, [1, 2, 3]);
+        assert_eq!(items(&draw_list, 2), ["layer 1"]);
+        assert_eq!(items(&draw_list, 3), ["layer 2"]);
+        assert_eq!(items(&draw_list, 0), ["layer 1", "layer 3"]);
+    }
+}
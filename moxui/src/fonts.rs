@@ -0,0 +1,478 @@
+use std::{
+    collections::HashMap,
+    io,
+    ops::{Range, RangeInclusive},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use glyphon::{
+    cosmic_text::CacheKeyFlags, fontdb, Attrs, AttrsOwned, Buffer, Family, FamilyOwned, FontSystem,
+    Metrics, Shaping, Stretch,
+};
+
+/// An `@font-face` rule: a face to load under a family name of the stylesheet's choosing.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FontFace {
+    /// The name `font-family` refers to the face by, whatever the font calls itself.
+    pub family: String,
+    /// Where to load the face from, tried in order until one loads.
+    pub src: Vec<FontSource>,
+    /// Overrides the weight the font declares.
+    pub weight: Option<fontdb::Weight>,
+    /// Overrides the style the font declares.
+    pub style: Option<fontdb::Style>,
+    /// The code points the face is used for, all of them when empty.
+    pub unicode_range: Vec<RangeInclusive<u32>>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum FontSource {
+    /// `url()`, read from a file like the `src` of an `<img>`.
+    Url(PathBuf),
+    /// `local()`, a family or PostScript name of a font already in the database.
+    Local(String),
+}
+
+/// `font-variant`: which letters are drawn as small capitals. Fonts can't be asked for their
+/// own small capitals, so they are synthesized from shrunken capitals like browsers do for
+/// fonts without them.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum FontVariant {
+    #[default]
+    Normal,
+    /// Lowercase letters.
+    SmallCaps,
+    /// Lowercase and uppercase letters.
+    AllSmallCaps,
+}
+
+/// The stretch closest to a width in percent of the normal width.
+pub fn stretch(percentage: f32) -> Stretch {
+    const STRETCHES: [(f32, Stretch); 9] = [
+        (50.0, Stretch::UltraCondensed),
+        (62.5, Stretch::ExtraCondensed),
+        (75.0, Stretch::Condensed),
+        (87.5, Stretch::SemiCondensed),
+        (100.0, Stretch::Normal),
+        (112.5, Stretch::SemiExpanded),
+        (125.0, Stretch::Expanded),
+        (150.0, Stretch::ExtraExpanded),
+        (200.0, Stretch::UltraExpanded),
+    ];
+
+    STRETCHES
+        .into_iter()
+        .min_by(|(a, _), (b, _)| (a - percentage).abs().total_cmp(&(b - percentage).abs()))
+        .map(|(_, stretch)| stretch)
+        .unwrap()
+}
+
+// How much smaller synthesized small capitals are than capitals.
+const SMALL_CAPS_SCALE: f32 = 0.7;
+
+// A face registered by an `@font-face` rule. It is renamed in the database so that shaping
+// with `name` picks exactly this face, even when several faces of `family` only differ in
+// their `unicode-range`.
+struct Face {
+    family: String,
+    name: String,
+    id: fontdb::ID,
+    unicode_range: Vec<RangeInclusive<u32>>,
+}
+
+/// The fonts the text of a tree is shaped with: a `FontSystem` with whatever fonts the
+/// embedder loaded, plus the faces of `@font-face` rules. Text is shaped again with fonts
+/// loaded here at the next relayout.
+pub struct Fonts {
+    pub font_system: FontSystem,
+    faces: Vec<Face>,
+    // Installed faces by lowercased family name, with the name as the database spells it.
+    families: HashMap<String, Option<(String, Vec<fontdb::ID>)>>,
+    // Bumped by every change to the fonts, so shaping done before it is redone.
+    pub(crate) generation: u64,
+}
+
+impl Fonts {
+    pub fn new(font_system: FontSystem) -> Self {
+        Self {
+            font_system,
+            faces: Vec::new(),
+            families: HashMap::new(),
+            generation: 0,
+        }
+    }
+
+    /// Registers the faces of a font file or collection under the names the font declares.
+    pub fn load_font_data(&mut self, data: Vec<u8>) {
+        self.font_system.db_mut().load_font_data(data);
+        self.changed();
+    }
+
+    /// Like `load_font_data`, reading the font from `path`.
+    pub fn load_font_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.font_system.db_mut().load_font_file(path)?;
+        self.changed();
+        Ok(())
+    }
+
+    /// Registers the faces of the first source of `face` that loads. Fails when none does, in
+    /// which case text falls back to the next family of its `font-family` like in a browser.
+    pub fn load_font_face(&mut self, face: &FontFace) -> io::Result<()> {
+        let infos = face
+            .src
+            .iter()
+            .find_map(|source| self.source_faces(source))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no source of the `{}` font face loads", face.family),
+                )
+            })?;
+
+        let db = self.font_system.db_mut();
+        for mut info in infos {
+            let name = format!("{} #{}", face.family, self.faces.len());
+            info.families = vec![(name.clone(), fontdb::Language::English_UnitedStates)];
+            info.weight = face.weight.unwrap_or(info.weight);
+            info.style = face.style.unwrap_or(info.style);
+            db.push_face_info(info);
+
+            // `push_face_info` doesn't hand out the id it assigned, but the name is unique.
+            let id = db
+                .faces()
+                .find(|info| info.families[0].0 == name)
+                .map(|info| info.id)
+                .expect("the face was just pushed");
+
+            self.faces.push(Face {
+                family: face.family.to_lowercase(),
+                name,
+                id,
+                unicode_range: face.unicode_range.clone(),
+            });
+        }
+
+        self.changed();
+        Ok(())
+    }
+
+    // The faces `source` holds, taken out of the database when they were loaded just for it.
+    fn source_faces(&mut self, source: &FontSource) -> Option<Vec<fontdb::FaceInfo>> {
+        let db = self.font_system.db_mut();
+
+        let infos = match source {
+            FontSource::Url(path) => {
+                let data = std::fs::read(path).ok()?;
+                let ids = db.load_font_source(fontdb::Source::Binary(Arc::new(data)));
+                let infos = ids
+                    .iter()
+                    .filter_map(|&id| db.face(id).cloned())
+                    .collect::<Vec<_>>();
+                ids.into_iter().for_each(|id| db.remove_face(id));
+                infos
+            }
+            FontSource::Local(name) => db
+                .faces()
+                .filter(|info| {
+                    info.post_script_name == *name
+                        || info.families.iter().any(|(family, _)| family == name)
+                })
+                .cloned()
+                .collect(),
+        };
+
+        (!infos.is_empty()).then_some(infos)
+    }
+
+    fn changed(&mut self) {
+        // Runs shaped before may have fallen back to other fonts than they would now.
+        self.font_system.shape_run_cache = Default::default();
+        self.families.clear();
+        self.generation += 1;
+    }
+
+    /// Splits `text` into runs of `attrs`, each with the first of `families` that has glyphs
+    /// for its characters. Characters none of them cover go to the last family, usually a
+    /// generic one, where shaping falls back to whatever font has them.
+    pub(crate) fn resolve(
+        &mut self,
+        text: &str,
+        attrs: Attrs,
+        families: &[FamilyOwned],
+    ) -> Vec<(Range<usize>, AttrsOwned)> {
+        let mut runs: Vec<(Range<usize>, AttrsOwned)> = Vec::new();
+
+        for (i, c) in text.char_indices() {
+            let end = i + c.len_utf8();
+
+            // Spaces don't split a run, so words on both sides shape together.
+            if c.is_whitespace() {
+                if let Some((range, _)) = runs.last_mut() {
+                    range.end = end;
+                    continue;
+                }
+            }
+
+            let run = families
+                .iter()
+                .find_map(|family| self.covering(family, c, attrs))
+                .unwrap_or_else(|| {
+                    let family = families.last().cloned().unwrap_or(FamilyOwned::SansSerif);
+                    self.fallback(attrs.family(family.as_family()))
+                });
+
+            match runs.last_mut() {
+                Some((range, last)) if *last == run => range.end = end,
+                _ => runs.push((i..end, run)),
+            }
+        }
+
+        runs
+    }
+
+    // `attrs` with the face of `family` that has a glyph for `c` and best matches the
+    // requested weight, style and stretch. Names declared by `@font-face` rules refer to
+    // those faces only, later rules winning ties.
+    fn covering(&mut self, family: &FamilyOwned, c: char, attrs: Attrs) -> Option<AttrsOwned> {
+        let name = match family {
+            FamilyOwned::Name(name) => name.to_lowercase(),
+            generic => self
+                .font_system
+                .db()
+                .family_name(&generic.as_family())
+                .to_lowercase(),
+        };
+
+        let faces = if self.faces.iter().any(|face| face.family == name) {
+            self.faces
+                .iter()
+                .rev()
+                .filter(|face| face.family == name)
+                .filter(|face| {
+                    face.unicode_range.is_empty()
+                        || face
+                            .unicode_range
+                            .iter()
+                            .any(|range| range.contains(&(c as u32)))
+                })
+                .map(|face| (face.name.clone(), face.id))
+                .collect::<Vec<_>>()
+        } else {
+            let db = self.font_system.db();
+            let (installed, ids) = self
+                .families
+                .entry(name.clone())
+                .or_insert_with(|| {
+                    let ids = db
+                        .faces()
+                        .filter(|info| {
+                            info.families
+                                .iter()
+                                .any(|(family, _)| family.to_lowercase() == name)
+                        })
+                        .map(|info| info.id)
+                        .collect::<Vec<_>>();
+                    let installed = db
+                        .face(*ids.first()?)?
+                        .families
+                        .iter()
+                        .find(|(family, _)| family.to_lowercase() == name)?
+                        .0
+                        .clone();
+                    Some((installed, ids))
+                })
+                .as_ref()?;
+            ids.iter().map(|&id| (installed.clone(), id)).collect()
+        };
+
+        let faces = faces
+            .into_iter()
+            .filter(|&(_, id)| has_glyph(&mut self.font_system, id, c))
+            .collect::<Vec<_>>();
+        let db = self.font_system.db();
+        let (name, info) = faces
+            .iter()
+            .filter_map(|(name, id)| Some((name, db.face(*id)?)))
+            .min_by_key(|(_, info)| match_key(info, &attrs))?;
+
+        Some(face_attrs(attrs.family(Family::Name(name)), info))
+    }
+
+    /// Prefixes `text` with no-break spaces in the face of its first run that add up to
+    /// `indent`, the last one shrunk to fit, as shaping can't indent lines. Sizes are those of
+    /// `metrics`, the metrics of the text.
+    pub(crate) fn indent(
+        &mut self,
+        text: &str,
+        runs: Vec<(Range<usize>, AttrsOwned)>,
+        indent: f32,
+        metrics: Metrics,
+    ) -> (String, Vec<(Range<usize>, AttrsOwned)>) {
+        let Some((attrs, advance)) = runs.first().and_then(|(_, attrs)| {
+            let advance = self.space_advance(attrs.as_attrs())?;
+            Some((attrs.clone(), advance))
+        }) else {
+            return (text.to_string(), runs);
+        };
+
+        let metrics = attrs.metrics_opt.map_or(metrics, Metrics::from);
+        let width = advance * metrics.font_size;
+        let count = (indent / width).floor();
+        let rest = indent - count * width;
+
+        let mut sizes = vec![metrics.font_size; count as usize];
+        if rest > 0.01 {
+            sizes.push(rest / advance);
+        }
+
+        let mut indented = String::with_capacity(text.len() + sizes.len() * 2);
+        let mut indented_runs: Vec<(Range<usize>, AttrsOwned)> = Vec::new();
+        for size in sizes {
+            let start = indented.len();
+            indented.push('\u{a0}');
+            let attrs = AttrsOwned::new(
+                attrs
+                    .as_attrs()
+                    .metrics(Metrics::new(size, metrics.line_height)),
+            );
+
+            match indented_runs.last_mut() {
+                Some((range, last)) if *last == attrs => range.end = indented.len(),
+                _ => indented_runs.push((start..indented.len(), attrs)),
+            }
+        }
+
+        let offset = indented.len();
+        indented.push_str(text);
+        indented_runs.extend(
+            runs.into_iter()
+                .map(|(range, attrs)| (range.start + offset..range.end + offset, attrs)),
+        );
+
+        (indented, indented_runs)
+    }
+
+    // Advance of a no-break space shaped with `attrs`, relative to the font size. It is
+    // measured by shaping, so faces that shaping falls back to count too.
+    fn space_advance(&mut self, attrs: Attrs) -> Option<f32> {
+        let metrics = Metrics::new(100.0, 100.0);
+        let mut buffer = Buffer::new(&mut self.font_system, metrics);
+        buffer.set_text(
+            &mut self.font_system,
+            "\u{a0}",
+            attrs.metrics(metrics),
+            Shaping::Advanced,
+        );
+        let advance = buffer.layout_runs().next()?.glyphs.first()?.w;
+
+        Some(advance / metrics.font_size)
+    }
+
+    // `attrs` for characters no family covers. Shaping falls back to fonts with exactly the
+    // weight, style and stretch asked for, so they are those of the closest installed face.
+    fn fallback(&self, attrs: Attrs) -> AttrsOwned {
+        match self
+            .font_system
+            .db()
+            .faces()
+            .min_by_key(|info| match_key(info, &attrs))
+        {
+            Some(info) => face_attrs(attrs, info),
+            None => AttrsOwned::new(attrs),
+        }
+    }
+}
+
+// Shaping only picks a face whose weight, style and stretch match exactly, so `attrs` takes
+// those of `info`, slanting upright faces when an italic one was asked for.
+fn face_attrs(attrs: Attrs, info: &fontdb::FaceInfo) -> AttrsOwned {
+    let mut flags = attrs.cache_key_flags;
+    if attrs.style != fontdb::Style::Normal && info.style == fontdb::Style::Normal {
+        flags |= CacheKeyFlags::FAKE_ITALIC;
+    }
+
+    AttrsOwned::new(
+        attrs
+            .weight(info.weight)
+            .style(info.style)
+            .stretch(info.stretch)
+            .cache_key_flags(flags),
+    )
+}
+
+// Orders faces like CSS font matching: the closest stretch first, then the style, then the
+// weight, where weights up to 500 look lighter first and heavier ones look heavier first.
+fn match_key(info: &fontdb::FaceInfo, attrs: &Attrs) -> (u16, u8, u8, u16) {
+    let stretch = info.stretch.to_number().abs_diff(attrs.stretch.to_number());
+
+    let style = match (attrs.style, info.style) {
+        (wanted, style) if wanted == style => 0,
+        (fontdb::Style::Normal, fontdb::Style::Oblique)
+        | (fontdb::Style::Italic | fontdb::Style::Oblique, fontdb::Style::Italic)
+        | (fontdb::Style::Italic | fontdb::Style::Oblique, fontdb::Style::Oblique) => 1,
+        _ => 2,
+    };
+
+    let (wanted, weight) = (attrs.weight.0, info.weight.0);
+    let (tier, distance) = match wanted {
+        400..=500 if (wanted..=500).contains(&weight) => (0, weight - wanted),
+        ..=500 if weight <= wanted => (1, wanted - weight),
+        ..=500 => (2, weight - wanted),
+        _ if weight >= wanted => (0, weight - wanted),
+        _ => (1, wanted - weight),
+    };
+
+    (stretch, style, tier, distance)
+}
+
+/// Turns the letters `variant` draws as small capitals into shrunken capitals, returning the
+/// text to shape and its runs. Sizes are those of `metrics`, the metrics of the text.
+pub(crate) fn small_caps(
+    text: &str,
+    runs: Vec<(Range<usize>, AttrsOwned)>,
+    variant: FontVariant,
+    metrics: Metrics,
+) -> (String, Vec<(Range<usize>, AttrsOwned)>) {
+    if variant == FontVariant::Normal {
+        return (text.to_string(), runs);
+    }
+
+    let small = Metrics::new(metrics.font_size * SMALL_CAPS_SCALE, metrics.line_height);
+    let mut shaped = String::with_capacity(text.len());
+    let mut small_runs: Vec<(Range<usize>, AttrsOwned)> = Vec::new();
+
+    for (range, attrs) in runs {
+        for c in text[range].chars() {
+            let shrink =
+                c.is_lowercase() || variant == FontVariant::AllSmallCaps && c.is_uppercase();
+            let start = shaped.len();
+            let attrs = if shrink {
+                shaped.extend(c.to_uppercase());
+                AttrsOwned::new(attrs.as_attrs().metrics(small))
+            } else {
+                shaped.push(c);
+                attrs.clone()
+            };
+
+            match small_runs.last_mut() {
+                Some((range, last)) if *last == attrs => range.end = shaped.len(),
+                _ => small_runs.push((start..shaped.len(), attrs)),
+            }
+        }
+    }
+
+    (shaped, small_runs)
+}
+
+impl From<FontSystem> for Fonts {
+    fn from(font_system: FontSystem) -> Self {
+        Self::new(font_system)
+    }
+}
+
+fn has_glyph(font_system: &mut FontSystem, id: fontdb::ID, c: char) -> bool {
+    font_system
+        .get_font(id)
+        .is_some_and(|font| font.rustybuzz().glyph_index(c).is_some())
+}
@@ -0,0 +1,387 @@
+use calc_units::{Context, Units};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LinearDirection {
+    /// CSS angle in degrees, `0` pointing up and increasing clockwise.
+    Angle(f32),
+    /// `to <corner>`, with -1 for left/top and 1 for right/bottom. The angle depends on the
+    /// aspect ratio of the box.
+    Corner(f32, f32),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RadialShape {
+    Circle,
+    Ellipse,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RadialSize {
+    ClosestSide,
+    FarthestSide,
+    ClosestCorner,
+    FarthestCorner,
+    /// Explicit radii, a circle only uses the first one.
+    Radii([Units; 2]),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum GradientKind {
+    Linear(LinearDirection),
+    Radial {
+        shape: RadialShape,
+        size: RadialSize,
+        position: [Units; 2],
+    },
+    Conic {
+        /// Starting angle in degrees.
+        from: f32,
+        position: [Units; 2],
+    },
+}
+
+/// A color stop. Positions are along the gradient line, or the turn for conic gradients where
+/// only percentages are meaningful. Missing positions are spread evenly like in CSS.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ColorStop {
+    pub color: [f32; 4],
+    pub position: Option<Units>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<ColorStop>,
+    pub repeating: bool,
+}
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GradientStop {
+    color: [f32; 4],
+    position: f32,
+    _padding: [f32; 3],
+}
+
+// Values of `InstanceData::kind` for gradients, matching the constants in the shader.
+pub(crate) const KIND_LINEAR_GRADIENT: u32 = 3;
+pub(crate) const KIND_RADIAL_GRADIENT: u32 = 4;
+pub(crate) const KIND_CONIC_GRADIENT: u32 = 5;
+
+/// A gradient laid out over a box, ready for the shader.
+pub(crate) struct ResolvedGradient {
+    pub kind: u32,
+    /// Linear: center and direction scaled by the inverse line length. Radial: center and
+    /// radii. Conic: center and starting angle in radians.
+    pub params: [f32; 4],
+    pub stops: Vec<GradientStop>,
+}
+
+impl Gradient {
+    /// Resolves the geometry and stop positions for a box given as x, y, width, height.
+    pub(crate) fn resolve(&self, area: [f32; 4], context: &Context) -> ResolvedGradient {
+        let [x, y, width, height] = area;
+        let center = [x + width / 2.0, y + height / 2.0];
+
+        let position = |position: &[Units; 2]| {
+            [
+                x + position[0].to_px(&Context {
+                    reference_size: width,
+                    ..*context
+                }),
+                y + position[1].to_px(&Context {
+                    reference_size: height,
+                    ..*context
+                }),
+            ]
+        };
+
+        let (kind, params, length) = match &self.kind {
+            GradientKind::Linear(direction) => {
+                let angle = match *direction {
+                    LinearDirection::Angle(degrees) => degrees.to_radians(),
+                    // The line is perpendicular to the diagonal between the other two corners.
+                    LinearDirection::Corner(horizontal, vertical) => {
+                        (horizontal * height).atan2(-vertical * width)
+                    }
+                };
+
+                let (sin, cos) = angle.sin_cos();
+                let length = (width * sin).abs() + (height * cos).abs();
+                let length = length.max(f32::EPSILON);
+
+                (
+                    KIND_LINEAR_GRADIENT,
+                    [center[0], center[1], sin / length, -cos / length],
+                    length,
+                )
+            }
+            GradientKind::Radial {
+                shape,
+                size,
+                position: center,
+            } => {
+                let center = position(center);
+                let sides = [
+                    (center[0] - x).abs(),
+                    (x + width - center[0]).abs(),
+                    (center[1] - y).abs(),
+                    (y + height - center[1]).abs(),
+                ];
+                let closest = [sides[0].min(sides[1]), sides[2].min(sides[3])];
+                let farthest = [sides[0].max(sides[1]), sides[2].max(sides[3])];
+
+                let radii = match (shape, size) {
+                    (RadialShape::Circle, RadialSize::ClosestSide) => {
+                        [closest[0].min(closest[1]); 2]
+                    }
+                    (RadialShape::Circle, RadialSize::FarthestSide) => {
+                        [farthest[0].max(farthest[1]); 2]
+                    }
+                    (RadialShape::Circle, RadialSize::ClosestCorner) => {
+                        [closest[0].hypot(closest[1]); 2]
+                    }
+                    (RadialShape::Circle, RadialSize::FarthestCorner) => {
+                        [farthest[0].hypot(farthest[1]); 2]
+                    }
+                    (RadialShape::Ellipse, RadialSize::ClosestSide) => closest,
+                    (RadialShape::Ellipse, RadialSize::FarthestSide) => farthest,
+                    // Keeps the aspect ratio of the side ellipse while passing through the corner.
+                    (RadialShape::Ellipse, RadialSize::ClosestCorner) => {
+                        closest.map(|r| r * std::f32::consts::SQRT_2)
+                    }
+                    (RadialShape::Ellipse, RadialSize::FarthestCorner) => {
+                        farthest.map(|r| r * std::f32::consts::SQRT_2)
+                    }
+                    (RadialShape::Circle, RadialSize::Radii([radius, _])) => {
+                        [radius.to_px(context); 2]
+                    }
+                    (RadialShape::Ellipse, RadialSize::Radii([rx, ry])) => [
+                        rx.to_px(&Context {
+                            reference_size: width,
+                            ..*context
+                        }),
+                        ry.to_px(&Context {
+                            reference_size: height,
+                            ..*context
+                        }),
+                    ],
+                };
+                let radii = radii.map(|r| r.max(f32::EPSILON));
+
+                (
+                    KIND_RADIAL_GRADIENT,
+                    [center[0], center[1], radii[0], radii[1]],
+                    radii[0],
+                )
+            }
+            GradientKind::Conic {
+                from,
+                position: center,
+            } => {
+                let center = position(center);
+                (
+                    KIND_CONIC_GRADIENT,
+                    [center[0], center[1], from.to_radians(), 0.0],
+                    1.0,
+                )
+            }
+        };
+
+        ResolvedGradient {
+            kind,
+            params,
+            stops: self.resolve_stops(length, context),
+        }
+    }
+
+    // Converts stop positions to fractions of the gradient line, filling in missing ones and
+    // keeping them in order.
+    fn resolve_stops(&self, length: f32, context: &Context) -> Vec<GradientStop> {
+        let last = self.stops.len().saturating_sub(1);
+        let mut positions = self
+            .stops
+            .iter()
+            .enumerate()
+            .map(|(i, stop)| match &stop.position {
+                Some(position) => Some(
+                    position.to_px(&Context {
+                        reference_size: length,
+                        ..*context
+                    }) / length,
+                ),
+                None if i == 0 => Some(0.0),
+                None if i == last => Some(1.0),
+                None => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut max = f32::MIN;
+        positions.iter_mut().flatten().for_each(|position| {
+            max = max.max(*position);
+            *position = max;
+        });
+
+        let mut i = 0;
+        while i < positions.len() {
+            if positions[i].is_some() {
+                i += 1;
+                continue;
+            }
+
+            let start = i - 1;
+            let end = (i..positions.len())
+                .find(|&j| positions[j].is_some())
+                .unwrap_or(last);
+            let (from, to) = (
+                positions[start].unwrap_or(0.0),
+                positions[end].unwrap_or(1.0),
+            );
+
+            (i..end).for_each(|j| {
+                let t = (j - start) as f32 / (end - start) as f32;
+                positions[j] = Some(from + (to - from) * t);
+            });
+            i = end;
+        }
+
+        self.stops
+            .iter()
+            .zip(positions)
+            .map(|(stop, position)| GradientStop {
+                color: stop.color,
+                position: position.unwrap_or(0.0),
+                _padding: [0.0; 3],
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTEXT: Context = Context {
+        root_font_size: 16.0,
+        reference_size: 0.0,
+        parent_font_size: 16.0,
+        viewport: (800.0, 600.0),
+        auto: 0.0,
+        dpi: 96.0,
+    };
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-4,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    fn gradient(kind: GradientKind, positions: &[Option<Units>]) -> Gradient {
+        Gradient {
+            kind,
+            stops: positions
+                .iter()
+                .map(|position| ColorStop {
+                    color: [1.0; 4],
+                    position: position.clone(),
+                })
+                .collect(),
+            repeating: false,
+        }
+    }
+
+    fn positions(resolved: &ResolvedGradient) -> Vec<f32> {
+        resolved.stops.iter().map(|stop| stop.position).collect()
+    }
+
+    #[test]
+    fn test_linear_line() {
+        let down = gradient(GradientKind::Linear(LinearDirection::Angle(180.0)), &[]);
+        let resolved = down.resolve([10.0, 20.0, 100.0, 50.0], &CONTEXT);
+        assert_eq!(resolved.kind, KIND_LINEAR_GRADIENT);
+        // The line runs through the center, as long as the box is high.
+        assert_close(&resolved.params, &[60.0, 45.0, 0.0, 1.0 / 50.0]);
+
+        // Corners of a square are along the diagonal.
+        let corner = gradient(GradientKind::Linear(LinearDirection::Corner(1.0, 1.0)), &[]);
+        let resolved = corner.resolve([0.0, 0.0, 100.0, 100.0], &CONTEXT);
+        let length = 100.0 * std::f32::consts::SQRT_2;
+        let step = std::f32::consts::FRAC_1_SQRT_2 / length;
+        assert_close(&resolved.params, &[50.0, 50.0, step, step]);
+    }
+
+    #[test]
+    fn test_radial_size() {
+        let radial = |shape, size, position| {
+            let kind = GradientKind::Radial {
+                shape,
+                size,
+                position,
+            };
+            gradient(kind, &[]).resolve([0.0, 0.0, 100.0, 50.0], &CONTEXT)
+        };
+        let center = [Units::Perc(50.0), Units::Perc(50.0)];
+        let corner = [Units::Px(0.0), Units::Px(0.0)];
+
+        let resolved = radial(RadialShape::Circle, RadialSize::ClosestSide, center.clone());
+        assert_eq!(resolved.kind, KIND_RADIAL_GRADIENT);
+        assert_close(&resolved.params, &[50.0, 25.0, 25.0, 25.0]);
+        let resolved = radial(RadialShape::Ellipse, RadialSize::FarthestSide, center);
+        assert_close(&resolved.params, &[50.0, 25.0, 50.0, 25.0]);
+        let resolved = radial(
+            RadialShape::Circle,
+            RadialSize::FarthestCorner,
+            corner.clone(),
+        );
+        let diagonal = 100.0f32.hypot(50.0);
+        assert_close(&resolved.params, &[0.0, 0.0, diagonal, diagonal]);
+        let resolved = radial(RadialShape::Ellipse, RadialSize::FarthestCorner, corner);
+        let scale = std::f32::consts::SQRT_2;
+        assert_close(&resolved.params, &[0.0, 0.0, 100.0 * scale, 50.0 * scale]);
+        let radii = RadialSize::Radii([Units::Perc(10.0), Units::Perc(10.0)]);
+        let resolved = radial(
+            RadialShape::Ellipse,
+            radii,
+            [Units::Px(0.0), Units::Px(0.0)],
+        );
+        assert_close(&resolved.params, &[0.0, 0.0, 10.0, 5.0]);
+    }
+
+    #[test]
+    fn test_conic_start() {
+        let conic = GradientKind::Conic {
+            from: 90.0,
+            position: [Units::Px(10.0), Units::Perc(100.0)],
+        };
+        let resolved = gradient(conic, &[]).resolve([0.0, 0.0, 100.0, 50.0], &CONTEXT);
+        assert_eq!(resolved.kind, KIND_CONIC_GRADIENT);
+        assert_close(
+            &resolved.params,
+            &[10.0, 50.0, std::f32::consts::FRAC_PI_2, 0.0],
+        );
+    }
+
+    #[test]
+    fn test_stop_positions() {
+        let kind = GradientKind::Linear(LinearDirection::Angle(90.0));
+        let resolve = |stops: &[Option<Units>]| {
+            positions(&gradient(kind.clone(), stops).resolve([0.0, 0.0, 200.0, 10.0], &CONTEXT))
+        };
+
+        // Missing positions are spread evenly between the ones around them.
+        assert_close(&resolve(&[None, None, None]), &[0.0, 0.5, 1.0]);
+        assert_close(
+            &resolve(&[None, Some(Units::Px(100.0)), None, None]),
+            &[0.0, 0.5, 0.75, 1.0],
+        );
+        // A position before an earlier one moves up to it.
+        assert_close(
+            &resolve(&[Some(Units::Perc(40.0)), None, Some(Units::Perc(20.0))]),
+            &[0.4, 0.4, 0.4],
+        );
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    fonts::{FontFace, FontSource},
+    images::ImageCache,
+    parser::{self, Stylesheet},
+    rectangle::Display,
+    text::{WhiteSpace, LINE_BREAK},
+    tree::node::Node,
+};
+
+#[derive(Debug)]
+pub struct Error {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for Error {}
+
+enum Content {
+    Element(Element),
+    Text(String),
+}
+
+struct Element {
+    tag: String,
+    id: Option<String>,
+    class: Vec<String>,
+    style: Option<String>,
+    src: Option<String>,
+    children: Vec<Content>,
+}
+
+/// A parsed HTML fragment together with the rules from its `<style>` blocks.
+///
+/// Parsing and building are separate so a document can be parsed once and handed to
+/// `Tree::new` as `|node| document.build(node)`.
+pub struct Document {
+    children: Vec<Content>,
+    stylesheet: Stylesheet,
+    // Decodes the images of the document once, relative to its directory.
+    images: ImageCache,
+}
+
+const VOID_ELEMENTS: &[&str] = &["img", "input", "br", "meta", "link"];
+
+// Elements that only carry metadata and never produce a node.
+const HIDDEN_ELEMENTS: &[&str] = &["head", "title", "meta", "link", "style"];
+
+// Elements closed by the end of their parent when their own end tag is left out.
+const OPTIONAL_END_TAGS: &[&str] = &["p", "li", "tr", "td", "th", "option"];
+
+// Whether the start tag `tag` closes an open `parent` whose end tag was left out.
+fn closed_by(parent: &str, tag: &str) -> bool {
+    match parent {
+        "p" => [
+            "div", "p", "h1", "h2", "h3", "h4", "h5", "h6", "ul", "ol", "li", "pre", "table", "tr",
+            "td", "th",
+        ]
+        .contains(&tag),
+        "li" => tag == "li",
+        "tr" => tag == "tr",
+        "td" | "th" => matches!(tag, "tr" | "td" | "th"),
+        "option" => tag == "option",
+        _ => false,
+    }
+}
+
+const ELEMENTS: &[&str] = &[
+    "html", "head", "body", "title", "meta", "link", "style", "div", "span", "p", "h1", "h2", "h3",
+    "h4", "h5", "h6", "ul", "ol", "li", "img", "button", "input", "br", "b", "strong", "i", "em",
+    "pre", "table", "thead", "tbody", "tfoot", "tr", "td", "th", "select", "option",
+];
+
+fn user_agent_style(tag: &str) -> &'static str {
+    match tag {
+        "html" | "div" | "li" => "display: block",
+        "body" => "display: block; margin: 8px",
+        "p" => "display: block; margin: 1em 0",
+        "h1" => "display: block; font-weight: bold; font-size: 2em; margin: 0.67em 0",
+        "h2" => "display: block; font-weight: bold; font-size: 1.5em; margin: 0.83em 0",
+        "h3" => "display: block; font-weight: bold; font-size: 1.17em; margin: 1em 0",
+        "h4" => "display: block; font-weight: bold; margin: 1.33em 0",
+        "h5" => "display: block; font-weight: bold; font-size: 0.83em; margin: 1.67em 0",
+        "h6" => "display: block; font-weight: bold; font-size: 0.67em; margin: 2.33em 0",
+        "ul" | "ol" => "display: block; margin: 1em 0; padding-left: 40px",
+        "span" => "display: inline",
+        "b" | "strong" => "display: inline; font-weight: bolder",
+        "i" | "em" => "display: inline; font-style: italic",
+        "img" => "display: inline-block",
+        "button" => "display: inline-block; padding: 1px 6px; border: 2px outset #767676",
+        "input" => "display: inline-block; padding: 1px 2px; border: 2px inset #767676",
+        "br" => "display: inline",
+        "pre" => "display: block; font-family: monospace; white-space: pre; margin: 1em 0",
+        // Without table layout, rows stack as blocks of inline-block cells.
+        "table" | "thead" | "tbody" | "tfoot" | "tr" => "display: block",
+        "td" => "display: inline-block; padding: 1px",
+        "th" => "display: inline-block; padding: 1px; font-weight: bold",
+        "select" => "display: inline-block; border: 1px solid #767676",
+        "option" => "display: block",
+        _ => "",
+    }
+}
+
+struct Tokenizer<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            message: message.into(),
+            position: self.position,
+        }
+    }
+
+    fn skip_past(&mut self, pattern: &str) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        let end = rest
+            .find(pattern)
+            .ok_or_else(|| self.error(format!("expected `{pattern}`")))?;
+        self.position += end + pattern.len();
+        Ok(&rest[..end])
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn name(&mut self) -> &'a str {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(rest.len());
+        self.position += end;
+        &rest[..end]
+    }
+
+    fn attribute_value(&mut self) -> Result<String, Error> {
+        let rest = self.rest();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                self.position += 1;
+                self.skip_past(&quote.to_string())?
+            }
+            _ => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+                    .unwrap_or(rest.len());
+                self.position += end;
+                &rest[..end]
+            }
+        };
+
+        Ok(decode_entities(value))
+    }
+
+    // Parses children until the closing tag of `parent`, or the end of input at the top level.
+    fn children(
+        &mut self,
+        parent: Option<&str>,
+        styles: &mut String,
+    ) -> Result<Vec<Content>, Error> {
+        let mut children = Vec::new();
+
+        loop {
+            let rest = self.rest();
+
+            if rest.is_empty() {
+                return match parent {
+                    Some(tag) if !OPTIONAL_END_TAGS.contains(&tag) => {
+                        Err(self.error(format!("unclosed `<{tag}>`")))
+                    }
+                    _ => Ok(children),
+                };
+            }
+
+            if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<!") || rest.starts_with("<?") {
+                self.skip_past(">")?;
+            } else if rest.starts_with("</") {
+                let start = self.position;
+                self.position += 2;
+                let tag = self.name().to_ascii_lowercase();
+
+                // An end tag for an ancestor also closes the elements whose end tag is
+                // optional, leaving it for the ancestor to take.
+                return match parent {
+                    Some(parent) if parent == tag => {
+                        self.skip_past(">")?;
+                        Ok(children)
+                    }
+                    Some(parent) if OPTIONAL_END_TAGS.contains(&parent) => {
+                        self.position = start;
+                        Ok(children)
+                    }
+                    _ => Err(Error {
+                        message: format!("unexpected `</{tag}>`"),
+                        position: start,
+                    }),
+                };
+            } else if rest.starts_with('<') {
+                let start = self.position;
+                self.position += 1;
+                let tag = self.name().to_ascii_lowercase();
+                self.position = start;
+
+                if parent.is_some_and(|parent| closed_by(parent, &tag)) {
+                    return Ok(children);
+                }
+                if let Some(element) = self.element(styles)? {
+                    children.push(Content::Element(element));
+                }
+            } else {
+                let end = rest.find('<').unwrap_or(rest.len());
+                self.position += end;
+                children.push(Content::Text(decode_entities(&rest[..end])));
+            }
+        }
+    }
+
+    fn element(&mut self, styles: &mut String) -> Result<Option<Element>, Error> {
+        let start = self.position;
+        self.position += 1;
+        let tag = self.name().to_ascii_lowercase();
+
+        if !ELEMENTS.contains(&tag.as_str()) {
+            return Err(Error {
+                message: format!("unsupported element `<{tag}>`"),
+                position: start,
+            });
+        }
+
+        let mut element = Element {
+            tag,
+            id: None,
+            class: Vec::new(),
+            style: None,
+            src: None,
+            children: Vec::new(),
+        };
+
+        let self_closing = loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+
+            if rest.starts_with("/>") {
+                self.position += 2;
+                break true;
+            }
+            if rest.starts_with('>') {
+                self.position += 1;
+                break false;
+            }
+
+            let name = self.name().to_ascii_lowercase();
+            if name.is_empty() {
+                return Err(self.error("malformed attribute"));
+            }
+
+            self.skip_whitespace();
+            let value = if self.rest().starts_with('=') {
+                self.position += 1;
+                self.skip_whitespace();
+                self.attribute_value()?
+            } else {
+                String::new()
+            };
+
+            match name.as_str() {
+                "id" => element.id = Some(value),
+                "class" => element.class = value.split_whitespace().map(String::from).collect(),
+                "style" => element.style = Some(value),
+                "src" => element.src = Some(value),
+                _ => {}
+            }
+        };
+
+        if element.tag == "style" {
+            let css = self.skip_past("</style>")?;
+            styles.push_str(css);
+            styles.push('\n');
+            return Ok(None);
+        }
+
+        if element.tag == "title" {
+            self.skip_past("</title>")?;
+            return Ok(None);
+        }
+
+        if !self_closing && !VOID_ELEMENTS.contains(&element.tag.as_str()) {
+            element.children = self.children(Some(&element.tag), styles)?;
+        }
+
+        Ok(Some(element))
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last_was_space = false;
+
+    for c in text.chars() {
+        if c.is_ascii_whitespace() {
+            if !last_was_space {
+                result.push(' ');
+            }
+            last_was_space = true;
+        } else {
+            result.push(c);
+            last_was_space = false;
+        }
+    }
+
+    result
+}
+
+impl Document {
+    /// Parses a document whose relative paths are resolved against the working directory.
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        Self::parse_with_base(source, "")
+    }
+
+    /// Parses a document whose relative paths, like the `src` of images, are resolved against
+    /// the directory `base`.
+    pub fn parse_with_base(source: &str, base: impl Into<PathBuf>) -> Result<Self, Error> {
+        let mut tokenizer = Tokenizer {
+            source,
+            position: 0,
+        };
+        let mut styles = String::new();
+        let children = tokenizer.children(None, &mut styles)?;
+
+        let base = base.into();
+        let mut stylesheet = Stylesheet::parse(&styles);
+        for face in &mut stylesheet.font_faces {
+            for source in &mut face.src {
+                if let FontSource::Url(path) = source {
+                    *path = base.join(&path);
+                }
+            }
+        }
+
+        Ok(Self {
+            children,
+            stylesheet,
+            images: ImageCache::new(base),
+        })
+    }
+
+    /// Reads and parses the document at `path`, resolving its relative paths against the
+    /// directory it is in.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|error| Error {
+            message: format!("can't read `{}`: {error}", path.display()),
+            position: 0,
+        })?;
+
+        Self::parse_with_base(&source, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// The `@font-face` rules of the document's stylesheets. They are loaded into a tree with
+    /// `Fonts::load_font_face`, faces that fail to load falling back like in a browser.
+    pub fn font_faces(&self) -> &[FontFace] {
+        &self.stylesheet.font_faces
+    }
+
+    /// Appends the document's elements as children of `node`.
+    pub fn build(&self, node: Node) -> Node {
+        let mut path = Vec::new();
+        self.build_children(node, &self.children, &mut path)
+    }
+
+    fn build_children<'a>(
+        &self,
+        mut node: Node,
+        children: &'a [Content],
+        path: &mut Vec<parser::Element<'a>>,
+    ) -> Node {
+        let has_elements = children.iter().any(|child| match child {
+            Content::Element(element) => !HIDDEN_ELEMENTS.contains(&element.tag.as_str()),
+            Content::Text(_) => false,
+        });
+
+        // White space that `white-space` keeps is left for shaping to collapse, only a line
+        // break right after `<pre>` going like in a browser.
+        let collapse = matches!(
+            node.style.white_space,
+            WhiteSpace::Normal | WhiteSpace::Nowrap
+        );
+        let pre = path.last().is_some_and(|element| element.tag == "pre");
+
+        // Text-only elements carry the text themselves, mixed content gets anonymous inline
+        // boxes for each run of text.
+        if !has_elements {
+            let text = children
+                .iter()
+                .filter_map(|child| match child {
+                    Content::Text(text) => Some(text.as_str()),
+                    Content::Element(_) => None,
+                })
+                .collect::<String>();
+            let text = if collapse {
+                collapse_whitespace(&text).trim().to_string()
+            } else if pre {
+                text.strip_prefix('\n').unwrap_or(&text).to_string()
+            } else {
+                text
+            };
+
+            if !text.is_empty() {
+                node = node.set_content(&text);
+            }
+            return node;
+        }
+
+        let inline =
+            |node: &Node| matches!(node.style.display, Display::Inline | Display::InlineBlock);
+        // A space between elements is only kept when both sides are inline.
+        let mut space = None;
+
+        let len = children.len();
+        for (i, child) in children.iter().enumerate() {
+            match child {
+                Content::Text(text) if !collapse => {
+                    let text = match i {
+                        0 if pre => text.strip_prefix('\n').unwrap_or(text),
+                        _ => text,
+                    };
+                    if !text.is_empty() {
+                        node = node.add_text(text);
+                    }
+                }
+                Content::Text(text) => {
+                    let mut text = collapse_whitespace(text);
+                    if i == 0 {
+                        text = text.trim_start().to_string();
+                    }
+                    if i == len - 1 {
+                        text = text.trim_end().to_string();
+                    }
+                    if text.is_empty() {
+                        continue;
+                    }
+
+                    let mut anonymous = node.inherit();
+                    anonymous.style.display = Display::Inline;
+                    let anonymous = anonymous.set_content(&text);
+                    if text.trim().is_empty() {
+                        space = node
+                            .children
+                            .last()
+                            .filter(|last| inline(last))
+                            .map(|_| anonymous);
+                    } else {
+                        node.children.push(anonymous);
+                    }
+                }
+                Content::Element(element) => {
+                    if HIDDEN_ELEMENTS.contains(&element.tag.as_str()) {
+                        continue;
+                    }
+
+                    path.push(parser::Element {
+                        tag: &element.tag,
+                        id: element.id.as_deref(),
+                        class: &element.class,
+                    });
+                    let child = self.build_element(&node, element, path);
+                    path.pop();
+
+                    if let Some(space) = space.take().filter(|_| inline(&child)) {
+                        node.children.push(space);
+                    }
+                    node.children.push(child);
+                }
+            }
+        }
+
+        node
+    }
+
+    fn build_element<'a>(
+        &self,
+        parent: &Node,
+        element: &'a Element,
+        path: &mut Vec<parser::Element<'a>>,
+    ) -> Node {
+        let mut node = parent.inherit();
+        node.id = element.id.clone();
+        node.class = element.class.clone();
+
+        // Images that fail to load leave an empty box, like a broken image without alt text.
+        if let ("img", Some(src)) = (element.tag.as_str(), &element.src) {
+            match Path::new(src)
+                .extension()
+                .and_then(|extension| extension.to_str())
+            {
+                Some("svg" | "svgz") => node.svg = self.images.svg(src),
+                _ => node.image = self.images.image(src),
+            }
+        }
+
+        // A line break joins the text around it as a paragraph separator, which survives
+        // white space collapsing.
+        if element.tag == "br" {
+            node = node.set_content(&LINE_BREAK.to_string());
+        }
+
+        let user_agent = parser::parse_declarations(user_agent_style(&element.tag));
+        let inline = element
+            .style
+            .as_deref()
+            .map(parser::parse_declarations)
+            .unwrap_or_default();
+
+        parser::cascade(
+            &mut node,
+            user_agent
+                .iter()
+                .chain(self.stylesheet.matching(path))
+                .chain(&inline),
+            &self.images,
+        );
+
+        self.build_children(node, &element.children, path)
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::Cursor,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+pub use image::ImageError;
+use image::{ImageReader, RgbaImage};
+
+use crate::svg::Svg;
+
+static NEXT_IMAGE: AtomicU64 = AtomicU64::new(0);
+
+/// A decoded PNG, JPEG or WebP image. Clones share the pixels, and the tree uploads each image
+/// to its texture atlas once, the first time it is drawn.
+#[derive(Clone, Debug)]
+pub struct Image {
+    id: u64,
+    pixels: Rc<RgbaImage>,
+}
+
+impl PartialEq for Image {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Image {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ImageError> {
+        let image = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()?
+            .decode()?;
+
+        Ok(Self::from_rgba(image.into_rgba8()))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        let image = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+
+        Ok(Self::from_rgba(image.into_rgba8()))
+    }
+
+    pub fn from_rgba(pixels: RgbaImage) -> Self {
+        Self {
+            id: NEXT_IMAGE.fetch_add(1, Ordering::Relaxed),
+            pixels: Rc::new(pixels),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.pixels.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.pixels.height()
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn rgba(&self) -> &RgbaImage {
+        &self.pixels
+    }
+
+    pub(crate) fn pixels(&self) -> &Rc<RgbaImage> {
+        &self.pixels
+    }
+}
+
+/// The images a document and its stylesheets refer to, each decoded once however many
+/// elements and `url()`s name it, with relative paths resolved against the document's
+/// directory. Images that fail to load are remembered as missing too. Clones share the cache.
+#[derive(Clone, Debug)]
+pub struct ImageCache {
+    base: PathBuf,
+    // Checking declarations only looks at their syntax, leaving the files alone.
+    decode: bool,
+    images: Rc<RefCell<HashMap<PathBuf, Option<Image>>>>,
+    svgs: Rc<RefCell<HashMap<PathBuf, Option<Svg>>>>,
+}
+
+impl ImageCache {
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self {
+            base: base.into(),
+            decode: true,
+            images: Rc::default(),
+            svgs: Rc::default(),
+        }
+    }
+
+    // A cache that never loads anything, for checking declarations.
+    pub(crate) fn unloaded() -> Self {
+        Self {
+            decode: false,
+            ..Self::new("")
+        }
+    }
+
+    /// The PNG, JPEG or WebP image at `path`, if it can be read and decoded.
+    pub fn image(&self, path: &str) -> Option<Image> {
+        let path = self.base.join(path);
+        self.images
+            .borrow_mut()
+            .entry(path)
+            .or_insert_with_key(|path| self.decode.then(|| Image::open(path).ok()).flatten())
+            .clone()
+    }
+
+    /// The SVG document at `path`, if it can be read and parsed.
+    pub fn svg(&self, path: &str) -> Option<Svg> {
+        let path = self.base.join(path);
+        self.svgs
+            .borrow_mut()
+            .entry(path)
+            .or_insert_with_key(|path| {
+                let bytes = self.decode.then(|| std::fs::read(path).ok()).flatten()?;
+                Svg::from_bytes(&bytes).ok()
+            })
+            .clone()
+    }
+}
+
+/// How an image node's picture is fitted into its content box, like CSS `object-fit`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ObjectFit {
+    /// Stretched to the box, ignoring the aspect ratio.
+    Fill,
+    Contain,
+    Cover,
+    /// Drawn at its intrinsic size.
+    None,
+    /// The smaller of `None` and `Contain`.
+    ScaleDown,
+}
@@ -1,5 +1,23 @@
+pub mod background;
 pub mod buffers;
+pub mod clip;
+pub mod filter;
+pub mod fonts;
+pub mod gradient;
+pub mod html;
+pub mod images;
+pub mod mask;
 mod math;
 mod parser;
 pub mod rectangle;
+pub mod signal;
+pub mod svg;
+pub mod text;
+pub mod transform;
 pub mod tree;
+
+// What `ui!` checks inline styles with while it expands.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::parser::check_declarations;
+}
@@ -0,0 +1,164 @@
+use calc_units::Units;
+
+use crate::background::{
+    cycle, BackgroundBox, BackgroundImage, BackgroundRepeat, BackgroundSize, Layer,
+};
+
+/// `mask-mode`: which channel of a mask layer's image sets how much of the node shows.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MaskMode {
+    Alpha,
+    Luminance,
+    /// Alpha, as images and gradients are no SVG `<mask>` elements.
+    MatchSource,
+}
+
+/// The `mask-*` properties, lists with one entry per layer like `Background`. The layers are
+/// laid out over the border box like backgrounds and added up into one mask, which scales the
+/// opacity of the node and its descendants.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mask {
+    pub image: Vec<BackgroundImage>,
+    pub size: Vec<BackgroundSize>,
+    pub position: Vec<[Units; 2]>,
+    pub repeat: Vec<[BackgroundRepeat; 2]>,
+    pub mode: Vec<MaskMode>,
+}
+
+impl Default for Mask {
+    fn default() -> Self {
+        Self {
+            image: Vec::new(),
+            size: vec![BackgroundSize::Size([Units::Auto, Units::Auto])],
+            position: vec![[Units::Perc(0.0), Units::Perc(0.0)]],
+            repeat: vec![[BackgroundRepeat::Repeat; 2]],
+            mode: vec![MaskMode::MatchSource],
+        }
+    }
+}
+
+impl Mask {
+    /// Layers from the bottom one up, each with whether its luminance masks rather than its
+    /// alpha.
+    pub(crate) fn layers(&self) -> impl Iterator<Item = (Layer<'_>, bool)> {
+        static AUTO: BackgroundSize = BackgroundSize::Size([Units::Auto, Units::Auto]);
+        static ORIGIN: [Units; 2] = [Units::Perc(0.0), Units::Perc(0.0)];
+
+        self.image.iter().enumerate().rev().map(|(i, image)| {
+            let layer = Layer {
+                image,
+                size: cycle(&self.size, i).unwrap_or(&AUTO),
+                position: cycle(&self.position, i).unwrap_or(&ORIGIN),
+                repeat: cycle(&self.repeat, i)
+                    .copied()
+                    .unwrap_or([BackgroundRepeat::Repeat; 2]),
+                origin: BackgroundBox::BorderBox,
+                clip: BackgroundBox::BorderBox,
+            };
+            let luminance = cycle(&self.mode, i) == Some(&MaskMode::Luminance);
+            (layer, luminance)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        filter,
+        gradient::{ColorStop, Gradient, GradientKind, LinearDirection},
+        rectangle::Rectangle,
+        tree::{
+            tests::{items, quad, state},
+            DrawList,
+        },
+    };
+
+    // A gradient told apart by the red of its color.
+    fn image(red: f32) -> BackgroundImage {
+        let stop = ColorStop {
+            color: [red, 0.0, 0.0, 1.0],
+            position: None,
+        };
+        BackgroundImage::Gradient(Gradient {
+            kind: GradientKind::Linear(LinearDirection::Angle(180.0)),
+            stops: vec![stop.clone(), stop],
+            repeating: false,
+        })
+    }
+
+    fn red(layer: &Layer) -> f32 {
+        match layer.image {
+            BackgroundImage::Gradient(gradient) => gradient.stops[0].color[0],
+            BackgroundImage::Image(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_mask_layers() {
+        let mask = Mask {
+            image: vec![image(0.0), image(0.5), image(1.0)],
+            size: vec![BackgroundSize::Cover, BackgroundSize::Contain],
+            mode: vec![MaskMode::Luminance, MaskMode::Alpha],
+            ..Default::default()
+        };
+
+        // From the bottom layer up, the lists repeat to cover every image.
+        let layers = mask
+            .layers()
+            .map(|(layer, luminance)| (red(&layer), layer.size.clone(), luminance))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            layers,
+            [
+                (1.0, BackgroundSize::Cover, true),
+                (0.5, BackgroundSize::Contain, false),
+                (0.0, BackgroundSize::Cover, true),
+            ]
+        );
+        // Masks are laid out over the border box.
+        assert!(mask.layers().all(|(layer, _)| {
+            layer.origin == BackgroundBox::BorderBox && layer.clip == BackgroundBox::BorderBox
+        }));
+
+        // `match-source` masks with alpha.
+        let mask = Mask {
+            image: vec![image(0.0)],
+            ..Default::default()
+        };
+        assert!(mask.layers().all(|(_, luminance)| !luminance));
+        assert_eq!(Mask::default().layers().count(), 0);
+    }
+
+    #[test]
+    fn test_mask_composite() {
+        let mut draw_list = DrawList::default();
+        let parent = draw_list.begin_layer();
+        draw_list.extend_instances([quad([0.0; 4])]);
+        let content = draw_list.end_layer(parent).unwrap();
+
+        // Luminance layers go into the mask through a pass of their own.
+        let parent = draw_list.begin_layer();
+        draw_list.extend_instances([quad([0.0; 4])]);
+        let inner = draw_list.begin_layer();
+        draw_list.extend_instances([quad([0.0; 4])]);
+        let luminance = draw_list.end_layer(inner).unwrap();
+        filter::luminance(&mut draw_list, luminance, &state());
+        let mask = draw_list.end_layer(parent).unwrap();
+        assert_eq!(
+            items(&draw_list, mask),
+            ["1..2".to_string(), format!("layer {luminance}")]
+        );
+
+        // The content is masked in a pass, which is composited.
+        let node = Rectangle::default();
+        filter::composite(&mut draw_list, content, &node, None, Some(mask), &state());
+        let masked = draw_list.layers.len() - 1;
+        assert_eq!(draw_list.order, [content, luminance, mask, masked]);
+        assert_eq!(
+            items(&draw_list, masked),
+            [format!("layer {content} masked by {mask}")]
+        );
+        assert_eq!(items(&draw_list, 0), [format!("layer {masked}")]);
+    }
+}
@@ -1,10 +1,47 @@
+/// Column major, like `matrix3d()` and WGSL. Points are columns multiplied on the right.
 pub type Mat4 = [[f32; 4]; 4];
 
-pub trait Matrix {
+pub trait Matrix: Sized {
+    const IDENTITY: Self;
+
     fn projection(left: f32, right: f32, top: f32, bottom: f32) -> Self;
+    fn translate(x: f32, y: f32, z: f32) -> Self;
+    fn scale(x: f32, y: f32, z: f32) -> Self;
+    /// Clockwise in degrees around `axis` when looking along it, as y points down.
+    fn rotate(axis: [f32; 3], degrees: f32) -> Self;
+    fn skew(x_degrees: f32, y_degrees: f32) -> Self;
+    /// Puts the viewer `distance` in front of the z = 0 plane.
+    fn perspective(distance: f32) -> Self;
+    /// From `[a, b, c, d, e, f]` mapping (x, y) to (a x + c y + e, b x + d y + f), like CSS
+    /// `matrix()`.
+    fn affine(matrix: [f32; 6]) -> Self;
+
+    /// `self` applied after `other`.
+    fn multiply(&self, other: &Self) -> Self;
+    /// Where a point of the z = 0 plane ends up, divided by w.
+    fn apply(&self, point: [f32; 2]) -> [f32; 2];
+    /// The point of the z = 0 plane that `apply` maps to `point`, `None` when the plane is
+    /// seen edge on or the point is behind the viewer.
+    fn unproject(&self, point: [f32; 2]) -> Option<[f32; 2]>;
+    fn inverse(&self) -> Option<Self>;
+    /// The uniform scale when the plane is only translated and scaled evenly.
+    fn uniform_scale(&self) -> Option<f32>;
+    /// How long the sides of the rectangle (x, y, width, height) end up, the longer of two
+    /// opposite sides under perspective.
+    fn drawn_size(&self, rect: [f32; 4]) -> [f32; 2];
+    /// The box (x, y, width, height) around where the rectangle (x, y, width, height) ends
+    /// up, `None` when part of it is behind the viewer.
+    fn bounds(&self, rect: [f32; 4]) -> Option<[f32; 4]>;
 }
 
 impl Matrix for Mat4 {
+    const IDENTITY: Self = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
     fn projection(left: f32, right: f32, top: f32, bottom: f32) -> Self {
         [
             [2.0 / (right - left), 0.0, 0.0, 0.0],
@@ -18,4 +55,259 @@ impl Matrix for Mat4 {
             ],
         ]
     }
+
+    fn translate(x: f32, y: f32, z: f32) -> Self {
+        let mut matrix = Self::IDENTITY;
+        matrix[3] = [x, y, z, 1.0];
+        matrix
+    }
+
+    fn scale(x: f32, y: f32, z: f32) -> Self {
+        [
+            [x, 0.0, 0.0, 0.0],
+            [0.0, y, 0.0, 0.0],
+            [0.0, 0.0, z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+
+    // The `rotate3d()` matrix of the CSS Transforms spec.
+    fn rotate(axis: [f32; 3], degrees: f32) -> Self {
+        let length = axis.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length == 0.0 {
+            return Self::IDENTITY;
+        }
+
+        let [x, y, z] = axis.map(|v| v / length);
+        let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+        let (sc, sq) = (sin * cos, sin * sin);
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z) * sq,
+                2.0 * (x * y * sq + z * sc),
+                2.0 * (x * z * sq - y * sc),
+                0.0,
+            ],
+            [
+                2.0 * (x * y * sq - z * sc),
+                1.0 - 2.0 * (x * x + z * z) * sq,
+                2.0 * (y * z * sq + x * sc),
+                0.0,
+            ],
+            [
+                2.0 * (x * z * sq + y * sc),
+                2.0 * (y * z * sq - x * sc),
+                1.0 - 2.0 * (x * x + y * y) * sq,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+
+    fn skew(x_degrees: f32, y_degrees: f32) -> Self {
+        let mut matrix = Self::IDENTITY;
+        matrix[0][1] = y_degrees.to_radians().tan();
+        matrix[1][0] = x_degrees.to_radians().tan();
+        matrix
+    }
+
+    fn perspective(distance: f32) -> Self {
+        let mut matrix = Self::IDENTITY;
+        matrix[2][3] = -1.0 / distance.max(1.0);
+        matrix
+    }
+
+    fn affine([a, b, c, d, e, f]: [f32; 6]) -> Self {
+        [
+            [a, b, 0.0, 0.0],
+            [c, d, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [e, f, 0.0, 1.0],
+        ]
+    }
+
+    fn multiply(&self, other: &Self) -> Self {
+        other.map(|column| [0, 1, 2, 3].map(|row| (0..4).map(|k| self[k][row] * column[k]).sum()))
+    }
+
+    fn apply(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let [x, y, w] = [0, 1, 3].map(|row| self[0][row] * x + self[1][row] * y + self[3][row]);
+        [x / w, y / w]
+    }
+
+    fn unproject(&self, [x, y]: [f32; 2]) -> Option<[f32; 2]> {
+        // Restricted to the plane, the matrix is the 3x3 homography of its x, y and w rows and
+        // columns.
+        let h = [0, 1, 3].map(|row| [0, 1, 3].map(|column| self[column][row]));
+        let cofactor = |r: usize, c: usize| {
+            let [r1, r2] = [(r + 1) % 3, (r + 2) % 3];
+            let [c1, c2] = [(c + 1) % 3, (c + 2) % 3];
+            h[r1][c1] * h[r2][c2] - h[r1][c2] * h[r2][c1]
+        };
+        let determinant: f32 = (0..3).map(|c| h[0][c] * cofactor(0, c)).sum();
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+
+        // The adjugate is the transposed cofactor matrix, its scale doesn't matter.
+        let [px, py, pw] =
+            [0, 1, 2].map(|r| cofactor(0, r) * x + cofactor(1, r) * y + cofactor(2, r));
+        let local = [px / pw, py / pw];
+        let w: f32 = h[2][0] * local[0] + h[2][1] * local[1] + h[2][2];
+        (pw != 0.0 && w > 0.0).then_some(local)
+    }
+
+    fn inverse(&self) -> Option<Self> {
+        // Gauss-Jordan elimination with the columns as rows inverts the transpose, whose rows
+        // are the columns of the inverse.
+        let mut rows = *self;
+        let mut inverse = Self::IDENTITY;
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&a, &b| rows[a][column].abs().total_cmp(&rows[b][column].abs()))?;
+            if rows[pivot][column].abs() < f32::EPSILON {
+                return None;
+            }
+            rows.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = rows[column][column];
+            rows[column] = rows[column].map(|v| v / scale);
+            inverse[column] = inverse[column].map(|v| v / scale);
+            for row in (0..4).filter(|&row| row != column) {
+                let factor = rows[row][column];
+                for k in 0..4 {
+                    rows[row][k] -= factor * rows[column][k];
+                    inverse[row][k] -= factor * inverse[column][k];
+                }
+            }
+        }
+        Some(inverse)
+    }
+
+    fn uniform_scale(&self) -> Option<f32> {
+        let [[a, b, _, bw], [c, d, _, dw], _, [_, _, _, w]] = *self;
+        (b == 0.0 && c == 0.0 && bw == 0.0 && dw == 0.0 && w == 1.0 && a == d && a > 0.0)
+            .then_some(a)
+    }
+
+    fn drawn_size(&self, [x, y, width, height]: [f32; 4]) -> [f32; 2] {
+        let [a, b, c, d] = [
+            [x, y],
+            [x + width, y],
+            [x, y + height],
+            [x + width, y + height],
+        ]
+        .map(|p| self.apply(p));
+        let length = |p: [f32; 2], q: [f32; 2]| (p[0] - q[0]).hypot(p[1] - q[1]);
+        [
+            length(a, b).max(length(c, d)),
+            length(a, c).max(length(b, d)),
+        ]
+    }
+
+    fn bounds(&self, [x, y, width, height]: [f32; 4]) -> Option<[f32; 4]> {
+        let corners = [
+            [x, y],
+            [x + width, y],
+            [x, y + height],
+            [x + width, y + height],
+        ];
+        let w = |[x, y]: [f32; 2]| self[0][3] * x + self[1][3] * y + self[3][3];
+        if corners.iter().any(|&corner| w(corner) <= 0.0) {
+            return None;
+        }
+
+        let [first, rest @ ..] = corners.map(|corner| self.apply(corner));
+        let (min, max) = rest.iter().fold((first, first), |(min, max), p| {
+            (
+                [min[0].min(p[0]), min[1].min(p[1])],
+                [max[0].max(p[0]), max[1].max(p[1])],
+            )
+        });
+        Some([min[0], min[1], max[0] - min[0], max[1] - min[1]])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close<const N: usize>(actual: [f32; N], expected: [f32; N]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-3,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    // Seen at an angle from 200 pixels away.
+    fn tilted() -> Mat4 {
+        Mat4::perspective(200.0)
+            .multiply(&Mat4::rotate([0.0, 1.0, 0.0], 40.0))
+            .multiply(&Mat4::translate(5.0, 6.0, 0.0))
+    }
+
+    #[test]
+    fn test_perspective() {
+        // Halfway to the viewer, the plane is drawn twice as large.
+        let closer = Mat4::perspective(100.0).multiply(&Mat4::translate(0.0, 0.0, 50.0));
+        assert_close(closer.apply([10.0, 10.0]), [20.0, 20.0]);
+        assert_close(
+            closer.bounds([0.0, 0.0, 10.0, 5.0]).unwrap(),
+            [0.0, 0.0, 20.0, 10.0],
+        );
+
+        let behind = Mat4::perspective(100.0).multiply(&Mat4::translate(0.0, 0.0, 150.0));
+        assert_eq!(behind.bounds([0.0, 0.0, 10.0, 5.0]), None);
+        assert_eq!(behind.unproject([10.0, 10.0]), None);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let matrix = Mat4::translate(10.0, 20.0, 30.0)
+            .multiply(&Mat4::rotate([1.0, 1.0, 0.0], 30.0))
+            .multiply(&Mat4::scale(2.0, 3.0, 4.0));
+        let identity = matrix.multiply(&matrix.inverse().unwrap());
+        for (actual, expected) in identity.iter().zip(Mat4::IDENTITY) {
+            assert_close(*actual, expected);
+        }
+        assert_eq!(Mat4::scale(0.0, 1.0, 1.0).inverse(), None);
+
+        let tilted = tilted();
+        assert_close(
+            tilted.unproject(tilted.apply([12.0, -7.0])).unwrap(),
+            [12.0, -7.0],
+        );
+        // Edge on, the plane is a line.
+        assert_eq!(
+            Mat4::rotate([0.0, 1.0, 0.0], 90.0).unproject([0.0, 0.0]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_drawn_size() {
+        let scaled = Mat4::translate(3.0, 4.0, 0.0).multiply(&Mat4::scale(2.0, 2.0, 1.0));
+        assert_eq!(scaled.uniform_scale(), Some(2.0));
+        assert_eq!(Mat4::scale(2.0, 3.0, 1.0).uniform_scale(), None);
+        assert_eq!(Mat4::rotate([0.0, 0.0, 1.0], 10.0).uniform_scale(), None);
+        assert_eq!(tilted().uniform_scale(), None);
+
+        let rotated = Mat4::rotate([0.0, 0.0, 1.0], 90.0);
+        assert_close(rotated.drawn_size([0.0, 0.0, 10.0, 20.0]), [10.0, 20.0]);
+        assert_close(
+            Mat4::scale(2.0, 3.0, 1.0).drawn_size([5.0, 5.0, 10.0, 10.0]),
+            [20.0, 30.0],
+        );
+        // The nearer side is drawn longer, across it the size shrinks.
+        let tilted = Mat4::perspective(200.0).multiply(&Mat4::rotate([0.0, 1.0, 0.0], 40.0));
+        let [width, height] = tilted.drawn_size([-5.0, -10.0, 10.0, 20.0]);
+        assert!(width < 10.0 && height > 20.0, "{width} {height}");
+
+        let diagonal = 10.0 * 2.0f32.sqrt();
+        let bounds = Mat4::rotate([0.0, 0.0, 1.0], 45.0).bounds([0.0, 0.0, 10.0, 10.0]);
+        assert_close(bounds.unwrap(), [-diagonal / 2.0, 0.0, diagonal, diagonal]);
+    }
 }
@@ -1,332 +1,1616 @@
-//use std::{collections::HashMap, convert::Infallible};
-//
-//use cssparser::*;
-//use lightningcss::{
-//    declaration::DeclarationBlock,
-//    error::PrinterError,
-//    printer::Printer,
-//    properties::custom::{Token, TokenOrValue},
-//    rules::{style::StyleRule, CssRule, CssRuleList, Location},
-//    selector::{Component, Selector},
-//    stylesheet::{ParserOptions, PrinterOptions, StyleSheet},
-//    targets::Browsers,
-//    traits::{AtRuleParser, ToCss},
-//    values::{
-//        color::{CssColor, RGBA},
-//        length::LengthValue,
-//    },
-//    vendor_prefix::VendorPrefix,
-//    visit_types,
-//    visitor::{Visit, VisitTypes, Visitor},
-//};
-//
-//fn main() {
-//    let args: Vec<String> = std::env::args().collect();
-//    let source = std::fs::read_to_string(&args[1]).unwrap();
-//    let opts = ParserOptions {
-//        filename: args[1].clone(),
-//        ..Default::default()
-//    };
-//
-//    let mut stylesheet = StyleSheet::parse_with(&source, opts, &mut TailwindAtRuleParser).unwrap();
-//
-//    println!("{:#?}", stylesheet);
-//
-//    let mut style_rules = HashMap::new();
-//    stylesheet
-//        .visit(&mut StyleRuleCollector {
-//            rules: &mut style_rules,
-//        })
-//        .unwrap();
-//    //println!("{:?}", style_rules);
-//    stylesheet
-//        .visit(&mut ApplyVisitor {
-//            rules: &style_rules,
-//        })
-//        .unwrap();
-//
-//    let result = stylesheet
-//        .to_css(PrinterOptions {
-//            targets: Browsers {
-//                chrome: Some(100 << 16),
-//                ..Browsers::default()
-//            }
-//            .into(),
-//            ..PrinterOptions::default()
-//        })
-//        .unwrap();
-//    //println!("{}", result.code);
-//}
-//
-///// An @tailwind directive.
-//#[derive(Debug, Clone)]
-//enum TailwindDirective {
-//    Base,
-//    Components,
-//    Utilities,
-//    Variants,
-//}
-//
-///// A custom at rule prelude.
-//enum Prelude {
-//    Tailwind(TailwindDirective),
-//    Apply(Vec<String>),
-//}
-//
-///// A @tailwind rule.
-//#[derive(Debug, Clone)]
-//struct TailwindRule {
-//    directive: TailwindDirective,
-//    loc: SourceLocation,
-//}
-//
-///// An @apply rule.
-//#[derive(Debug, Clone)]
-//struct ApplyRule {
-//    names: Vec<String>,
-//    loc: SourceLocation,
-//}
-//
-///// A custom at rule.
-//#[derive(Debug, Clone)]
-//enum AtRule {
-//    Tailwind(TailwindRule),
-//    Apply(ApplyRule),
-//}
-//
-//#[derive(Debug)]
-//struct TailwindAtRuleParser;
-//impl<'i> AtRuleParser<'i> for TailwindAtRuleParser {
-//    type Prelude = Prelude;
-//    type Error = Infallible;
-//    type AtRule = AtRule;
-//
-//    //fn parse_prelude<'t>(
-//    //    &mut self,
-//    //    name: CowRcStr<'i>,
-//    //    input: &mut Parser<'i, 't>,
-//    //    _options: &ParserOptions<'_, 'i>,
-//    //) -> Result<Self::Prelude, ParseError<'i, Self::Error>> {
-//    //    match_ignore_ascii_case! {&*name,
-//    //      "tailwind" => {
-//    //        let location = input.current_source_location();
-//    //        let ident = input.expect_ident()?;
-//    //        let directive = match_ignore_ascii_case! { &*ident,
-//    //          "base" => TailwindDirective::Base,
-//    //          "components" => TailwindDirective::Components,
-//    //          "utilities" => TailwindDirective::Utilities,
-//    //          "variants" => TailwindDirective::Variants,
-//    //          _ => return Err(location.new_unexpected_token_error(
-//    //            cssparser::Token::Ident(ident.clone())
-//    //          ))
-//    //        };
-//    //        Ok(Prelude::Tailwind(directive))
-//    //      },
-//    //      "apply" => {
-//    //        let mut names = Vec::new();
-//    //        loop {
-//    //          if let Ok(name) = input.try_parse(|input| input.expect_ident_cloned()) {
-//    //            names.push(name.as_ref().into());
-//    //          } else {
-//    //            break
-//    //          }
-//    //        }
-//
-//    //        Ok(Prelude::Apply(names))
-//    //      },
-//    //      _ => Err(input.new_error(BasicParseErrorKind::AtRuleInvalid(name)))
-//    //    }
-//    //}
-//
-//    //fn rule_without_block(
-//    //    &mut self,
-//    //    prelude: Self::Prelude,
-//    //    start: &cssparser::ParserState,
-//    //    _options: &ParserOptions<'_, 'i>,
-//    //    _is_nested: bool,
-//    //) -> Result<Self::AtRule, ()> {
-//    //    let loc = start.source_location();
-//    //    match prelude {
-//    //        Prelude::Tailwind(directive) => Ok(AtRule::Tailwind(TailwindRule { directive, loc })),
-//    //        Prelude::Apply(names) => Ok(AtRule::Apply(ApplyRule { names, loc })),
-//    //    }
-//    //}
-//}
-//
-//struct StyleRuleCollector<'i, 'a> {
-//    rules: &'a mut HashMap<String, DeclarationBlock<'i>>,
-//}
-//
-//impl<'i, 'a> Visitor<'i, AtRule> for StyleRuleCollector<'i, 'a> {
-//    type Error = Infallible;
-//
-//    fn visit_types(&self) -> VisitTypes {
-//        VisitTypes::RULES
-//    }
-//
-//    fn visit_rule(
-//        &mut self,
-//        rule: &mut lightningcss::rules::CssRule<'i, AtRule>,
-//    ) -> Result<(), Self::Error> {
-//        match rule {
-//            CssRule::Style(rule) => {
-//                for selector in rule.selectors.0.iter() {
-//                    if selector.len() != 1 {
-//                        continue; // TODO
-//                    }
-//                    for component in selector.iter_raw_match_order() {
-//                        match component {
-//                            Component::Class(name) => {
-//                                self.rules
-//                                    .insert(name.0.to_string(), rule.declarations.clone());
-//                            }
-//                            _ => {}
-//                        }
-//                    }
-//                }
-//            }
-//            _ => {}
-//        }
-//
-//        rule.visit_children(self)
-//    }
-//}
-//
-//struct ApplyVisitor<'a, 'i> {
-//    rules: &'a HashMap<String, DeclarationBlock<'i>>,
-//}
-//
-//impl<'a, 'i> Visitor<'i, AtRule> for ApplyVisitor<'a, 'i> {
-//    type Error = Infallible;
-//
-//    fn visit_types(&self) -> VisitTypes {
-//        visit_types!(RULES | COLORS | LENGTHS | DASHED_IDENTS | SELECTORS | TOKENS)
-//    }
-//
-//    fn visit_rule(&mut self, rule: &mut CssRule<'i, AtRule>) -> Result<(), Self::Error> {
-//        // Replace @apply rule with nested style rule.
-//        if let CssRule::Custom(AtRule::Apply(apply)) = rule {
-//            let mut declarations = DeclarationBlock::new();
-//            for name in &apply.names {
-//                let Some(applied) = self.rules.get(name) else {
-//                    continue;
-//                };
-//                declarations
-//                    .important_declarations
-//                    .extend(applied.important_declarations.iter().cloned());
-//                declarations
-//                    .declarations
-//                    .extend(applied.declarations.iter().cloned());
-//            }
-//            *rule = CssRule::Style(StyleRule {
-//                selectors: Component::Nesting.into(),
-//                vendor_prefix: VendorPrefix::None,
-//                declarations,
-//                rules: CssRuleList(vec![]),
-//                loc: Location {
-//                    source_index: 0,
-//                    line: apply.loc.line,
-//                    column: apply.loc.column,
-//                },
-//            })
-//        }
-//
-//        rule.visit_children(self)
-//    }
-//
-//    fn visit_url(
-//        &mut self,
-//        url: &mut lightningcss::values::url::Url<'i>,
-//    ) -> Result<(), Self::Error> {
-//        url.url = format!("https://mywebsite.com/{}", url.url).into();
-//        Ok(())
-//    }
-//
-//    fn visit_color(
-//        &mut self,
-//        color: &mut lightningcss::values::color::CssColor,
-//    ) -> Result<(), Self::Error> {
-//        //*color = color.to_lab().unwrap();
-//        Ok(())
-//    }
-//
-//    fn visit_length(
-//        &mut self,
-//        length: &mut lightningcss::values::length::LengthValue,
-//    ) -> Result<(), Self::Error> {
-//        match length {
-//            LengthValue::Px(px) => *length = LengthValue::Rem(*px / 16.0),
-//            _ => {}
-//        }
-//
-//        Ok(())
-//    }
-//
-//    fn visit_dashed_ident(
-//        &mut self,
-//        ident: &mut lightningcss::values::ident::DashedIdent,
-//    ) -> Result<(), Self::Error> {
-//        ident.0 = format!("--tw-{}", &ident.0[2..]).into();
-//        Ok(())
-//    }
-//
-//    fn visit_selector(&mut self, selector: &mut Selector<'i>) -> Result<(), Self::Error> {
-//        for c in selector.iter_mut_raw_match_order() {
-//            match c {
-//                Component::Class(c) => {
-//                    *c = format!("tw-{}", c).into();
-//                }
-//                _ => {}
-//            }
-//        }
-//
-//        Ok(())
-//    }
-//
-//    fn visit_token(&mut self, token: &mut TokenOrValue<'i>) -> Result<(), Self::Error> {
-//        match token {
-//            TokenOrValue::Function(f) if f.name == "theme" => match f.arguments.0.first() {
-//                Some(TokenOrValue::Token(Token::String(s))) => match s.as_ref() {
-//                    "blue-500" => {
-//                        *token = TokenOrValue::Color(CssColor::RGBA(RGBA::new(0, 0, 255, 1.0)))
-//                    }
-//                    "red-500" => {
-//                        *token = TokenOrValue::Color(CssColor::RGBA(RGBA::new(255, 0, 0, 1.0)))
-//                    }
-//                    _ => {}
-//                },
-//                _ => {}
-//            },
-//            _ => {}
-//        }
-//
-//        token.visit_children(self)
-//    }
-//}
-//
-////#[cfg(feature = "visitor")]
-//impl<'i, V: Visitor<'i, AtRule>> Visit<'i, AtRule, V> for AtRule {
-//    const CHILD_TYPES: VisitTypes = VisitTypes::empty();
-//
-//    fn visit_children(&mut self, _: &mut V) -> Result<(), V::Error> {
-//        Ok(())
-//    }
-//}
-//
-//impl ToCss for AtRule {
-//    fn to_css<W: std::fmt::Write>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError> {
-//        match self {
-//            AtRule::Tailwind(rule) => {
-//                let _ = rule.loc; // TODO: source maps
-//                let directive = match rule.directive {
-//                    TailwindDirective::Base => "TAILWIND BASE HERE",
-//                    TailwindDirective::Components => "TAILWIND COMPONENTS HERE",
-//                    TailwindDirective::Utilities => "TAILWIND UTILITIES HERE",
-//                    TailwindDirective::Variants => "TAILWIND VARIANTS HERE",
-//                };
-//                dest.write_str(directive)
-//            }
-//            AtRule::Apply(_) => Ok(()),
-//        }
-//    }
-//}
+use std::ops::RangeInclusive;
+
+use calc_units::Units;
+use cssparser::{Delimiter, ParseError, Parser, ParserInput, Token};
+use glyphon::{fontdb, Color, FamilyOwned};
+use lightningcss::{
+    traits::Parse,
+    values::color::{CssColor, RGBA},
+};
+
+use crate::{
+    background::{Background, BackgroundBox, BackgroundImage, BackgroundRepeat, BackgroundSize},
+    clip::{ClipPath, FillRule, ShapeRadius},
+    filter::Filter,
+    fonts::{self, FontFace, FontSource, FontVariant},
+    gradient::{ColorStop, Gradient, GradientKind, LinearDirection, RadialShape, RadialSize},
+    images::{ImageCache, ObjectFit},
+    mask::MaskMode,
+    rectangle::{BorderStyle, BoxShadow, BoxSizing, Display, Position},
+    text::{Hyphens, OverflowWrap, TextAlign, WhiteSpace, WordBreak},
+    transform::{BackfaceVisibility, Transform, TransformStyle},
+    tree::node::Node,
+};
+
+#[derive(Clone, Debug)]
+pub struct Declaration {
+    pub property: String,
+    pub value: String,
+    /// Marked `!important`, which applies it after all normal declarations.
+    pub important: bool,
+}
+
+// Splits `property: value; ...` into declarations. Malformed entries are dropped like a
+// browser would.
+pub fn parse_declarations(source: &str) -> Vec<Declaration> {
+    declarations(source).into_iter().flatten().collect()
+}
+
+// Tokenizes `source` into declarations, or the source of those that are malformed, so
+// semicolons in strings, `url()`s and blocks don't end a declaration early.
+fn declarations(source: &str) -> Vec<Result<Declaration, &str>> {
+    let mut input = ParserInput::new(source);
+    let mut parser = Parser::new(&mut input);
+    let mut declarations = Vec::new();
+
+    while !parser.is_exhausted() {
+        let start = parser.position();
+        let declaration = parser.parse_until_after(Delimiter::Semicolon, |parser| {
+            let property = parser.expect_ident()?.to_ascii_lowercase();
+            parser.expect_colon()?;
+
+            let start = parser.position();
+            parser.parse_until_before(Delimiter::Bang, |parser| {
+                while parser.next().is_ok() {}
+                Ok::<_, ParseError<()>>(())
+            })?;
+            let value = parser.slice_from(start).trim();
+
+            let important = !parser.is_exhausted();
+            if important {
+                parser.expect_delim('!')?;
+                parser.expect_ident_matching("important")?;
+                parser.expect_exhausted()?;
+            }
+            if value.is_empty() {
+                return Err(parser.new_custom_error::<_, ()>(()));
+            }
+            Ok(Declaration {
+                property,
+                value: value.to_string(),
+                important,
+            })
+        });
+
+        let source = parser.slice_from(start).trim_end_matches(';').trim();
+        match declaration {
+            Ok(declaration) => declarations.push(Ok(declaration)),
+            Err(_) if source.is_empty() => {}
+            Err(_) => declarations.push(Err(source)),
+        }
+    }
+
+    declarations
+}
+
+// Checks that every declaration of `source` is well-formed and understood, returning the
+// first one that isn't. `ui!` checks inline styles with it at compile time.
+pub fn check_declarations(source: &str) -> Result<(), String> {
+    let mut node = Node::default();
+    let images = ImageCache::unloaded();
+    declarations(source)
+        .into_iter()
+        .try_for_each(|declaration| match declaration {
+            Ok(declaration) => apply_declaration(&mut node, &declaration, &images)
+                .ok_or_else(|| format!("{}: {}", declaration.property, declaration.value)),
+            Err(source) => Err(source.to_string()),
+        })
+}
+
+// Applies `declarations` in cascade order, the normal ones in the order given and then the
+// `!important` ones. Those that aren't understood are skipped like a browser would, and
+// `url()`s load through `images`.
+pub fn cascade<'a>(
+    node: &mut Node,
+    declarations: impl IntoIterator<Item = &'a Declaration>,
+    images: &ImageCache,
+) {
+    let (important, normal): (Vec<_>, Vec<_>) = declarations
+        .into_iter()
+        .partition(|declaration| declaration.important);
+    for declaration in normal.into_iter().chain(important) {
+        apply_declaration(node, declaration, images);
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Compound {
+    pub tag: Option<String>,
+    pub id: Option<String>,
+    pub class: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Combinator {
+    Descendant,
+    Child,
+}
+
+// Compounds are stored right to left, each paired with the combinator joining it to the
+// compound before it in source order.
+#[derive(Clone, Debug)]
+pub struct Selector {
+    pub compounds: Vec<(Compound, Combinator)>,
+}
+
+pub struct Element<'a> {
+    pub tag: &'a str,
+    pub id: Option<&'a str>,
+    pub class: &'a [String],
+}
+
+impl Compound {
+    fn parse(source: &str) -> Option<Self> {
+        let mut compound = Compound::default();
+        let mut rest = source;
+
+        let end = rest.find(['.', '#']).unwrap_or(rest.len());
+        match &rest[..end] {
+            "" | "*" => {}
+            tag => compound.tag = Some(tag.to_ascii_lowercase()),
+        }
+        rest = &rest[end..];
+
+        while let Some(prefix) = rest.chars().next() {
+            rest = &rest[1..];
+            let end = rest.find(['.', '#']).unwrap_or(rest.len());
+            let name = &rest[..end];
+            if name.is_empty() {
+                return None;
+            }
+
+            match prefix {
+                '.' => compound.class.push(name.to_string()),
+                _ => compound.id = Some(name.to_string()),
+            }
+            rest = &rest[end..];
+        }
+
+        Some(compound)
+    }
+
+    fn matches(&self, element: &Element) -> bool {
+        self.tag.as_ref().is_none_or(|tag| tag == element.tag)
+            && self.id.as_deref().is_none_or(|id| Some(id) == element.id)
+            && self.class.iter().all(|class| element.class.contains(class))
+    }
+}
+
+impl Selector {
+    fn parse(source: &str) -> Option<Self> {
+        let source = source.replace('>', " > ");
+        let mut compounds = Vec::new();
+        let mut combinator = Combinator::Descendant;
+
+        for token in source.split_whitespace() {
+            if token == ">" {
+                combinator = Combinator::Child;
+                continue;
+            }
+
+            if !token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '#' | '*'))
+            {
+                return None;
+            }
+
+            compounds.push((Compound::parse(token)?, combinator));
+            combinator = Combinator::Descendant;
+        }
+
+        if compounds.is_empty() {
+            return None;
+        }
+
+        // Each compound keeps the combinator written before it, which after reversing is the
+        // step from it towards its ancestors.
+        compounds.reverse();
+
+        Some(Self { compounds })
+    }
+
+    pub fn specificity(&self) -> (usize, usize, usize) {
+        self.compounds
+            .iter()
+            .fold((0, 0, 0), |(ids, classes, tags), (compound, _)| {
+                (
+                    ids + compound.id.is_some() as usize,
+                    classes + compound.class.len(),
+                    tags + compound.tag.is_some() as usize,
+                )
+            })
+    }
+
+    // `path` runs from the root down to the element being matched.
+    pub fn matches(&self, path: &[Element]) -> bool {
+        fn matches_from(compounds: &[(Compound, Combinator)], path: &[Element]) -> bool {
+            let Some(((compound, combinator), rest)) = compounds.split_first() else {
+                return true;
+            };
+            let Some((element, ancestors)) = path.split_last() else {
+                return false;
+            };
+
+            if !compound.matches(element) {
+                return false;
+            }
+
+            if rest.is_empty() {
+                return true;
+            }
+
+            match combinator {
+                Combinator::Child => matches_from(rest, ancestors),
+                Combinator::Descendant => {
+                    (1..=ancestors.len()).any(|len| matches_from(rest, &ancestors[..len]))
+                }
+            }
+        }
+
+        matches_from(&self.compounds, path)
+    }
+}
+
+pub struct Rule {
+    pub selectors: Vec<Selector>,
+    pub declarations: Vec<Declaration>,
+}
+
+#[derive(Default)]
+pub struct Stylesheet {
+    pub rules: Vec<Rule>,
+    pub font_faces: Vec<FontFace>,
+}
+
+impl Stylesheet {
+    // Only plain style rules and `@font-face` are understood; other at-rules and rules with
+    // unsupported selectors are skipped, along with any blocks nested in them.
+    pub fn parse(source: &str) -> Self {
+        let source = strip_comments(source);
+        let mut input = ParserInput::new(&source);
+        let mut parser = Parser::new(&mut input);
+        let mut rules = Vec::new();
+        let mut font_faces = Vec::new();
+
+        while !parser.is_exhausted() {
+            let Some((prelude, block)) = next_rule(&mut parser) else {
+                continue;
+            };
+
+            if let Some(at_rule) = prelude.strip_prefix('@') {
+                if at_rule.eq_ignore_ascii_case("font-face") {
+                    font_faces.extend(parse_font_face(block));
+                }
+                continue;
+            }
+
+            let selectors = prelude
+                .split(',')
+                .map(Selector::parse)
+                .collect::<Option<Vec<_>>>();
+
+            if let Some(selectors) = selectors {
+                rules.push(Rule {
+                    selectors,
+                    declarations: parse_declarations(block),
+                });
+            }
+        }
+
+        Self { rules, font_faces }
+    }
+
+    // Returns the declarations that apply to the last element of `path`, in cascade order.
+    pub fn matching(&self, path: &[Element]) -> Vec<&Declaration> {
+        let mut matched = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter_map(|(order, rule)| {
+                rule.selectors
+                    .iter()
+                    .filter(|selector| selector.matches(path))
+                    .map(Selector::specificity)
+                    .max()
+                    .map(|specificity| (specificity, order, rule))
+            })
+            .collect::<Vec<_>>();
+
+        matched.sort_by_key(|(specificity, order, _)| (*specificity, *order));
+        matched
+            .into_iter()
+            .flat_map(|(_, _, rule)| rule.declarations.iter())
+            .collect()
+    }
+}
+
+// Reads the prelude and block of the next rule. Statement at-rules like `@import` end at a
+// semicolon and have no block, and the tokenizer keeps braces in strings and nested blocks
+// from ending the rule early.
+fn next_rule<'i>(parser: &mut Parser<'i, '_>) -> Option<(&'i str, &'i str)> {
+    let start = parser.position();
+    loop {
+        let end = parser.position();
+        match parser.next() {
+            Ok(Token::CurlyBracketBlock) => {
+                let prelude = parser.slice(start..end).trim();
+                let block = parser
+                    .parse_nested_block(|block| {
+                        let start = block.position();
+                        while block.next().is_ok() {}
+                        Ok::<_, ParseError<()>>(block.slice_from(start))
+                    })
+                    .ok()?;
+                return Some((prelude, block));
+            }
+            Ok(Token::Semicolon) | Err(_) => return None,
+            Ok(_) => {}
+        }
+    }
+}
+
+fn strip_comments(source: &str) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(start) = rest.find("/*") {
+        result.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+
+    result.push_str(rest);
+    result
+}
+
+pub fn parse_color(value: &str) -> Option<[f32; 4]> {
+    let color = CssColor::parse_string(value).ok()?;
+    let rgba = RGBA::try_from(&color).ok()?;
+
+    Some([
+        rgba.red as f32 / 255.0,
+        rgba.green as f32 / 255.0,
+        rgba.blue as f32 / 255.0,
+        rgba.alpha as f32 / 255.0,
+    ])
+}
+
+fn parse_length(value: &str) -> Option<Units> {
+    value.parse().ok()
+}
+
+// Expands the CSS one to four value shorthand into top, right, bottom, left.
+fn parse_sides(value: &str) -> Option<[Units; 4]> {
+    expand_sides(value, parse_length)
+}
+
+fn expand_sides<T: Clone>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<[T; 4]> {
+    let values = value
+        .split_whitespace()
+        .map(parse)
+        .collect::<Option<Vec<_>>>()?;
+
+    match values.as_slice() {
+        [all] => Some([all.clone(), all.clone(), all.clone(), all.clone()]),
+        [vertical, horizontal] => Some([
+            vertical.clone(),
+            horizontal.clone(),
+            vertical.clone(),
+            horizontal.clone(),
+        ]),
+        [top, horizontal, bottom] => Some([
+            top.clone(),
+            horizontal.clone(),
+            bottom.clone(),
+            horizontal.clone(),
+        ]),
+        [top, right, bottom, left] => {
+            Some([top.clone(), right.clone(), bottom.clone(), left.clone()])
+        }
+        _ => None,
+    }
+}
+
+fn parse_border_style(value: &str) -> Option<BorderStyle> {
+    match value {
+        "none" => Some(BorderStyle::None),
+        "hidden" => Some(BorderStyle::Hidden),
+        "solid" => Some(BorderStyle::Solid),
+        "dashed" => Some(BorderStyle::Dashed),
+        "dotted" => Some(BorderStyle::Dotted),
+        "double" => Some(BorderStyle::Double),
+        "groove" => Some(BorderStyle::Groove),
+        "ridge" => Some(BorderStyle::Ridge),
+        "inset" => Some(BorderStyle::Inset),
+        "outset" => Some(BorderStyle::Outset),
+        _ => None,
+    }
+}
+
+fn side_index(side: &str) -> Option<usize> {
+    match side {
+        "top" => Some(0),
+        "right" => Some(1),
+        "bottom" => Some(2),
+        "left" => Some(3),
+        _ => None,
+    }
+}
+
+fn set_border_color(node: &mut Node, side: usize, color: Option<[f32; 4]>) {
+    match side {
+        0 => node.border.top_color = color,
+        1 => node.border.right_color = color,
+        2 => node.border.bottom_color = color,
+        _ => node.border.left_color = color,
+    }
+}
+
+// Parses a border color, with `None` for `currentColor`.
+fn parse_border_color(value: &str) -> Option<Option<[f32; 4]>> {
+    match value {
+        "currentcolor" | "currentColor" => Some(None),
+        value => parse_color(value).map(Some),
+    }
+}
+
+// Applies `border` and `border-<side>` shorthands, which accept width, style and color in
+// any order.
+fn apply_border(node: &mut Node, sides: &[usize], value: &str) -> Option<()> {
+    let mut width = None;
+    let mut style = None;
+    let mut color = None;
+
+    for token in split_top_level(value) {
+        if let Some(length) = parse_length(token) {
+            width = Some(length);
+        } else if let Some(parsed) = parse_border_style(token) {
+            style = Some(parsed);
+        } else if let Some(parsed) = parse_border_color(token) {
+            color = Some(parsed);
+        } else {
+            return None;
+        }
+    }
+
+    // Like CSS, whatever the shorthand leaves out is reset: the style to `none` and the color
+    // to `currentColor`.
+    for &side in sides {
+        node.style.border_size[side] = width.clone().unwrap_or(Units::Px(3.0));
+        node.style.border_style[side] = style.unwrap_or(BorderStyle::None);
+        set_border_color(node, side, color.flatten());
+    }
+
+    Some(())
+}
+
+// Splits on whitespace while keeping parenthesized arguments such as `rgb(0 0 0)` intact.
+fn split_top_level(value: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut depth = 0;
+    let mut start = None;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c.is_whitespace() && depth == 0 => {
+                if let Some(s) = start.take() {
+                    tokens.push(&value[s..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+
+    if let Some(s) = start {
+        tokens.push(&value[s..]);
+    }
+
+    tokens
+}
+
+// Splits a comma separated list, keeping commas inside parentheses.
+fn split_list(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    items.push(value[start..].trim());
+    items
+}
+
+// Parses one `box-shadow` layer: offsets, optional blur and spread, an optional color and
+// `inset`.
+fn parse_box_shadow(value: &str) -> Option<BoxShadow> {
+    let mut shadow = BoxShadow::default();
+    let mut lengths = Vec::new();
+
+    for token in split_top_level(value) {
+        if token == "inset" && !shadow.inset {
+            shadow.inset = true;
+        } else if let Some(length) = parse_length(token) {
+            lengths.push(length);
+        } else if shadow.color.is_none() {
+            shadow.color = match token {
+                "currentcolor" | "currentColor" => None,
+                token => Some(parse_color(token)?),
+            };
+        } else {
+            return None;
+        }
+    }
+
+    let mut lengths = lengths.into_iter();
+    shadow.offset = [lengths.next()?, lengths.next()?];
+    if let Some(blur) = lengths.next() {
+        shadow.blur = blur;
+    }
+    if let Some(spread) = lengths.next() {
+        shadow.spread = spread;
+    }
+
+    lengths.next().is_none().then_some(shadow)
+}
+
+// A number or percentage, as filter amounts and `opacity` take them.
+fn parse_amount(value: &str) -> Option<f32> {
+    match value.strip_suffix('%') {
+        Some(percentage) => Some(percentage.parse::<f32>().ok()? / 100.0),
+        None => value.parse().ok(),
+    }
+}
+
+// Parses one `filter` function. Amounts and angles can be left out.
+fn parse_filter(value: &str) -> Option<Filter> {
+    let (name, arguments) = value.strip_suffix(')')?.split_once('(')?;
+    let arguments = arguments.trim();
+    let amount = |default: f32| match arguments {
+        "" => Some(default),
+        arguments => parse_amount(arguments),
+    };
+
+    let filter = match name {
+        "blur" => Filter::Blur(match arguments {
+            "" => Units::Px(0.0),
+            arguments => parse_length(arguments)?,
+        }),
+        "brightness" => Filter::Brightness(amount(1.0)?),
+        "contrast" => Filter::Contrast(amount(1.0)?),
+        "grayscale" => Filter::Grayscale(amount(1.0)?),
+        "invert" => Filter::Invert(amount(1.0)?),
+        "opacity" => Filter::Opacity(amount(1.0)?),
+        "saturate" => Filter::Saturate(amount(1.0)?),
+        "sepia" => Filter::Sepia(amount(1.0)?),
+        "hue-rotate" => Filter::HueRotate(match arguments {
+            "" => 0.0,
+            arguments => parse_angle(arguments)?,
+        }),
+        "drop-shadow" => {
+            let shadow = parse_box_shadow(arguments)?;
+            if shadow.inset || shadow.spread != Units::Px(0.0) {
+                return None;
+            }
+            Filter::DropShadow {
+                offset: shadow.offset,
+                blur: shadow.blur,
+                color: shadow.color,
+            }
+        }
+        _ => return None,
+    };
+    Some(filter)
+}
+
+fn parse_filters(value: &str) -> Option<Vec<Filter>> {
+    match value {
+        "none" => Some(Vec::new()),
+        value => split_top_level(value)
+            .into_iter()
+            .map(parse_filter)
+            .collect(),
+    }
+}
+
+// Parses one `transform` function. Arguments are separated by commas or whitespace.
+fn parse_transform(value: &str) -> Option<Transform> {
+    let (name, arguments) = value.strip_suffix(')')?.split_once('(')?;
+    let arguments = arguments
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|argument| !argument.is_empty())
+        .collect::<Vec<_>>();
+    let numbers = || {
+        arguments
+            .iter()
+            .map(|argument| argument.parse::<f32>().ok())
+            .collect::<Option<Vec<_>>>()
+    };
+    let angles = || {
+        arguments
+            .iter()
+            .map(|argument| parse_angle(argument))
+            .collect::<Option<Vec<_>>>()
+    };
+
+    let transform = match (name.trim(), arguments.len()) {
+        ("translate", 1) => Transform::Translate(parse_length(arguments[0])?, Units::Px(0.0)),
+        ("translate", 2) => {
+            Transform::Translate(parse_length(arguments[0])?, parse_length(arguments[1])?)
+        }
+        ("translateX", 1) => Transform::Translate(parse_length(arguments[0])?, Units::Px(0.0)),
+        ("translateY", 1) => Transform::Translate(Units::Px(0.0), parse_length(arguments[0])?),
+        ("translateZ", 1) => {
+            Transform::Translate3d(Units::Px(0.0), Units::Px(0.0), parse_length(arguments[0])?)
+        }
+        ("translate3d", 3) => Transform::Translate3d(
+            parse_length(arguments[0])?,
+            parse_length(arguments[1])?,
+            parse_length(arguments[2])?,
+        ),
+        ("scale", 1) => {
+            let scale = parse_amount(arguments[0])?;
+            Transform::Scale(scale, scale)
+        }
+        ("scale", 2) => Transform::Scale(parse_amount(arguments[0])?, parse_amount(arguments[1])?),
+        ("scaleX", 1) => Transform::Scale(parse_amount(arguments[0])?, 1.0),
+        ("scaleY", 1) => Transform::Scale(1.0, parse_amount(arguments[0])?),
+        ("scaleZ", 1) => Transform::Scale3d(1.0, 1.0, parse_amount(arguments[0])?),
+        ("scale3d", 3) => Transform::Scale3d(
+            parse_amount(arguments[0])?,
+            parse_amount(arguments[1])?,
+            parse_amount(arguments[2])?,
+        ),
+        ("rotate" | "rotateZ", 1) => Transform::Rotate(parse_angle(arguments[0])?),
+        ("rotateX", 1) => Transform::Rotate3d([1.0, 0.0, 0.0], parse_angle(arguments[0])?),
+        ("rotateY", 1) => Transform::Rotate3d([0.0, 1.0, 0.0], parse_angle(arguments[0])?),
+        ("rotate3d", 4) => {
+            let axis = arguments[..3]
+                .iter()
+                .map(|argument| argument.parse::<f32>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            Transform::Rotate3d(axis.try_into().ok()?, parse_angle(arguments[3])?)
+        }
+        ("skew", 1 | 2) => {
+            let angles = angles()?;
+            Transform::Skew(angles[0], angles.get(1).copied().unwrap_or(0.0))
+        }
+        ("skewX", 1) => Transform::Skew(parse_angle(arguments[0])?, 0.0),
+        ("skewY", 1) => Transform::Skew(0.0, parse_angle(arguments[0])?),
+        ("matrix", 6) => Transform::Matrix(numbers()?.try_into().ok()?),
+        ("matrix3d", 16) => Transform::Matrix3d(numbers()?.try_into().ok()?),
+        ("perspective", 1) => Transform::Perspective(parse_length(arguments[0])?),
+        _ => return None,
+    };
+    Some(transform)
+}
+
+fn parse_transforms(value: &str) -> Option<Vec<Transform>> {
+    match value {
+        "none" => Some(Vec::new()),
+        value => split_top_level(value)
+            .into_iter()
+            .map(parse_transform)
+            .collect(),
+    }
+}
+
+fn parse_angle(value: &str) -> Option<f32> {
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.parse::<f32>().ok()?;
+
+    match unit {
+        "deg" => Some(number),
+        "rad" => Some(number.to_degrees()),
+        "grad" => Some(number * 0.9),
+        "turn" => Some(number * 360.0),
+        "" if number == 0.0 => Some(0.0),
+        _ => None,
+    }
+}
+
+// Parses `<x> <y>`, one value or keywords in either order, as offsets from the top left corner.
+fn parse_position(tokens: &[&str]) -> Option<[Units; 2]> {
+    let horizontal = |token: &str| match token {
+        "left" => Some(Units::Perc(0.0)),
+        "center" => Some(Units::Perc(50.0)),
+        "right" => Some(Units::Perc(100.0)),
+        token => parse_length(token),
+    };
+    let vertical = |token: &str| match token {
+        "top" => Some(Units::Perc(0.0)),
+        "center" => Some(Units::Perc(50.0)),
+        "bottom" => Some(Units::Perc(100.0)),
+        token => parse_length(token),
+    };
+
+    match *tokens {
+        [token @ ("top" | "bottom")] => Some([Units::Perc(50.0), vertical(token)?]),
+        [token] => Some([horizontal(token)?, Units::Perc(50.0)]),
+        [first @ ("top" | "bottom"), second] => Some([horizontal(second)?, vertical(first)?]),
+        [x, y] => Some([horizontal(x)?, vertical(y)?]),
+        _ => None,
+    }
+}
+
+// Parses a `circle()` or `ellipse()` radius.
+fn parse_shape_radius(value: &str) -> Option<ShapeRadius> {
+    match value {
+        "closest-side" => Some(ShapeRadius::ClosestSide),
+        "farthest-side" => Some(ShapeRadius::FarthestSide),
+        value => parse_length(value).map(ShapeRadius::Length),
+    }
+}
+
+// Splits a leading `nonzero` or `evenodd` argument off a `polygon()` or `path()`.
+fn parse_fill_rule(arguments: &str) -> (FillRule, &str) {
+    match arguments.split_once(',') {
+        Some((rule, rest)) if rule.trim() == "nonzero" => (FillRule::NonZero, rest.trim()),
+        Some((rule, rest)) if rule.trim() == "evenodd" => (FillRule::EvenOdd, rest.trim()),
+        _ => (FillRule::NonZero, arguments),
+    }
+}
+
+// Parses a basic shape or `path()` of `clip-path`.
+fn parse_clip_path(value: &str) -> Option<ClipPath> {
+    let (name, arguments) = value.strip_suffix(')')?.split_once('(')?;
+    let arguments = arguments.trim();
+    // Shapes are centered unless positioned with `at`.
+    let center = |tokens: &[&str]| match tokens {
+        [] => Some([Units::Perc(50.0), Units::Perc(50.0)]),
+        ["at", position @ ..] => parse_position(position),
+        _ => None,
+    };
+
+    let clip_path = match name.trim() {
+        "inset" => {
+            let (insets, radius) = match arguments.split_once(" round ") {
+                Some((insets, radius)) => (insets, Some(radius)),
+                None => (arguments, None),
+            };
+            ClipPath::Inset {
+                insets: parse_sides(insets)?,
+                radius: match radius {
+                    Some(radius) => expand_sides(radius, parse_length)?,
+                    None => [const { Units::Px(0.0) }; 4],
+                },
+            }
+        }
+        "circle" => {
+            let tokens = split_top_level(arguments);
+            let (radius, position) = match tokens.split_first() {
+                Some((&first, rest)) if first != "at" => (parse_shape_radius(first)?, rest),
+                _ => (ShapeRadius::ClosestSide, tokens.as_slice()),
+            };
+            ClipPath::Circle {
+                radius,
+                center: center(position)?,
+            }
+        }
+        "ellipse" => {
+            let tokens = split_top_level(arguments);
+            let (radii, position) = match tokens.as_slice() {
+                [rx, ry, position @ ..] if *rx != "at" => {
+                    ([parse_shape_radius(rx)?, parse_shape_radius(ry)?], position)
+                }
+                position => (
+                    [ShapeRadius::ClosestSide, ShapeRadius::ClosestSide],
+                    position,
+                ),
+            };
+            ClipPath::Ellipse {
+                radii,
+                center: center(position)?,
+            }
+        }
+        "polygon" => {
+            let (fill_rule, arguments) = parse_fill_rule(arguments);
+            let points = split_list(arguments)
+                .into_iter()
+                .map(|point| match *split_top_level(point) {
+                    [x, y] => Some([parse_length(x)?, parse_length(y)?]),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            ClipPath::Polygon { fill_rule, points }
+        }
+        "path" => {
+            let (fill_rule, arguments) = parse_fill_rule(arguments);
+            let path = arguments
+                .strip_prefix('"')
+                .and_then(|path| path.strip_suffix('"'))
+                .or_else(|| {
+                    arguments
+                        .strip_prefix('\'')
+                        .and_then(|path| path.strip_suffix('\''))
+                })?;
+            // The path ends up in SVG markup, which it must not break out of.
+            if path.contains(['<', '>', '&', '"']) {
+                return None;
+            }
+            ClipPath::Path {
+                fill_rule,
+                path: path.to_string(),
+            }
+        }
+        _ => return None,
+    };
+    Some(clip_path)
+}
+
+// Parses `<color> [<position> [<position>]]`, where two positions make two stops. Conic
+// gradients take angles, which become percentages of a turn.
+fn parse_color_stops(value: &str, stops: &mut Vec<ColorStop>, angular: bool) -> Option<()> {
+    let tokens = split_top_level(value);
+    let (color, positions) = tokens.split_first()?;
+    let color = parse_color(color)?;
+
+    let position = |token: &&str| match angular {
+        true => parse_angle(token)
+            .map(|degrees| Units::Perc(degrees / 3.6))
+            .or_else(|| parse_length(token).filter(|units| matches!(units, Units::Perc(_)))),
+        false => parse_length(token),
+    };
+
+    match positions {
+        [] => stops.push(ColorStop {
+            color,
+            position: None,
+        }),
+        [_] | [_, _] => {
+            for token in positions {
+                stops.push(ColorStop {
+                    color,
+                    position: Some(position(token)?),
+                });
+            }
+        }
+        _ => return None,
+    }
+
+    Some(())
+}
+
+fn parse_gradient(value: &str) -> Option<Gradient> {
+    let (name, arguments) = value.strip_suffix(')')?.split_once('(')?;
+    let (repeating, name) = match name.trim().strip_prefix("repeating-") {
+        Some(name) => (true, name),
+        None => (false, name.trim()),
+    };
+
+    let mut arguments = split_list(arguments);
+    let first = split_top_level(arguments.first()?);
+    // The first argument configures the gradient unless it is already a color stop.
+    let configured = parse_color(first.first()?).is_none();
+
+    let kind = match name {
+        "linear-gradient" => GradientKind::Linear(match configured {
+            false => LinearDirection::Angle(180.0),
+            true => match first.as_slice() {
+                ["to", sides @ ..] => {
+                    let (mut x, mut y) = (0.0, 0.0);
+                    for side in sides {
+                        match *side {
+                            "left" => x = -1.0,
+                            "right" => x = 1.0,
+                            "top" => y = -1.0,
+                            "bottom" => y = 1.0,
+                            _ => return None,
+                        }
+                    }
+                    match (x, y) {
+                        (0.0, 0.0) => return None,
+                        (0.0, y) => LinearDirection::Angle(if y < 0.0 { 0.0 } else { 180.0 }),
+                        (x, 0.0) => LinearDirection::Angle(if x < 0.0 { 270.0 } else { 90.0 }),
+                        (x, y) => LinearDirection::Corner(x, y),
+                    }
+                }
+                [angle] => LinearDirection::Angle(parse_angle(angle)?),
+                _ => return None,
+            },
+        }),
+        "radial-gradient" => {
+            let (mut shape, mut size, mut position) = (None, None, None);
+            let mut radii = Vec::new();
+
+            if configured {
+                let mut tokens = first.iter();
+                while let Some(&token) = tokens.next() {
+                    match token {
+                        "circle" => shape = Some(RadialShape::Circle),
+                        "ellipse" => shape = Some(RadialShape::Ellipse),
+                        "closest-side" => size = Some(RadialSize::ClosestSide),
+                        "farthest-side" => size = Some(RadialSize::FarthestSide),
+                        "closest-corner" => size = Some(RadialSize::ClosestCorner),
+                        "farthest-corner" => size = Some(RadialSize::FarthestCorner),
+                        "at" => position = Some(parse_position(tokens.as_slice())?),
+                        token => radii.push(parse_length(token)?),
+                    }
+                    if position.is_some() {
+                        break;
+                    }
+                }
+            }
+
+            let size = match radii.as_slice() {
+                [] => size.unwrap_or(RadialSize::FarthestCorner),
+                [radius] if shape != Some(RadialShape::Ellipse) => {
+                    shape = Some(RadialShape::Circle);
+                    RadialSize::Radii([radius.clone(), radius.clone()])
+                }
+                [rx, ry] if shape != Some(RadialShape::Circle) && size.is_none() => {
+                    shape = Some(RadialShape::Ellipse);
+                    RadialSize::Radii([rx.clone(), ry.clone()])
+                }
+                _ => return None,
+            };
+
+            GradientKind::Radial {
+                shape: shape.unwrap_or(RadialShape::Ellipse),
+                size,
+                position: position.unwrap_or([Units::Perc(50.0), Units::Perc(50.0)]),
+            }
+        }
+        "conic-gradient" => {
+            let (mut from, mut position) = (0.0, [Units::Perc(50.0), Units::Perc(50.0)]);
+
+            if configured {
+                match first.as_slice() {
+                    ["from", angle, "at", rest @ ..] => {
+                        from = parse_angle(angle)?;
+                        position = parse_position(rest)?;
+                    }
+                    ["from", angle] => from = parse_angle(angle)?,
+                    ["at", rest @ ..] => position = parse_position(rest)?,
+                    _ => return None,
+                }
+            }
+
+            GradientKind::Conic { from, position }
+        }
+        _ => return None,
+    };
+
+    if configured {
+        arguments.remove(0);
+    }
+
+    let mut stops = Vec::new();
+    for argument in arguments {
+        parse_color_stops(argument, &mut stops, name == "conic-gradient")?;
+    }
+
+    (stops.len() >= 2).then_some(Gradient {
+        kind,
+        stops,
+        repeating,
+    })
+}
+
+// Parses one image layer. A `url()` that fails to load leaves no layer, like `none`.
+fn parse_image(value: &str, images: &ImageCache) -> Option<Option<BackgroundImage>> {
+    match value
+        .strip_prefix("url(")
+        .and_then(|url| url.strip_suffix(')'))
+    {
+        Some(url) => {
+            let path = url.trim().trim_matches(|c| c == '"' || c == '\'');
+            Some(images.image(path).map(BackgroundImage::Image))
+        }
+        None => parse_gradient(value).map(|gradient| Some(BackgroundImage::Gradient(gradient))),
+    }
+}
+
+// Parses a background property list, where `parse` handles one layer.
+fn parse_layers<T>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<Vec<T>> {
+    split_list(value).into_iter().map(parse).collect()
+}
+
+fn parse_background_image(value: &str, images: &ImageCache) -> Option<Vec<BackgroundImage>> {
+    let layers = split_list(value)
+        .into_iter()
+        .filter(|&layer| layer != "none")
+        .map(|layer| parse_image(layer, images))
+        .collect::<Option<Vec<_>>>()?;
+    Some(layers.into_iter().flatten().collect())
+}
+
+fn parse_background_size(tokens: &[&str]) -> Option<BackgroundSize> {
+    let length = |token: &str| match token {
+        "auto" => Some(Units::Auto),
+        token => parse_length(token),
+    };
+
+    match *tokens {
+        ["cover"] => Some(BackgroundSize::Cover),
+        ["contain"] => Some(BackgroundSize::Contain),
+        [width] => Some(BackgroundSize::Size([length(width)?, Units::Auto])),
+        [width, height] => Some(BackgroundSize::Size([length(width)?, length(height)?])),
+        _ => None,
+    }
+}
+
+fn parse_background_repeat(tokens: &[&str]) -> Option<[BackgroundRepeat; 2]> {
+    let repeat = |token: &str| match token {
+        "repeat" => Some(BackgroundRepeat::Repeat),
+        "space" => Some(BackgroundRepeat::Space),
+        "round" => Some(BackgroundRepeat::Round),
+        "no-repeat" => Some(BackgroundRepeat::NoRepeat),
+        _ => None,
+    };
+
+    match *tokens {
+        ["repeat-x"] => Some([BackgroundRepeat::Repeat, BackgroundRepeat::NoRepeat]),
+        ["repeat-y"] => Some([BackgroundRepeat::NoRepeat, BackgroundRepeat::Repeat]),
+        [both] => Some([repeat(both)?; 2]),
+        [x, y] => Some([repeat(x)?, repeat(y)?]),
+        _ => None,
+    }
+}
+
+fn parse_background_box(value: &str) -> Option<BackgroundBox> {
+    match value {
+        "border-box" => Some(BackgroundBox::BorderBox),
+        "padding-box" => Some(BackgroundBox::PaddingBox),
+        "content-box" => Some(BackgroundBox::ContentBox),
+        _ => None,
+    }
+}
+
+// Applies the `background` shorthand: comma separated layers of image, position with an
+// optional `/ size`, repeat and boxes in any order, where the bottom layer may add a color.
+fn apply_background(node: &mut Node, value: &str, images: &ImageCache) -> Option<()> {
+    let layers = split_list(value);
+    let mut background = Background::default();
+    background.size.clear();
+    background.position.clear();
+    background.repeat.clear();
+    background.origin.clear();
+    background.clip.clear();
+    let mut color = [0.0; 4];
+
+    for (i, layer) in layers.iter().enumerate() {
+        let tokens = split_top_level(layer)
+            .into_iter()
+            .flat_map(|token| match token.split_once('/') {
+                Some((position, size)) if !token.contains('(') => vec![position, "/", size],
+                _ => vec![token],
+            })
+            .filter(|token| !token.is_empty())
+            .collect::<Vec<_>>();
+
+        let mut image = None;
+        let (mut position, mut size, mut repeat, mut boxes) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let mut in_size = false;
+
+        for token in tokens {
+            if token == "/" {
+                in_size = true;
+            } else if in_size
+                && (matches!(token, "auto" | "cover" | "contain") || parse_length(token).is_some())
+            {
+                size.push(token);
+            } else if token == "none" {
+                in_size = false;
+            } else if let Some(parsed) = parse_image(token, images) {
+                image = parsed;
+                in_size = false;
+            } else if token.starts_with("repeat")
+                || matches!(token, "space" | "round" | "no-repeat")
+            {
+                repeat.push(token);
+                in_size = false;
+            } else if token.ends_with("-box") {
+                boxes.push(parse_background_box(token)?);
+                in_size = false;
+            } else if matches!(token, "left" | "right" | "top" | "bottom" | "center")
+                || parse_length(token).is_some()
+            {
+                position.push(token);
+            } else if i == layers.len() - 1 {
+                color = parse_color(token)?;
+                in_size = false;
+            } else {
+                return None;
+            }
+        }
+
+        let Some(image) = image else {
+            continue;
+        };
+
+        background.image.push(image);
+        background.position.push(match position.is_empty() {
+            true => [Units::Perc(0.0), Units::Perc(0.0)],
+            false => parse_position(&position)?,
+        });
+        background.size.push(match size.is_empty() {
+            true => BackgroundSize::Size([Units::Auto, Units::Auto]),
+            false => parse_background_size(&size)?,
+        });
+        background.repeat.push(match repeat.is_empty() {
+            true => [BackgroundRepeat::Repeat; 2],
+            false => parse_background_repeat(&repeat)?,
+        });
+        let (origin, clip) = match *boxes {
+            [] => (BackgroundBox::PaddingBox, BackgroundBox::BorderBox),
+            [both] => (both, both),
+            [origin, clip] => (origin, clip),
+            _ => return None,
+        };
+        background.origin.push(origin);
+        background.clip.push(clip);
+    }
+
+    node.background = background;
+    node.background_color = color;
+    Some(())
+}
+
+fn parse_font_family(value: &str) -> Option<Vec<FamilyOwned>> {
+    split_list(value)
+        .into_iter()
+        .map(|family| {
+            // Quoted names are never generic families, `"serif"` is a font called serif.
+            if let Some(name) = family
+                .strip_prefix('"')
+                .and_then(|family| family.strip_suffix('"'))
+                .or_else(|| {
+                    family
+                        .strip_prefix('\'')
+                        .and_then(|family| family.strip_suffix('\''))
+                })
+            {
+                return (!name.is_empty()).then(|| FamilyOwned::Name(name.into()));
+            }
+
+            Some(match family {
+                "" => return None,
+                "serif" => FamilyOwned::Serif,
+                "sans-serif" => FamilyOwned::SansSerif,
+                "monospace" => FamilyOwned::Monospace,
+                "cursive" => FamilyOwned::Cursive,
+                "fantasy" => FamilyOwned::Fantasy,
+                name => FamilyOwned::Name(name.split_whitespace().collect::<Vec<_>>().join(" ")),
+            })
+        })
+        .collect()
+}
+
+// Parses the descriptors of an `@font-face` rule, which is dropped without a family or a
+// source to load.
+fn parse_font_face(block: &str) -> Option<FontFace> {
+    let mut face = FontFace {
+        family: String::new(),
+        src: Vec::new(),
+        weight: None,
+        style: None,
+        unicode_range: Vec::new(),
+    };
+
+    for Declaration {
+        property, value, ..
+    } in parse_declarations(block)
+    {
+        match property.as_str() {
+            "font-family" => {
+                face.family = value.trim_matches(|c| c == '"' || c == '\'').to_string()
+            }
+            "src" => {
+                face.src = split_list(&value)
+                    .into_iter()
+                    .filter_map(parse_font_source)
+                    .collect()
+            }
+            // A range for variable fonts keeps the weight the font declares.
+            "font-weight" => {
+                face.weight = match value.as_str() {
+                    "normal" => Some(fontdb::Weight::NORMAL),
+                    "bold" => Some(fontdb::Weight::BOLD),
+                    value => value
+                        .parse()
+                        .ok()
+                        .filter(|weight| (1..=1000).contains(weight))
+                        .map(fontdb::Weight),
+                }
+            }
+            "font-style" => face.style = parse_font_style(&value),
+            "unicode-range" => {
+                face.unicode_range = split_list(&value)
+                    .into_iter()
+                    .map(parse_unicode_range)
+                    .collect::<Option<_>>()?
+            }
+            _ => {}
+        }
+    }
+
+    (!face.family.is_empty() && !face.src.is_empty()).then_some(face)
+}
+
+// Parses one `src` entry, ignoring the `format()` hint as fonts are recognized by content.
+fn parse_font_source(value: &str) -> Option<FontSource> {
+    let source = split_top_level(value).into_iter().next()?;
+    let (function, argument) = source.strip_suffix(')')?.split_once('(')?;
+    let argument = argument.trim().trim_matches(|c| c == '"' || c == '\'');
+
+    match function {
+        "url" => Some(FontSource::Url(argument.into())),
+        "local" => Some(FontSource::Local(argument.to_string())),
+        _ => None,
+    }
+}
+
+// Parses `U+26`, `U+0-7F` or `U+4??`, where each `?` stands for any hex digit.
+fn parse_unicode_range(value: &str) -> Option<RangeInclusive<u32>> {
+    let value = value
+        .strip_prefix("U+")
+        .or_else(|| value.strip_prefix("u+"))?;
+    let hex = |digits: &str| {
+        (!digits.is_empty() && digits.len() <= 6)
+            .then(|| u32::from_str_radix(digits, 16).ok())
+            .flatten()
+    };
+
+    let range = match value.split_once('-') {
+        Some((start, end)) => hex(start)?..=hex(end)?,
+        None if value.contains('?') => {
+            hex(&value.replace('?', "0"))?..=hex(&value.replace('?', "F"))?
+        }
+        None => hex(value)?..=hex(value)?,
+    };
+
+    (range.start() <= range.end()).then_some(range)
+}
+
+// Parses a `font-weight`, `bolder` and `lighter` being relative to the inherited `weight`.
+fn parse_font_weight(value: &str, weight: fontdb::Weight) -> Option<fontdb::Weight> {
+    Some(match value {
+        "normal" => fontdb::Weight::NORMAL,
+        "bold" => fontdb::Weight::BOLD,
+        "bolder" => fontdb::Weight(match weight.0 {
+            ..350 => 400,
+            350..550 => 700,
+            _ => 900,
+        }),
+        "lighter" => fontdb::Weight(match weight.0 {
+            ..550 => 100,
+            550..750 => 400,
+            _ => 700,
+        }),
+        value => fontdb::Weight(
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|weight| (1.0..=1000.0).contains(weight))?
+                .round() as u16,
+        ),
+    })
+}
+
+// An angle after `oblique` is accepted, as the slant can't be chosen.
+fn parse_font_style(value: &str) -> Option<fontdb::Style> {
+    match *split_top_level(value) {
+        ["normal"] => Some(fontdb::Style::Normal),
+        ["italic"] => Some(fontdb::Style::Italic),
+        ["oblique"] | ["oblique", _] => Some(fontdb::Style::Oblique),
+        _ => None,
+    }
+}
+
+fn parse_font_stretch(value: &str) -> Option<fontdb::Stretch> {
+    Some(match value {
+        "ultra-condensed" => fontdb::Stretch::UltraCondensed,
+        "extra-condensed" => fontdb::Stretch::ExtraCondensed,
+        "condensed" => fontdb::Stretch::Condensed,
+        "semi-condensed" => fontdb::Stretch::SemiCondensed,
+        "normal" => fontdb::Stretch::Normal,
+        "semi-expanded" => fontdb::Stretch::SemiExpanded,
+        "expanded" => fontdb::Stretch::Expanded,
+        "extra-expanded" => fontdb::Stretch::ExtraExpanded,
+        "ultra-expanded" => fontdb::Stretch::UltraExpanded,
+        value => fonts::stretch(value.strip_suffix('%')?.parse().ok()?),
+    })
+}
+
+fn parse_font_size(value: &str) -> Option<Units> {
+    Some(match value {
+        "xx-small" => Units::Px(9.0),
+        "x-small" => Units::Px(10.0),
+        "small" => Units::Px(13.0),
+        "medium" => Units::Px(16.0),
+        "large" => Units::Px(18.0),
+        "x-large" => Units::Px(24.0),
+        "xx-large" => Units::Px(32.0),
+        "smaller" => Units::Em(0.83),
+        "larger" => Units::Em(1.2),
+        value => parse_length(value)?,
+    })
+}
+
+fn parse_line_height(value: &str) -> Option<Units> {
+    match value {
+        "normal" => Some(Units::Perc(120.0)),
+        value => match value.parse::<f32>() {
+            Ok(number) => Some(Units::Perc(number * 100.0)),
+            Err(_) => parse_length(value),
+        },
+    }
+}
+
+// Applies a single declaration to the node, loading `url()`s through `images`. Returns `None`
+// when the property is unsupported or the value is invalid, in which case the node is left
+// untouched.
+pub fn apply_declaration(
+    node: &mut Node,
+    declaration: &Declaration,
+    images: &ImageCache,
+) -> Option<()> {
+    let value = declaration.value.as_str();
+    let property = declaration.property.as_str();
+
+    match property {
+        "display" => {
+            node.style.display = match value {
+                "block" => Display::Block,
+                "inline" => Display::Inline,
+                "inline-block" => Display::InlineBlock,
+                "contents" => Display::Contents,
+                "none" => Display::None,
+                _ => return None,
+            }
+        }
+        "position" => {
+            node.style.position = match value {
+                "static" => Position::Static,
+                "relative" => Position::Relative,
+                "absolute" => Position::Absolute,
+                "fixed" => Position::Fixed,
+                "sticky" => Position::Sticky,
+                _ => return None,
+            }
+        }
+        "box-sizing" => {
+            node.style.box_sizing = match value {
+                "content-box" => BoxSizing::ContentBox,
+                "border-box" => BoxSizing::BorderBox,
+                _ => return None,
+            }
+        }
+        "top" => node.style.top = parse_length(value)?,
+        "right" => node.style.right = parse_length(value)?,
+        "bottom" => node.style.bottom = parse_length(value)?,
+        "left" => node.style.left = parse_length(value)?,
+        "width" => node.style.width = parse_length(value)?,
+        "height" => node.style.height = parse_length(value)?,
+        "min-width" => node.style.min_width = parse_length(value)?,
+        "min-height" => node.style.min_height = parse_length(value)?,
+        "max-width" => node.style.max_width = parse_length(value)?,
+        "max-height" => node.style.max_height = parse_length(value)?,
+        "margin" => node.style.margin = parse_sides(value)?,
+        "padding" => node.style.padding = parse_sides(value)?,
+        "border-width" => node.style.border_size = parse_sides(value)?,
+        "border-radius" => node.style.border_radius = parse_sides(value)?,
+        "border" => apply_border(node, &[0, 1, 2, 3], value)?,
+        "border-color" => {
+            let color = parse_border_color(value)?;
+            (0..4).for_each(|side| set_border_color(node, side, color));
+        }
+        "border-style" => node.style.border_style = expand_sides(value, parse_border_style)?,
+        "outline" => {
+            for token in split_top_level(value) {
+                if let Some(length) = parse_length(token) {
+                    node.style.outline_width = length;
+                } else if let Some(color) = parse_color(token) {
+                    node.outline.color = color;
+                }
+            }
+        }
+        "outline-width" => node.style.outline_width = parse_length(value)?,
+        "outline-offset" => node.style.outline_offset = parse_length(value)?,
+        "outline-color" => node.outline.color = parse_color(value)?,
+        "background-color" => node.background_color = parse_color(value)?,
+        "background-image" => node.background.image = parse_background_image(value, images)?,
+        "background-size" => {
+            node.background.size = parse_layers(value, |layer| {
+                parse_background_size(&split_top_level(layer))
+            })?
+        }
+        "background-position" => {
+            node.background.position =
+                parse_layers(value, |layer| parse_position(&split_top_level(layer)))?
+        }
+        "background-repeat" => {
+            node.background.repeat = parse_layers(value, |layer| {
+                parse_background_repeat(&split_top_level(layer))
+            })?
+        }
+        "background-origin" => node.background.origin = parse_layers(value, parse_background_box)?,
+        "background-clip" => node.background.clip = parse_layers(value, parse_background_box)?,
+        "background" => apply_background(node, value, images)?,
+        "object-fit" => {
+            node.style.object_fit = match value {
+                "fill" => ObjectFit::Fill,
+                "contain" => ObjectFit::Contain,
+                "cover" => ObjectFit::Cover,
+                "none" => ObjectFit::None,
+                "scale-down" => ObjectFit::ScaleDown,
+                _ => return None,
+            }
+        }
+        "object-position" => node.style.object_position = parse_position(&split_top_level(value))?,
+        "color" => {
+            let [r, g, b, a] = parse_color(value)?.map(|c| (c * 255.0).round() as u8);
+            node.style.font_color = Color::rgba(r, g, b, a);
+        }
+        "font-size" => node.style.font_size = parse_font_size(value)?,
+        "line-height" => node.style.line_height = parse_line_height(value)?,
+        "font-family" => node.style.font_family = parse_font_family(value)?,
+        "font-weight" => node.style.font_weight = parse_font_weight(value, node.style.font_weight)?,
+        "font-style" => node.style.font_style = parse_font_style(value)?,
+        "font-stretch" => node.style.font_stretch = parse_font_stretch(value)?,
+        "font-variant" => {
+            node.style.font_variant = match value {
+                "normal" => FontVariant::Normal,
+                "small-caps" => FontVariant::SmallCaps,
+                "all-small-caps" => FontVariant::AllSmallCaps,
+                _ => return None,
+            }
+        }
+        "text-align" => {
+            node.style.text_align = match value {
+                "start" => TextAlign::Start,
+                "end" => TextAlign::End,
+                "left" => TextAlign::Left,
+                "right" => TextAlign::Right,
+                "center" => TextAlign::Center,
+                "justify" => TextAlign::Justify,
+                _ => return None,
+            }
+        }
+        "text-indent" => node.style.text_indent = parse_length(value)?,
+        "white-space" => {
+            node.style.white_space = match value {
+                "normal" => WhiteSpace::Normal,
+                "nowrap" => WhiteSpace::Nowrap,
+                "pre" => WhiteSpace::Pre,
+                "pre-wrap" => WhiteSpace::PreWrap,
+                "pre-line" => WhiteSpace::PreLine,
+                "break-spaces" => WhiteSpace::BreakSpaces,
+                _ => return None,
+            }
+        }
+        "word-break" => {
+            node.style.word_break = match value {
+                "normal" => WordBreak::Normal,
+                "break-all" => WordBreak::BreakAll,
+                "keep-all" => WordBreak::KeepAll,
+                "break-word" => WordBreak::BreakWord,
+                _ => return None,
+            }
+        }
+        "overflow-wrap" | "word-wrap" => {
+            node.style.overflow_wrap = match value {
+                "normal" => OverflowWrap::Normal,
+                "break-word" => OverflowWrap::BreakWord,
+                "anywhere" => OverflowWrap::Anywhere,
+                _ => return None,
+            }
+        }
+        "hyphens" => {
+            node.style.hyphens = match value {
+                "none" => Hyphens::None,
+                "manual" => Hyphens::Manual,
+                "auto" => Hyphens::Auto,
+                _ => return None,
+            }
+        }
+        "opacity" => node.opacity = parse_amount(value)?.clamp(0.0, 1.0),
+        "filter" => node.filter = parse_filters(value)?,
+        "backdrop-filter" => node.backdrop_filter = parse_filters(value)?,
+        "clip-path" => {
+            node.clip_path = match value {
+                "none" => None,
+                value => Some(parse_clip_path(value)?),
+            }
+        }
+        "mask-image" => node.mask.image = parse_background_image(value, images)?,
+        "mask-size" => {
+            node.mask.size = parse_layers(value, |layer| {
+                parse_background_size(&split_top_level(layer))
+            })?
+        }
+        "mask-position" => {
+            node.mask.position =
+                parse_layers(value, |layer| parse_position(&split_top_level(layer)))?
+        }
+        "mask-repeat" => {
+            node.mask.repeat = parse_layers(value, |layer| {
+                parse_background_repeat(&split_top_level(layer))
+            })?
+        }
+        "mask-mode" => {
+            node.mask.mode = parse_layers(value, |layer| match layer {
+                "alpha" => Some(MaskMode::Alpha),
+                "luminance" => Some(MaskMode::Luminance),
+                "match-source" => Some(MaskMode::MatchSource),
+                _ => None,
+            })?
+        }
+        "transform" => node.transform = parse_transforms(value)?,
+        "transform-origin" => node.transform_origin = parse_position(&split_top_level(value))?,
+        "transform-style" => {
+            node.transform_style = match value {
+                "flat" => TransformStyle::Flat,
+                "preserve-3d" => TransformStyle::Preserve3d,
+                _ => return None,
+            }
+        }
+        "perspective" => {
+            node.perspective = match value {
+                "none" => None,
+                value => Some(parse_length(value)?),
+            }
+        }
+        "perspective-origin" => node.perspective_origin = parse_position(&split_top_level(value))?,
+        "backface-visibility" => {
+            node.backface_visibility = match value {
+                "visible" => BackfaceVisibility::Visible,
+                "hidden" => BackfaceVisibility::Hidden,
+                _ => return None,
+            }
+        }
+        "box-shadow" => {
+            node.box_shadow = match value {
+                "none" => Vec::new(),
+                value => split_list(value)
+                    .into_iter()
+                    .map(parse_box_shadow)
+                    .collect::<Option<_>>()?,
+            }
+        }
+        property => {
+            let mut parts = property.splitn(3, '-');
+            let (Some(prefix), Some(side)) = (parts.next(), parts.next()) else {
+                return None;
+            };
+            let side = side_index(side)?;
+
+            match (prefix, parts.next()) {
+                ("margin", None) => node.style.margin[side] = parse_length(value)?,
+                ("padding", None) => node.style.padding[side] = parse_length(value)?,
+                ("border", None) => apply_border(node, &[side], value)?,
+                ("border", Some("width")) => node.style.border_size[side] = parse_length(value)?,
+                ("border", Some("color")) => {
+                    set_border_color(node, side, parse_border_color(value)?)
+                }
+                ("border", Some("style")) => {
+                    node.style.border_style[side] = parse_border_style(value)?
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    Some(())
+}
@@ -1,7 +1,19 @@
 use calc_units::{Context, Units};
-use glyphon::{Color, FamilyOwned};
+use glyphon::{cosmic_text::Wrap, Attrs, Color, FamilyOwned, Stretch, Weight};
 
-use crate::tree::{node::ParentState, State};
+use crate::{
+    background::{Background, BackgroundBox, BackgroundImage, Layer},
+    clip::{self, ClipPath, ClipShape},
+    filter::{ColorMatrix, Filter},
+    fonts::FontVariant,
+    gradient::GradientStop,
+    images::ObjectFit,
+    mask::Mask,
+    math::{Mat4, Matrix},
+    text::{Hyphens, OverflowWrap, TextAlign, WhiteSpace, WordBreak},
+    transform::{self, BackfaceVisibility, Transform, TransformStyle},
+    tree::{atlas::ImageAtlas, node::ParentState, State},
+};
 
 #[repr(C, align(16))]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -12,19 +24,12 @@ pub struct InstanceData {
     outline_width: f32,
     outline_offset: f32,
 
-    scale: [f32; 2],
-    skew: [f32; 2],
-    rotation: f32,
+    kind: u32,
+    shadow_blur: f32,
+    // The node's accumulated transform, taking the instance from layout to document
+    // coordinates.
+    transform: Mat4,
 
-    invert: f32,
-    brightness: f32,
-    saturate: f32,
-    contrast: f32,
-    grayscale: f32,
-    sepia: f32,
-    hue_rotate: f32,
-
-    _padding: [u8; 8],
     rect_color: [f32; 4],
     outline_color: [f32; 4],
     border_radius: [f32; 4],
@@ -33,6 +38,198 @@ pub struct InstanceData {
     border_right_color: [f32; 4],
     border_bottom_color: [f32; 4],
     border_left_color: [f32; 4],
+
+    // Shadow instances only: the shape casting the shadow and the box it is clipped to, both
+    // as x, y, width, height. Outer shadows are cut out of the clip box, inner ones kept in it.
+    shadow_box: [f32; 4],
+    shadow_radius: [f32; 4],
+    clip_box: [f32; 4],
+
+    // Gradient instances only, see `ResolvedGradient`. The stops are an offset and count into
+    // the gradient stop buffer.
+    gradient: [f32; 4],
+    gradient_stops: [u32; 2],
+    repeating: u32,
+
+    // Background layers only: bit 0 and 1 repeat the tile horizontally and vertically, see
+    // `Tile`. `uv` is the image's texel rectangle in the atlas.
+    background_repeat: u32,
+    tile: [f32; 4],
+    uv: [f32; 4],
+    tile_spacing: [f32; 2],
+    // `BorderStyle::code` of the top, right, bottom and left side in successive bytes.
+    border_style: u32,
+
+    // Layer instances only: the standard deviation of blur passes, which keep their edges at
+    // `uv` if any, and the color filters folded into one `ColorMatrix` for composites, which
+    // are clipped to their quad with `border_radius` corners, the ellipse in it or the mask at
+    // `uv` in the atlas, depending on the kind. They read their layer `tile.xy` away, which
+    // moves shadows and lines layers of different origins up, and masked passes read a second
+    // layer `tile.zw` away.
+    layer_blur: f32,
+    color_matrix: [[f32; 4]; 4],
+    color_offset: [f32; 4],
+}
+
+// Values of `InstanceData::kind`, matching the constants in the shader.
+const KIND_BOX: u32 = 0;
+const KIND_OUTER_SHADOW: u32 = 1;
+const KIND_INNER_SHADOW: u32 = 2;
+const KIND_IMAGE: u32 = 6;
+const KIND_LAYER: u32 = 7;
+const KIND_LAYER_BLUR_X: u32 = 8;
+const KIND_LAYER_BLUR_Y: u32 = 9;
+const KIND_LAYER_SHADOW: u32 = 10;
+const KIND_LAYER_PLACED: u32 = 11;
+const KIND_LAYER_ELLIPSE: u32 = 12;
+const KIND_LAYER_MASK: u32 = 13;
+const KIND_LAYER_LUMINANCE: u32 = 14;
+const KIND_LAYER_MASKED: u32 = 15;
+
+// How far blur passes reach, in texels, as in the shader. Larger blurs are cut down to what
+// fits, three standard deviations each way.
+const MAX_BLUR_RADIUS: f32 = 96.0;
+
+impl InstanceData {
+    pub(crate) fn with_transform(self, transform: &Mat4) -> Self {
+        Self {
+            transform: *transform,
+            ..self
+        }
+    }
+
+    pub(crate) fn with_color_matrix(self, matrix: &ColorMatrix) -> Self {
+        let (color_matrix, color_offset) = matrix.columns();
+        Self {
+            color_matrix,
+            color_offset,
+            ..self
+        }
+    }
+
+    // Layer instances cover `rect` (x, y, width, height) and, except for placed layers, read
+    // the texel under the pixel being drawn, `tile.xy` away once placed.
+    fn layer_pass(rect: [f32; 4], kind: u32) -> Self {
+        Self {
+            rect_pos: [rect[0], rect[1]],
+            rect_size: [rect[2], rect[3]],
+            kind,
+            transform: Mat4::IDENTITY,
+            ..bytemuck::Zeroable::zeroed()
+        }
+    }
+
+    /// The box the instance covers in viewport pixels, `None` when part of it is behind the
+    /// viewer.
+    pub(crate) fn bounds(&self, scroll: [f32; 2]) -> Option<[f32; 4]> {
+        let [x, y, width, height] = self.transform.bounds([
+            self.rect_pos[0],
+            self.rect_pos[1],
+            self.rect_size[0],
+            self.rect_size[1],
+        ])?;
+        Some([x - scroll[0], y - scroll[1], width, height])
+    }
+
+    /// Where a layer instance can draw the layer it reads when what is drawn into that layer
+    /// lies within `source`: moved by shadows and spread by blurs. `None` for placed layers,
+    /// which are only bounded by their quad.
+    pub(crate) fn reach(&self, [x, y, width, height]: [f32; 4]) -> Option<[f32; 4]> {
+        let radius = (self.layer_blur * 3.0).ceil().min(MAX_BLUR_RADIUS);
+        match self.kind {
+            KIND_LAYER_SHADOW => Some([x + self.tile[0], y + self.tile[1], width, height]),
+            KIND_LAYER_BLUR_X => Some([x - radius, y, width + radius * 2.0, height]),
+            KIND_LAYER_BLUR_Y => Some([x, y - radius, width, height + radius * 2.0]),
+            KIND_LAYER_PLACED => None,
+            _ => Some([x, y, width, height]),
+        }
+    }
+
+    /// Lines a layer instance up with the layer it reads, given the viewport pixel at the top
+    /// left of the layer it draws into, of `layer` and of the `mask` layer masked passes read.
+    pub(crate) fn place(&mut self, target: [f32; 2], layer: [f32; 2], mask: [f32; 2]) {
+        match self.kind {
+            KIND_LAYER_PLACED => self.tile = [layer[0], layer[1], 0.0, 0.0],
+            _ => {
+                self.tile[0] += layer[0] - target[0];
+                self.tile[1] += layer[1] - target[1];
+                self.tile[2] = mask[0] - target[0];
+                self.tile[3] = mask[1] - target[1];
+                // Blur edges go from viewport pixels to texels of the layer.
+                if matches!(self.kind, KIND_LAYER_BLUR_X | KIND_LAYER_BLUR_Y) {
+                    self.uv[0] -= layer[0];
+                    self.uv[1] -= layer[1];
+                }
+            }
+        }
+    }
+
+    /// Composites a layer with `matrix` applied to its colors.
+    pub(crate) fn layer(rect: [f32; 4], matrix: &ColorMatrix) -> Self {
+        Self::layer_pass(rect, KIND_LAYER).with_color_matrix(matrix)
+    }
+
+    /// One direction of a gaussian blur with standard deviation `sigma`, at most a third of
+    /// `MAX_BLUR_RADIUS`. Outside the layer, texels are transparent, or with
+    /// `duplicate_edges` the ones on the edge of the viewport `rect` covers, like SVG's
+    /// `edgeMode="duplicate"`.
+    pub(crate) fn layer_blur(
+        rect: [f32; 4],
+        sigma: f32,
+        vertical: bool,
+        duplicate_edges: bool,
+    ) -> Self {
+        let kind = match vertical {
+            false => KIND_LAYER_BLUR_X,
+            true => KIND_LAYER_BLUR_Y,
+        };
+        Self {
+            layer_blur: sigma.min(MAX_BLUR_RADIUS / 3.0),
+            uv: match duplicate_edges {
+                true => [0.0, 0.0, rect[2], rect[3]],
+                false => [0.0; 4],
+            },
+            ..Self::layer_pass(rect, kind)
+        }
+    }
+
+    /// The layer's alpha moved by `offset` and filled with `color`.
+    pub(crate) fn layer_shadow(rect: [f32; 4], offset: [f32; 2], color: [f32; 4]) -> Self {
+        Self {
+            rect_color: color,
+            tile: [offset[0], offset[1], 0.0, 0.0],
+            ..Self::layer_pass(rect, KIND_LAYER_SHADOW)
+        }
+    }
+
+    /// Draws a layer with its top left texel on the corner of `rect`, filtered, so it can be
+    /// transformed with the instance.
+    pub(crate) fn placed_layer(rect: [f32; 4]) -> Self {
+        Self::layer_pass(rect, KIND_LAYER_PLACED)
+    }
+
+    /// Composites a layer within the ellipse inscribed in `rect`.
+    pub(crate) fn ellipse_layer(rect: [f32; 4]) -> Self {
+        Self::layer_pass(rect, KIND_LAYER_ELLIPSE).with_color_matrix(&ColorMatrix::IDENTITY)
+    }
+
+    /// Composites a layer with the alpha of the atlas texels `uv` stretched over `rect`.
+    pub(crate) fn masked_layer(rect: [f32; 4], uv: [f32; 4]) -> Self {
+        Self {
+            uv,
+            ..Self::layer_pass(rect, KIND_LAYER_MASK).with_color_matrix(&ColorMatrix::IDENTITY)
+        }
+    }
+
+    /// Composites a layer as white with its luminance for alpha, for `mask-mode: luminance`.
+    pub(crate) fn luminance_layer(rect: [f32; 4]) -> Self {
+        Self::layer_pass(rect, KIND_LAYER_LUMINANCE)
+    }
+
+    /// A layer with its alpha scaled by the alpha of the mask layer bound along with it.
+    pub(crate) fn masked_pass(rect: [f32; 4]) -> Self {
+        Self::layer_pass(rect, KIND_LAYER_MASKED)
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -44,34 +241,88 @@ pub enum Position {
     Sticky,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum BoxSizing {
     ContentBox,
     BorderBox,
 }
 
+/// `border-style` of one side. `None` and `Hidden` collapse the border width to zero.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BorderStyle {
+    None,
+    Hidden,
+    Solid,
+    Dashed,
+    Dotted,
+    Double,
+    Groove,
+    Ridge,
+    Inset,
+    Outset,
+}
+
+impl BorderStyle {
+    // Code for the shader, matching the `BORDER_*` constants there.
+    fn code(self) -> u32 {
+        match self {
+            Self::None | Self::Hidden | Self::Solid => 0,
+            Self::Dashed => 1,
+            Self::Dotted => 2,
+            Self::Double => 3,
+            Self::Groove => 4,
+            Self::Ridge => 5,
+            Self::Inset => 6,
+            Self::Outset => 7,
+        }
+    }
+}
+
 pub struct Border {
     pub radius: [f32; 4],
     pub size: [f32; 4],
-    pub top_color: [f32; 4],
-    pub bottom_color: [f32; 4],
-    pub left_color: [f32; 4],
-    pub right_color: [f32; 4],
+    /// A `None` color uses the font color, like `currentColor`.
+    pub top_color: Option<[f32; 4]>,
+    pub bottom_color: Option<[f32; 4]>,
+    pub left_color: Option<[f32; 4]>,
+    pub right_color: Option<[f32; 4]>,
 }
 
 impl Default for Border {
     fn default() -> Self {
         Self {
             radius: [0.0, 0.0, 0.0, 0.0],
-            top_color: [0.0, 0.0, 0.0, 0.0],
-            bottom_color: [0.0, 0.0, 0.0, 0.0],
-            left_color: [0.0, 0.0, 0.0, 0.0],
-            right_color: [0.0, 0.0, 0.0, 0.0],
+            top_color: Some([0.0, 0.0, 0.0, 0.0]),
+            bottom_color: Some([0.0, 0.0, 0.0, 0.0]),
+            left_color: Some([0.0, 0.0, 0.0, 0.0]),
+            right_color: Some([0.0, 0.0, 0.0, 0.0]),
             size: [0.0, 0.0, 0.0, 0.0],
         }
     }
 }
 
+/// One layer of `box-shadow`. A `None` color uses the font color, like `currentColor`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BoxShadow {
+    pub offset: [Units; 2],
+    pub blur: Units,
+    pub spread: Units,
+    pub color: Option<[f32; 4]>,
+    pub inset: bool,
+}
+
+impl Default for BoxShadow {
+    fn default() -> Self {
+        Self {
+            offset: [Units::Px(0.0), Units::Px(0.0)],
+            blur: Units::Px(0.0),
+            spread: Units::Px(0.0),
+            color: None,
+            inset: false,
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum Display {
     Inline,
@@ -81,6 +332,7 @@ pub enum Display {
     None,
 }
 
+#[derive(PartialEq, Clone)]
 pub struct Style {
     pub top: Units,
     pub right: Units,
@@ -92,19 +344,42 @@ pub struct Style {
     pub padding: [Units; 4],
     pub border_size: [Units; 4],
     pub border_radius: [Units; 4],
+    /// Top, right, bottom and left. Unlike CSS the default is solid, so setting a width is
+    /// enough to show a border.
+    pub border_style: [BorderStyle; 4],
     pub outline_width: Units,
     pub outline_offset: Units,
     pub box_sizing: BoxSizing,
     pub font_color: Color,
     pub font_size: Units,
     pub line_height: Units,
-    pub font_family: FamilyOwned,
+    /// Families tried in order for each character, like the `font-family` fallback list.
+    pub font_family: Vec<FamilyOwned>,
+    pub font_weight: Weight,
+    pub font_style: glyphon::Style,
+    pub font_stretch: Stretch,
+    pub font_variant: FontVariant,
+    pub text_align: TextAlign,
+    /// Indents the first line of text, and the first row of inline boxes. Percentages refer to
+    /// the width of the parent. Negative indents are not supported by the shaper and leave the
+    /// text unindented.
+    pub text_indent: Units,
+    /// How the white space of text collapses and whether its lines wrap. A paragraph of rich
+    /// text or inline elements wraps by the style of the node it starts at.
+    pub white_space: WhiteSpace,
+    pub word_break: WordBreak,
+    pub overflow_wrap: OverflowWrap,
+    pub hyphens: Hyphens,
     pub height: Units,
     pub width: Units,
     pub max_width: Units,
     pub max_height: Units,
     pub min_width: Units,
     pub min_height: Units,
+    pub object_fit: ObjectFit,
+    /// Offsets of an image node's picture within its content box, percentages aligning that
+    /// point of the picture with the same point of the box.
+    pub object_position: [Units; 2],
 }
 
 impl Style {
@@ -139,6 +414,29 @@ impl Style {
             .max(self.min_width.to_px(&min_context))
             .min(self.max_width.to_px(&max_context))
     }
+
+    /// The weight, style and stretch text is shaped with.
+    pub fn font_attrs(&self) -> Attrs<'static> {
+        Attrs::new()
+            .weight(self.font_weight)
+            .style(self.font_style)
+            .stretch(self.font_stretch)
+    }
+
+    /// Where lines of text wrap, by `white-space`, `word-break` and `overflow-wrap`.
+    pub fn text_wrap(&self) -> Wrap {
+        if !self.white_space.wraps() {
+            Wrap::None
+        } else if self.word_break == WordBreak::BreakAll {
+            Wrap::Glyph
+        } else if self.word_break == WordBreak::BreakWord
+            || self.overflow_wrap != OverflowWrap::Normal
+        {
+            Wrap::WordOrGlyph
+        } else {
+            Wrap::Word
+        }
+    }
 }
 
 impl Default for Style {
@@ -158,15 +456,28 @@ impl Default for Style {
             padding: [const { Units::Px(0.0) }; 4],
             border_size: [const { Units::Px(0.0) }; 4],
             border_radius: [const { Units::Px(0.0) }; 4],
+            border_style: [BorderStyle::Solid; 4],
             box_sizing: BoxSizing::ContentBox,
             font_size: Units::Px(16.0),
             font_color: Color::rgb(255, 255, 255),
             line_height: Units::Perc(120.0),
-            font_family: FamilyOwned::Serif,
+            font_family: vec![FamilyOwned::Serif],
+            font_weight: Weight::NORMAL,
+            font_style: glyphon::Style::Normal,
+            font_stretch: Stretch::Normal,
+            font_variant: FontVariant::Normal,
+            text_align: TextAlign::Start,
+            text_indent: Units::Px(0.0),
+            white_space: WhiteSpace::Normal,
+            word_break: WordBreak::Normal,
+            overflow_wrap: OverflowWrap::Normal,
+            hyphens: Hyphens::Manual,
             max_width: Units::Auto,
             max_height: Units::Auto,
             min_width: Units::Auto,
             min_height: Units::Auto,
+            object_fit: ObjectFit::Fill,
+            object_position: [Units::Perc(50.0), Units::Perc(50.0)],
         }
     }
 }
@@ -181,19 +492,35 @@ pub struct Rectangle {
     pub padding: [f32; 4],
     pub border: Border,
     pub outline: Outline,
-    pub brightness: f32,
-    pub contrast: f32,
-    pub grayscale: f32,
-    pub hue_rotate: f32,
-    pub invert: f32,
-    pub saturate: f32,
-    pub sepia: f32,
-    pub scale: [f32; 2],
-    pub rotate: f32,
-    pub skew: [f32; 2],
-    pub translate: [f32; 2],
+    /// Shadows in CSS order, the first one painted on top.
+    pub box_shadow: Vec<BoxShadow>,
+    pub background: Background,
+    /// Applies to the node and its descendants as a group, which is drawn into an offscreen
+    /// layer first whenever it is below 1.
+    pub opacity: f32,
+    /// Applied in order to the node and its descendants drawn into an offscreen layer, before
+    /// `opacity`.
+    pub filter: Vec<Filter>,
+    /// Applied to what is drawn behind the node, shown within its border box.
+    pub backdrop_filter: Vec<Filter>,
+    pub clip_path: Option<ClipPath>,
+    /// Masks the node and its descendants drawn into an offscreen layer, after `filter` and
+    /// before `opacity`.
+    pub mask: Mask,
+    /// Moves the node and its descendants without affecting layout, see `Transform`.
+    pub transform: Vec<Transform>,
+    /// Point of the border box the transform is applied around. Percentages refer to its size.
+    pub transform_origin: [Units; 2],
+    /// Distance of the viewer from the plane of the node, for its children. `None` keeps them
+    /// flat.
+    pub perspective: Option<Units>,
+    /// Point of the border box the children are seen from with `perspective`.
+    pub perspective_origin: [Units; 2],
+    pub transform_style: TransformStyle,
+    pub backface_visibility: BackfaceVisibility,
     pub font_size: f32,
     pub line_height: f32,
+    pub text_indent: f32,
 
     pub style: Style,
 }
@@ -210,19 +537,22 @@ impl Default for Rectangle {
             background_color: [0.0, 0.0, 0.0, 0.0],
             border: Border::default(),
             outline: Outline::default(),
-            brightness: 0.0,
-            contrast: 1.0,
-            grayscale: 0.0,
-            hue_rotate: 0.0,
-            invert: 0.0,
-            saturate: 1.0,
-            sepia: 0.0,
-            scale: [1.0, 1.0],
-            rotate: 0.0,
-            skew: [0.0, 0.0],
-            translate: [0.0, 0.0],
+            box_shadow: Vec::new(),
+            background: Background::default(),
+            opacity: 1.0,
+            filter: Vec::new(),
+            backdrop_filter: Vec::new(),
+            clip_path: None,
+            mask: Mask::default(),
+            transform: Vec::new(),
+            transform_origin: [Units::Perc(50.0), Units::Perc(50.0)],
+            perspective: None,
+            perspective_origin: [Units::Perc(50.0), Units::Perc(50.0)],
+            transform_style: TransformStyle::Flat,
+            backface_visibility: BackfaceVisibility::Visible,
             font_size: 16.0,
             line_height: 16.0 * 1.2,
+            text_indent: 0.0,
 
             style: Style::default(),
         }
@@ -230,6 +560,30 @@ impl Default for Rectangle {
 }
 
 impl Rectangle {
+    /// Copies the properties that are set directly rather than computed from `style`.
+    pub fn copy_paint_properties(&mut self, other: &Rectangle) {
+        self.background_color = other.background_color;
+        self.border.top_color = other.border.top_color;
+        self.border.right_color = other.border.right_color;
+        self.border.bottom_color = other.border.bottom_color;
+        self.border.left_color = other.border.left_color;
+        self.outline.color = other.outline.color;
+        self.box_shadow.clone_from(&other.box_shadow);
+        self.background.clone_from(&other.background);
+        self.opacity = other.opacity;
+        self.filter.clone_from(&other.filter);
+        self.backdrop_filter.clone_from(&other.backdrop_filter);
+        self.clip_path.clone_from(&other.clip_path);
+        self.mask.clone_from(&other.mask);
+        self.transform.clone_from(&other.transform);
+        self.transform_origin.clone_from(&other.transform_origin);
+        self.perspective.clone_from(&other.perspective);
+        self.perspective_origin
+            .clone_from(&other.perspective_origin);
+        self.transform_style = other.transform_style;
+        self.backface_visibility = other.backface_visibility;
+    }
+
     pub fn get_render_extents(&self, parent_state: &ParentState, state: &State) -> Extents {
         let context = Context {
             root_font_size: state.root_font_size,
@@ -240,10 +594,8 @@ impl Rectangle {
             auto: 0.0,
         };
 
-        let x =
-            self.x + self.margin[3] - self.outline.width - self.outline.offset + self.translate[0];
-        let y =
-            self.y + self.margin[0] - self.outline.width - self.outline.offset + self.translate[1];
+        let x = self.x + self.margin[3] - self.outline.width - self.outline.offset;
+        let y = self.y + self.margin[0] - self.outline.width - self.outline.offset;
 
         let (x, y) = match self.style.position {
             Position::Fixed => (x + state.scroll.0, y + state.scroll.1),
@@ -443,6 +795,7 @@ impl Rectangle {
 
     pub fn get_instance_data(&self, parent_state: &ParentState, state: &State) -> InstanceData {
         let extents = self.get_render_extents(parent_state, state);
+        let current_color = self.current_color();
 
         InstanceData {
             rect_pos: [extents.x, extents.y],
@@ -451,49 +804,507 @@ impl Rectangle {
             outline_width: self.outline.width,
             outline_offset: self.outline.offset,
 
-            rotation: self.rotate,
-            scale: self.scale,
-            skew: self.skew,
-
-            invert: self.invert,
-            brightness: self.brightness,
-            saturate: self.saturate,
-            contrast: self.contrast,
-            grayscale: self.grayscale,
-            sepia: self.sepia,
-            hue_rotate: self.hue_rotate,
-
-            _padding: [const { 0 }; 8],
+            kind: KIND_BOX,
+            shadow_blur: 0.0,
+            transform: Mat4::IDENTITY,
             rect_color: self.background_color,
             outline_color: self.outline.color,
             border_size: self.border.size,
             border_radius: self.border.radius,
-            border_top_color: [
-                self.border.top_color[0] * self.border.top_color[3],
-                self.border.top_color[1] * self.border.top_color[3],
-                self.border.top_color[2] * self.border.top_color[3],
-                self.border.top_color[3],
-            ],
-            border_right_color: [
-                self.border.right_color[0] * self.border.right_color[3],
-                self.border.right_color[1] * self.border.right_color[3],
-                self.border.right_color[2] * self.border.right_color[3],
-                self.border.right_color[3],
-            ],
-            border_bottom_color: [
-                self.border.bottom_color[0] * self.border.bottom_color[3],
-                self.border.bottom_color[1] * self.border.bottom_color[3],
-                self.border.bottom_color[2] * self.border.bottom_color[3],
-                self.border.bottom_color[3],
-            ],
-            border_left_color: [
-                self.border.left_color[0] * self.border.left_color[3],
-                self.border.left_color[1] * self.border.left_color[3],
-                self.border.left_color[2] * self.border.left_color[3],
-                self.border.left_color[3],
-            ],
+            border_top_color: self.border.top_color.unwrap_or(current_color),
+            border_right_color: self.border.right_color.unwrap_or(current_color),
+            border_bottom_color: self.border.bottom_color.unwrap_or(current_color),
+            border_left_color: self.border.left_color.unwrap_or(current_color),
+
+            shadow_box: [0.0; 4],
+            shadow_radius: [0.0; 4],
+            clip_box: [0.0; 4],
+
+            gradient: [0.0; 4],
+            gradient_stops: [0, 0],
+            repeating: 0,
+
+            background_repeat: 0,
+            tile: [0.0; 4],
+            uv: [0.0; 4],
+            tile_spacing: [0.0; 2],
+            border_style: self
+                .style
+                .border_style
+                .iter()
+                .enumerate()
+                .fold(0, |styles, (side, style)| {
+                    styles | style.code() << (side * 8)
+                }),
+
+            layer_blur: 0.0,
+            color_matrix: [[0.0; 4]; 4],
+            color_offset: [0.0; 4],
         }
     }
+
+    /// The layer for `backdrop-filter`, clipped to the rounded border box.
+    pub(crate) fn get_backdrop_instance(
+        &self,
+        parent_state: &ParentState,
+        state: &State,
+    ) -> InstanceData {
+        InstanceData {
+            border_radius: self.border.radius,
+            ..InstanceData::layer(
+                self.paint_boxes(parent_state, state).border_box,
+                &ColorMatrix::IDENTITY,
+            )
+        }
+    }
+
+    /// `transform` around `transform-origin`, in layout coordinates.
+    pub(crate) fn local_transform(&self, parent_state: &ParentState, state: &State) -> Mat4 {
+        if self.transform.is_empty() {
+            return Mat4::IDENTITY;
+        }
+
+        let [x, y, width, height] = self.paint_boxes(parent_state, state).border_box;
+        let origin = self.border_box_point(&self.transform_origin, [x, y, width, height], state);
+        transform::matrix(
+            &self.transform,
+            origin,
+            [width, height],
+            &self.paint_context(state),
+        )
+    }
+
+    /// `perspective` around `perspective-origin`, applied before the children's transforms.
+    pub(crate) fn children_perspective(&self, parent_state: &ParentState, state: &State) -> Mat4 {
+        let Some(perspective) = &self.perspective else {
+            return Mat4::IDENTITY;
+        };
+
+        let border_box = self.paint_boxes(parent_state, state).border_box;
+        let origin = self.border_box_point(&self.perspective_origin, border_box, state);
+        transform::perspective(perspective.to_px(&self.paint_context(state)), origin)
+    }
+
+    // A position such as `transform-origin` resolved within `border_box`.
+    fn border_box_point(
+        &self,
+        position: &[Units; 2],
+        border_box: [f32; 4],
+        state: &State,
+    ) -> [f32; 2] {
+        let [x, y, width, height] = border_box;
+        let length = |units: &Units, reference_size: f32| {
+            units.to_px(&Context {
+                reference_size,
+                ..self.paint_context(state)
+            })
+        };
+        [
+            x + length(&position[0], width),
+            y + length(&position[1], height),
+        ]
+    }
+
+    /// Whether `point`, in layout coordinates, is inside the rounded border box.
+    pub(crate) fn contains(
+        &self,
+        point: [f32; 2],
+        parent_state: &ParentState,
+        state: &State,
+    ) -> bool {
+        let border_box = self.paint_boxes(parent_state, state).border_box;
+        clip::in_rounded_rect(point, border_box, self.border.radius)
+    }
+
+    /// `clip-path` resolved on the border box.
+    pub(crate) fn clip_shape(
+        &self,
+        parent_state: &ParentState,
+        state: &State,
+    ) -> Option<ClipShape> {
+        let border_box = self.paint_boxes(parent_state, state).border_box;
+        let clip_path = self.clip_path.as_ref()?;
+        Some(clip_path.resolve(border_box, &self.paint_context(state)))
+    }
+
+    /// The composite of the node's layer through its `clip-path`. `None` when there is
+    /// nothing left to draw, such as for empty shapes.
+    pub(crate) fn get_clip_instance(
+        &self,
+        shape: &ClipShape,
+        atlas: &mut ImageAtlas,
+    ) -> Option<InstanceData> {
+        let rect = shape.rect();
+        if rect[2] <= 0.0 || rect[3] <= 0.0 {
+            return None;
+        }
+
+        let instance = match shape {
+            ClipShape::Rounded { radius, .. } => InstanceData {
+                border_radius: *radius,
+                ..InstanceData::layer(rect, &ColorMatrix::IDENTITY)
+            },
+            ClipShape::Ellipse { .. } => InstanceData::ellipse_layer(rect),
+            ClipShape::Path { .. } => {
+                let uv = atlas.allocate_mask(&shape.svg(rect)?, [rect[2], rect[3]])?;
+                InstanceData::masked_layer(rect, uv)
+            }
+        };
+        Some(instance)
+    }
+
+    /// The font color as `currentColor` resolves it.
+    pub(crate) fn current_color(&self) -> [f32; 4] {
+        let font_color = self.style.font_color;
+        [
+            font_color.r(),
+            font_color.g(),
+            font_color.b(),
+            font_color.a(),
+        ]
+        .map(|c| c as f32 / 255.0)
+    }
+
+    // Context for lengths resolved at paint time, where `em` refers to the node's own font.
+    pub(crate) fn paint_context(&self, state: &State) -> Context {
+        Context {
+            root_font_size: state.root_font_size,
+            dpi: state.dpi,
+            viewport: state.viewport,
+            parent_font_size: self.font_size,
+            reference_size: 0.0,
+            auto: 0.0,
+        }
+    }
+
+    fn paint_boxes(&self, parent_state: &ParentState, state: &State) -> PaintBoxes {
+        let extents = self.get_render_extents(parent_state, state);
+
+        let outline = self.outline.width + self.outline.offset;
+        let border_box = [
+            extents.x + outline,
+            extents.y + outline,
+            extents.width - outline * 2.0,
+            extents.height - outline * 2.0,
+        ];
+        let [top, right, bottom, left] = self.border.size;
+        let padding_box = [
+            border_box[0] + left,
+            border_box[1] + top,
+            border_box[2] - left - right,
+            border_box[3] - top - bottom,
+        ];
+        let radius = self.border.radius;
+        // Inner corners shrink by the wider of the two borders meeting there.
+        let inner_radius = [
+            (radius[0] - top.max(left)).max(0.0),
+            (radius[1] - top.max(right)).max(0.0),
+            (radius[2] - bottom.max(right)).max(0.0),
+            (radius[3] - bottom.max(left)).max(0.0),
+        ];
+
+        let [top, right, bottom, left] = self.padding;
+        let content_box = [
+            padding_box[0] + left,
+            padding_box[1] + top,
+            padding_box[2] - left - right,
+            padding_box[3] - top - bottom,
+        ];
+        let content_radius = [
+            (inner_radius[0] - top.max(left)).max(0.0),
+            (inner_radius[1] - top.max(right)).max(0.0),
+            (inner_radius[2] - bottom.max(right)).max(0.0),
+            (inner_radius[3] - bottom.max(left)).max(0.0),
+        ];
+
+        PaintBoxes {
+            border_box,
+            padding_box,
+            content_box,
+            inner_radius,
+            content_radius,
+        }
+    }
+
+    /// All instances of the node's own box in painting order: outer shadows, background color,
+    /// background layers, border and outline, then inner shadows.
+    pub(crate) fn get_instances(
+        &self,
+        parent_state: &ParentState,
+        state: &State,
+        gradient_stops: &mut Vec<GradientStop>,
+        atlas: &mut ImageAtlas,
+    ) -> Vec<InstanceData> {
+        let mut instances = self.get_box_shadow_instances(parent_state, state, false);
+        let instance = self.get_instance_data(parent_state, state);
+        let backgrounds = self.get_background_instances(parent_state, state, gradient_stops, atlas);
+
+        match backgrounds.is_empty() {
+            true => instances.push(instance),
+            // Layers go between the background color and the border, so the box is split in
+            // two around them.
+            false => {
+                instances.push(InstanceData {
+                    outline_color: [0.0; 4],
+                    border_top_color: [0.0; 4],
+                    border_right_color: [0.0; 4],
+                    border_bottom_color: [0.0; 4],
+                    border_left_color: [0.0; 4],
+                    ..instance
+                });
+                instances.extend(backgrounds);
+                instances.push(InstanceData {
+                    rect_color: [0.0; 4],
+                    ..instance
+                });
+            }
+        }
+
+        instances.extend(self.get_box_shadow_instances(parent_state, state, true));
+        instances
+    }
+
+    /// Instances for the background layers, bottom layer first, with their gradient stops
+    /// appended to `gradient_stops` and their images allocated in `atlas`.
+    pub(crate) fn get_background_instances(
+        &self,
+        parent_state: &ParentState,
+        state: &State,
+        gradient_stops: &mut Vec<GradientStop>,
+        atlas: &mut ImageAtlas,
+    ) -> Vec<InstanceData> {
+        self.background
+            .layers()
+            .filter_map(|layer| {
+                self.get_layer_instance(&layer, parent_state, state, gradient_stops, atlas)
+            })
+            .collect()
+    }
+
+    /// Like `get_background_instances` for the mask layers, each with whether its luminance
+    /// masks rather than its alpha.
+    pub(crate) fn get_mask_instances(
+        &self,
+        parent_state: &ParentState,
+        state: &State,
+        gradient_stops: &mut Vec<GradientStop>,
+        atlas: &mut ImageAtlas,
+    ) -> Vec<(InstanceData, bool)> {
+        self.mask
+            .layers()
+            .filter_map(|(layer, luminance)| {
+                self.get_layer_instance(&layer, parent_state, state, gradient_stops, atlas)
+                    .map(|instance| (instance, luminance))
+            })
+            .collect()
+    }
+
+    /// The instance painting one background or mask layer, `None` if there is nothing to draw.
+    fn get_layer_instance(
+        &self,
+        layer: &Layer,
+        parent_state: &ParentState,
+        state: &State,
+        gradient_stops: &mut Vec<GradientStop>,
+        atlas: &mut ImageAtlas,
+    ) -> Option<InstanceData> {
+        let context = self.paint_context(state);
+        let boxes = self.paint_boxes(parent_state, state);
+        let area = |background_box| match background_box {
+            BackgroundBox::BorderBox => (boxes.border_box, self.border.radius),
+            BackgroundBox::PaddingBox => (boxes.padding_box, boxes.inner_radius),
+            BackgroundBox::ContentBox => (boxes.content_box, boxes.content_radius),
+        };
+
+        let (origin, _) = area(layer.origin);
+        let (clip, clip_radius) = area(layer.clip);
+        let tile = layer.tile(origin, &context);
+        if tile.rect[2] <= 0.0 || tile.rect[3] <= 0.0 {
+            return None;
+        }
+
+        let instance = InstanceData {
+            rect_pos: [clip[0], clip[1]],
+            rect_size: [clip[2], clip[3]],
+            outline_width: 0.0,
+            outline_offset: 0.0,
+            border_radius: clip_radius,
+            border_size: [0.0; 4],
+            background_repeat: tile.repeat[0] as u32 | (tile.repeat[1] as u32) << 1,
+            tile: tile.rect,
+            tile_spacing: tile.spacing,
+            ..self.get_instance_data(parent_state, state)
+        };
+
+        Some(match layer.image {
+            BackgroundImage::Gradient(gradient) => {
+                // Gradients are laid out over a single tile, in its own coordinates.
+                let resolved = gradient.resolve([0.0, 0.0, tile.rect[2], tile.rect[3]], &context);
+                let offset = gradient_stops.len() as u32;
+                let count = resolved.stops.len() as u32;
+                gradient_stops.extend(resolved.stops);
+
+                InstanceData {
+                    kind: resolved.kind,
+                    gradient: resolved.params,
+                    gradient_stops: [offset, count],
+                    repeating: gradient.repeating as u32,
+                    ..instance
+                }
+            }
+            BackgroundImage::Image(image) => InstanceData {
+                kind: KIND_IMAGE,
+                uv: atlas.allocate(image)?,
+                ..instance
+            },
+        })
+    }
+
+    /// Where an image node's picture goes: its intrinsic size fitted into the content box by
+    /// `object-fit` and placed by `object-position`.
+    pub(crate) fn object_rect(
+        &self,
+        intrinsic: [f32; 2],
+        parent_state: &ParentState,
+        state: &State,
+    ) -> [f32; 4] {
+        let [x, y, width, height] = self.paint_boxes(parent_state, state).content_box;
+        let [intrinsic_width, intrinsic_height] = intrinsic.map(|size| size.max(f32::EPSILON));
+
+        let contain = (width / intrinsic_width).min(height / intrinsic_height);
+        let scale = match self.style.object_fit {
+            ObjectFit::Fill => None,
+            ObjectFit::Contain => Some(contain),
+            ObjectFit::Cover => Some((width / intrinsic_width).max(height / intrinsic_height)),
+            ObjectFit::None => Some(1.0),
+            ObjectFit::ScaleDown => Some(contain.min(1.0)),
+        };
+        let size = match scale {
+            Some(scale) => [intrinsic_width * scale, intrinsic_height * scale],
+            None => [width, height],
+        };
+
+        let context = self.paint_context(state);
+        let [left, top] = &self.style.object_position;
+        [
+            x + left.to_px(&Context {
+                reference_size: width - size[0],
+                ..context
+            }),
+            y + top.to_px(&Context {
+                reference_size: height - size[1],
+                ..context
+            }),
+            size[0],
+            size[1],
+        ]
+    }
+
+    /// The instance painting an image node's picture at `rect`, found at `uv` in the atlas,
+    /// clipped to the rounded content box.
+    pub(crate) fn get_image_instance(
+        &self,
+        rect: [f32; 4],
+        uv: [f32; 4],
+        parent_state: &ParentState,
+        state: &State,
+    ) -> InstanceData {
+        let boxes = self.paint_boxes(parent_state, state);
+        let content_box = boxes.content_box;
+
+        InstanceData {
+            rect_pos: [content_box[0], content_box[1]],
+            rect_size: [content_box[2], content_box[3]],
+            outline_width: 0.0,
+            outline_offset: 0.0,
+            border_radius: boxes.content_radius,
+            border_size: [0.0; 4],
+            kind: KIND_IMAGE,
+            tile: rect,
+            uv,
+            ..self.get_instance_data(parent_state, state)
+        }
+    }
+
+    /// Instances for the outer (`inset == false`) or inner shadows, in painting order. Outer
+    /// shadows go below the box, inner ones above its background.
+    pub fn get_box_shadow_instances(
+        &self,
+        parent_state: &ParentState,
+        state: &State,
+        inset: bool,
+    ) -> Vec<InstanceData> {
+        let base = self.get_instance_data(parent_state, state);
+        let context = self.paint_context(state);
+        let PaintBoxes {
+            border_box,
+            padding_box,
+            inner_radius,
+            ..
+        } = self.paint_boxes(parent_state, state);
+        let radius = self.border.radius;
+
+        let current_color = self.current_color();
+
+        self.box_shadow
+            .iter()
+            .rev()
+            .filter(|shadow| shadow.inset == inset)
+            .map(|shadow| {
+                let offset_x = shadow.offset[0].to_px(&context);
+                let offset_y = shadow.offset[1].to_px(&context);
+                let blur = shadow.blur.to_px(&context).max(0.0);
+                let spread = shadow.spread.to_px(&context);
+
+                let (kind, clip_box, clip_radius, spread) = match inset {
+                    false => (KIND_OUTER_SHADOW, border_box, radius, spread),
+                    true => (KIND_INNER_SHADOW, padding_box, inner_radius, -spread),
+                };
+
+                let shadow_box = [
+                    clip_box[0] + offset_x - spread,
+                    clip_box[1] + offset_y - spread,
+                    (clip_box[2] + spread * 2.0).max(0.0),
+                    (clip_box[3] + spread * 2.0).max(0.0),
+                ];
+                // Rounded corners grow and shrink with the spread, sharp ones stay sharp.
+                let shadow_radius = clip_radius.map(|r| match r > 0.0 {
+                    true => (r + spread).max(0.0),
+                    false => 0.0,
+                });
+
+                // The blur fades out within 1.5 blur radii, three standard deviations.
+                let quad = match inset {
+                    false => {
+                        let extent = blur * 1.5;
+                        [
+                            shadow_box[0] - extent,
+                            shadow_box[1] - extent,
+                            shadow_box[2] + extent * 2.0,
+                            shadow_box[3] + extent * 2.0,
+                        ]
+                    }
+                    true => padding_box,
+                };
+
+                InstanceData {
+                    rect_pos: [quad[0], quad[1]],
+                    rect_size: [quad[2], quad[3]],
+                    outline_width: 0.0,
+                    outline_offset: 0.0,
+                    kind,
+                    shadow_blur: blur,
+                    rect_color: shadow.color.unwrap_or(current_color),
+                    border_radius: clip_radius,
+                    border_size: [0.0; 4],
+                    shadow_box,
+                    shadow_radius,
+                    clip_box,
+                    ..base
+                }
+            })
+            .collect()
+    }
 }
 
 pub struct Outline {
@@ -512,6 +1323,14 @@ impl Default for Outline {
     }
 }
 
+struct PaintBoxes {
+    border_box: [f32; 4],
+    padding_box: [f32; 4],
+    content_box: [f32; 4],
+    inner_radius: [f32; 4],
+    content_radius: [f32; 4],
+}
+
 pub struct Extents {
     pub x: f32,
     pub y: f32,
@@ -9,16 +9,10 @@ struct InstanceData {
     rect_size: vec2<f32>,
     outline_width: f32,
     outline_offset: f32,
-    scale: vec2<f32>,
-    skew: vec2<f32>,
-    rotation: f32,
-    invert: f32,
-    brightness: f32,
-    saturate: f32,
-    contrast: f32,
-    grayscale: f32,
-    sepia: f32,
-    hue_rotate: f32,
+    kind: u32,
+    shadow_blur: f32,
+    // From layout to document coordinates, with perspective in w.
+    transform: mat4x4<f32>,
     rect_color: vec4<f32>,
     outline_color: vec4<f32>,
     border_radius: vec4<f32>,
@@ -27,10 +21,68 @@ struct InstanceData {
     border_right_color: vec4<f32>,
     border_bottom_color: vec4<f32>,
     border_left_color: vec4<f32>,
+    shadow_box: vec4<f32>,
+    shadow_radius: vec4<f32>,
+    clip_box: vec4<f32>,
+    gradient: vec4<f32>,
+    gradient_stops: vec2<u32>,
+    repeating: u32,
+    background_repeat: u32,
+    tile: vec4<f32>,
+    uv: vec4<f32>,
+    tile_spacing: vec2<f32>,
+    border_style: u32,
+    layer_blur: f32,
+    color_matrix: mat4x4<f32>,
+    color_offset: vec4<f32>,
 };
+
+const KIND_BOX: u32 = 0u;
+const KIND_OUTER_SHADOW: u32 = 1u;
+const KIND_INNER_SHADOW: u32 = 2u;
+const KIND_LINEAR_GRADIENT: u32 = 3u;
+const KIND_RADIAL_GRADIENT: u32 = 4u;
+const KIND_CONIC_GRADIENT: u32 = 5u;
+const KIND_IMAGE: u32 = 6u;
+const KIND_LAYER: u32 = 7u;
+const KIND_LAYER_BLUR_X: u32 = 8u;
+const KIND_LAYER_BLUR_Y: u32 = 9u;
+const KIND_LAYER_SHADOW: u32 = 10u;
+const KIND_LAYER_PLACED: u32 = 11u;
+const KIND_LAYER_ELLIPSE: u32 = 12u;
+const KIND_LAYER_MASK: u32 = 13u;
+const KIND_LAYER_LUMINANCE: u32 = 14u;
+const KIND_LAYER_MASKED: u32 = 15u;
+
+// Per side values of `border_style`, matching `BorderStyle::code`.
+const BORDER_SOLID: u32 = 0u;
+const BORDER_DASHED: u32 = 1u;
+const BORDER_DOTTED: u32 = 2u;
+const BORDER_DOUBLE: u32 = 3u;
+const BORDER_GROOVE: u32 = 4u;
+const BORDER_RIDGE: u32 = 5u;
+const BORDER_INSET: u32 = 6u;
+const BORDER_OUTSET: u32 = 7u;
 @group(1) @binding(1)
 var<storage, read> instance_data: array<InstanceData>;
 
+struct GradientStop {
+    color: vec4<f32>,
+    position: f32,
+};
+@group(2) @binding(1)
+var<storage, read> gradient_stops: array<GradientStop>;
+@group(2) @binding(2)
+var atlas: texture_2d<f32>;
+@group(2) @binding(3)
+var atlas_sampler: sampler;
+// The offscreen layer a layer instance draws from.
+@group(2) @binding(4)
+var layer: texture_2d<f32>;
+// The mask layer of a masked pass, lined up with `layer`.
+@group(2) @binding(5)
+var mask_layer: texture_2d<f32>;
+
 struct VertexInput {
     @location(0) position: vec2<f32>,
 };
@@ -47,23 +99,6 @@ struct VertexOutput {
     @location(7) instance_index: u32,
 };
 
-fn rotation_matrix(angle: f32) -> mat2x2<f32> {
-    let angle_inner = angle * 3.14159265359 / 180.0;
-    let sinTheta = sin(angle_inner);
-    let cosTheta = cos(angle_inner);
-    return mat2x2<f32>(
-        cosTheta, -sinTheta,
-        sinTheta, cosTheta
-    );
-}
-
-fn skew_matrix(skewX: f32, skewY: f32) -> mat2x2<f32> {
-    return mat2x2<f32>(
-        vec2<f32>(1.0, skewY * 3.14159265359 / 180.0),
-        vec2<f32>(skewX * 3.14159265359 / 180.0, 1.0)
-    );
-}
-
 @vertex
 fn vs_main(
     model: VertexInput,
@@ -72,40 +107,24 @@ fn vs_main(
     var out: VertexOutput;
     let instance = instance_data[instanceIndex];
 
-    let scale = instance.scale;
+    // Everything is shaded in layout coordinates, interpolated with perspective, only the
+    // corners move.
+    let position = model.position * instance.rect_size + instance.rect_pos;
+    out.clip_position = projection.projection * instance.transform * vec4<f32>(position, 0.0, 1.0);
+    // Boxes are painted in order, depth only has to stay within the clip volume.
+    out.clip_position.z = 0.0;
 
-    let scaled_dimensions = vec4<f32>(
-        instance.rect_pos * scale,
-        instance.rect_size * scale
-    );
-
-    let position = model.position * scaled_dimensions.zw + scaled_dimensions.xy;
-
-    out.clip_position = projection.projection * vec4<f32>(
-        position * rotation_matrix(instance.rotation) * skew_matrix(instance.skew.x, instance.skew.y), 
-        0.0, 
-        1.0
-    );
-
-    let outline_width = vec2<f32>(instance.outline_width, instance.outline_width) * scale;
-    let outline_offset = vec2<f32>(instance.outline_offset, instance.outline_offset) * scale;
-
-    let border_size = instance.border_size * vec4<f32>(scale, scale);
+    let outline_width = vec2<f32>(instance.outline_width, instance.outline_width);
+    let outline_offset = vec2<f32>(instance.outline_offset, instance.outline_offset);
+    let border_size = instance.border_size;
 
     out.uv = position;
-    out.rect_pos = scaled_dimensions.xy + vec2<f32>(
-        border_size.x, 
-        border_size.y
-    ) * scale + vec2<f32>(
-        outline_width + outline_offset
-    );
-    out.rect_size = scaled_dimensions.zw - vec2<f32>(
+    out.rect_pos = instance.rect_pos + border_size.xy + outline_width + outline_offset;
+    out.rect_size = instance.rect_size - vec2<f32>(
         border_size.x + border_size.z,
         border_size.y + border_size.w
-    ) * scale - vec2<f32>(
-        outline_width + outline_offset
-    ) * 2;
-    out.border_radius = instance.border_radius * vec4<f32>(scale, scale);
+    ) - (outline_width + outline_offset) * 2.0;
+    out.border_radius = instance.border_radius;
     out.border_size = border_size;
     out.outline_width = outline_width;
     out.outline_offset = outline_offset;
@@ -115,147 +134,482 @@ fn vs_main(
 }
 
 fn sdf_rounded_rect(p: vec2<f32>, b: vec2<f32>, r: vec4<f32>) -> f32 {
-    var x = r.x;
-    var y = r.y;
+    let radius = corner_radius(p, r);
+    let q = abs(p) - b + radius;
+    return min(max(q.x, q.y), 0.0) + length(max(q, vec2<f32>(0.0))) - radius;
+}
 
-    x = select(r.z, r.x, p.x > 0.0);
-    y = select(y, x, p.y > 0.0);
+fn corner_radius(p: vec2<f32>, r: vec4<f32>) -> f32 {
+    let top = select(r.x, r.y, p.x > 0.0);
+    let bottom = select(r.w, r.z, p.x > 0.0);
+    return select(top, bottom, p.y > 0.0);
+}
 
-    let q = abs(p) - b + x;
-    return min(max(q.x, q.y), 0.0) + length(max(q, vec2<f32>(0.0))) - x;
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    return exp(-(x * x) / (2.0 * sigma * sigma)) / (sqrt(2.0 * 3.14159265359) * sigma);
 }
 
-fn brightness_matrix(brightness: f32) -> mat4x4<f32> {
-    return mat4x4<f32>(
-        1, 0, 0, 0,
-        0, 1, 0, 0,
-        0, 0, 1, 0,
-        brightness, brightness, brightness, 1
-    );
+fn erf(x: vec2<f32>) -> vec2<f32> {
+    let s = sign(x);
+    let a = abs(x);
+    var r = 1.0 + (0.278393 + (0.230389 + 0.078108 * (a * a)) * a) * a;
+    r *= r;
+    return s - s / (r * r);
 }
 
-fn contrast_matrix(contrast: f32) -> mat4x4<f32> {
-    let t = (1.0 - contrast) / 2.0;
-    return mat4x4<f32>(
-        contrast, 0, 0, 0,
-        0, contrast, 0, 0,
-        0, 0, contrast, 0,
-        t, t, t, 1
-    );
+// Horizontal integral of the gaussian over one row of the rounded rect, which is closed form.
+fn blurred_row(x: f32, y: f32, sigma: f32, corner: f32, half_size: vec2<f32>) -> f32 {
+    let delta = min(half_size.y - corner - abs(y), 0.0);
+    let curved = half_size.x - corner + sqrt(max(0.0, corner * corner - delta * delta));
+    let integral = 0.5 + 0.5 * erf((x + vec2<f32>(-curved, curved)) * (sqrt(0.5) / sigma));
+    return integral.y - integral.x;
 }
 
-fn saturation_matrix(saturation: f32) -> mat4x4<f32> {
-    let luminance = vec3<f32>(0.3086, 0.6094, 0.0820);
-    let one_minus_sat = 1.0 - saturation;
+// Coverage of a rounded rect convolved with a gaussian, sampling the vertical integral at a
+// few rows within three standard deviations.
+fn blurred_rounded_rect(p: vec2<f32>, half_size: vec2<f32>, r: vec4<f32>, sigma: f32) -> f32 {
+    let corner = min(corner_radius(p, r), min(half_size.x, half_size.y));
+    let low = p.y - half_size.y;
+    let high = p.y + half_size.y;
+    let start = clamp(-3.0 * sigma, low, high);
+    let end = clamp(3.0 * sigma, low, high);
+    let step = (end - start) / 4.0;
+
+    var y = start + step * 0.5;
+    var value = 0.0;
+    for (var i = 0; i < 4; i++) {
+        value += blurred_row(p.x, p.y - y, sigma, corner, half_size) * gaussian(y, sigma) * step;
+        y += step;
+    }
+    return value;
+}
+
+fn box_shadow(in: VertexOutput, instance: InstanceData) -> vec4<f32> {
+    let shadow_box = instance.shadow_box;
+    let clip_box = instance.clip_box;
+
+    let shadow_half = shadow_box.zw / 2.0;
+    let shadow_p = in.uv - shadow_box.xy - shadow_half;
+    var coverage: f32;
+    if instance.shadow_blur < 0.5 {
+        coverage = 1.0 - smoothstep(0.0, 1.0, sdf_rounded_rect(shadow_p, shadow_half, instance.shadow_radius));
+    } else {
+        coverage = blurred_rounded_rect(shadow_p, shadow_half, instance.shadow_radius, instance.shadow_blur / 2.0);
+    }
+
+    let clip_half = clip_box.zw / 2.0;
+    let clip_dist = sdf_rounded_rect(in.uv - clip_box.xy - clip_half, clip_half, in.border_radius);
+    let inside_clip = 1.0 - smoothstep(0.0, 1.0, clip_dist);
+
+    // Outer shadows only show outside the border box, inner ones are the inverse of the
+    // shape and only show inside the padding box.
+    var alpha: f32;
+    if instance.kind == KIND_INNER_SHADOW {
+        alpha = (1.0 - coverage) * inside_clip;
+    } else {
+        alpha = coverage * (1.0 - inside_clip);
+    }
+
+    return vec4<f32>(instance.rect_color.rgb, instance.rect_color.a * alpha);
+}
+
+fn premultiply(color: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(color.rgb * color.a, color.a);
+}
+
+// Color at `t` along the gradient line, interpolating between stops in premultiplied space.
+fn gradient_color(position: f32, offset: u32, count: u32, repeating: bool) -> vec4<f32> {
+    let first = gradient_stops[offset].position;
+    let last = gradient_stops[offset + count - 1u].position;
+
+    var t = position;
+    if repeating && last > first {
+        t = first + fract((t - first) / (last - first)) * (last - first);
+    }
+
+    var color = premultiply(gradient_stops[offset].color);
+    for (var i = 1u; i < count; i++) {
+        let previous = gradient_stops[offset + i - 1u];
+        let next = gradient_stops[offset + i];
+        if t >= previous.position {
+            let amount = clamp((t - previous.position) / max(next.position - previous.position, 0.00001), 0.0, 1.0);
+            color = mix(premultiply(previous.color), premultiply(next.color), amount);
+        }
+    }
+
+    if color.a > 0.0 {
+        return vec4<f32>(color.rgb / color.a, color.a);
+    }
+    return vec4<f32>(0.0);
+}
+
+// Position within the copy of the tile covering `p`, and whether `p` is on a copy at all.
+fn tile_position(p: vec2<f32>, instance: InstanceData) -> vec3<f32> {
+    let size = instance.tile.zw;
+    let stride = size + instance.tile_spacing;
+    var local = p - instance.tile.xy;
+
+    if (instance.background_repeat & 1u) != 0u {
+        local.x -= floor(local.x / stride.x) * stride.x;
+    }
+    if (instance.background_repeat & 2u) != 0u {
+        local.y -= floor(local.y / stride.y) * stride.y;
+    }
+
+    let inside = all(local >= vec2<f32>(0.0)) && all(local < size);
+    return vec3<f32>(local, select(0.0, 1.0, inside));
+}
 
-    var red: vec3<f32> = vec3<f32>(luminance.x * one_minus_sat);
-    red += vec3<f32>(saturation, 0, 0);
+fn gradient(p: vec2<f32>, instance: InstanceData) -> vec4<f32> {
+    let params = instance.gradient;
+
+    var t: f32;
+    switch instance.kind {
+        case KIND_LINEAR_GRADIENT: {
+            t = dot(p - params.xy, params.zw) + 0.5;
+        }
+        case KIND_RADIAL_GRADIENT: {
+            t = length((p - params.xy) / params.zw);
+        }
+        default: {
+            let d = p - params.xy;
+            t = fract((atan2(d.x, -d.y) - params.z) / (2.0 * 3.14159265359));
+        }
+    }
+
+    return gradient_color(t, instance.gradient_stops.x, instance.gradient_stops.y, instance.repeating != 0u);
+}
+
+fn image(p: vec2<f32>, instance: InstanceData) -> vec4<f32> {
+    let texel = instance.uv.xy + p / instance.tile.zw * instance.uv.zw;
+    // Stay half a texel inside the image so filtering doesn't reach its neighbours.
+    let clamped = clamp(texel, instance.uv.xy + 0.5, instance.uv.xy + instance.uv.zw - 0.5);
+    return textureSampleLevel(atlas, atlas_sampler, clamped / vec2<f32>(textureDimensions(atlas)), 0.0);
+}
 
-    var green: vec3<f32> = vec3<f32>(luminance.y * one_minus_sat);
-    green += vec3<f32>(0, saturation, 0);
+// How far blur passes reach, in texels, with standard deviations of at most a third of it.
+const MAX_BLUR_RADIUS: i32 = 96;
 
-    var blue: vec3<f32> = vec3<f32>(luminance.z * one_minus_sat);
-    blue += vec3<f32>(0, 0, saturation);
+// Offscreen layers hold premultiplied colors, the passes return straight ones.
+fn unpremultiply(color: vec4<f32>) -> vec4<f32> {
+    if color.a <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>(color.rgb / color.a, color.a);
+}
+
+// Layers line up with the render target but for their origins, so a pass reads the texel
+// under the pixel it draws `tile.xy` away, which also moves shadows.
+fn layer_texel(in: VertexOutput, instance: InstanceData) -> vec2<i32> {
+    return vec2<i32>(floor(in.clip_position.xy - instance.tile.xy));
+}
+
+// A texel of `texture`, transparent outside of it as nothing was drawn there.
+fn load_layer(texture: texture_2d<f32>, texel: vec2<i32>) -> vec4<f32> {
+    if any(texel < vec2<i32>(0)) || any(texel >= vec2<i32>(textureDimensions(texture))) {
+        return vec4<f32>(0.0);
+    }
+    return textureLoad(texture, texel, 0);
+}
+
+// How much of a composite's quad is kept: its rounded rectangle, the ellipse in it or a mask
+// from the atlas.
+fn clip_coverage(in: VertexOutput, instance: InstanceData) -> f32 {
+    let half_size = instance.rect_size / 2.0;
+    let p = in.uv - instance.rect_pos - half_size;
+
+    switch instance.kind {
+        case KIND_LAYER_ELLIPSE: {
+            // The implicit function over its gradient approximates the distance to the edge.
+            let q = p / half_size;
+            let gradient = length(q / half_size);
+            let dist = select((length(q) - 1.0) * length(q) / gradient, -1.0, gradient <= 0.0);
+            return 1.0 - smoothstep(-0.5, 0.5, dist);
+        }
+        case KIND_LAYER_MASK: {
+            let t = (in.uv - instance.rect_pos) / instance.rect_size;
+            let uv = (instance.uv.xy + t * instance.uv.zw) / vec2<f32>(textureDimensions(atlas));
+            return textureSampleLevel(atlas, atlas_sampler, uv, 0.0).a;
+        }
+        default: {
+            let dist = sdf_rounded_rect(p, half_size, instance.border_radius);
+            return 1.0 - smoothstep(-0.5, 0.5, dist);
+        }
+    }
+}
+
+// The layer through the color matrix of its filters and opacity, clipped by the instance.
+fn composite(in: VertexOutput, instance: InstanceData) -> vec4<f32> {
+    let color = unpremultiply(load_layer(layer, layer_texel(in, instance)));
+    var filtered = clamp(instance.color_matrix * color + instance.color_offset, vec4<f32>(0.0), vec4<f32>(1.0));
+    filtered.a *= clip_coverage(in, instance);
+    return filtered;
+}
+
+// The layer as white with its luminance for alpha, for `mask-mode: luminance`.
+fn luminance_layer(in: VertexOutput, instance: InstanceData) -> vec4<f32> {
+    let color = unpremultiply(load_layer(layer, layer_texel(in, instance)));
+    let luminance = dot(color.rgb, vec3<f32>(0.2125, 0.7154, 0.0721));
+    return vec4<f32>(1.0, 1.0, 1.0, luminance * color.a);
+}
+
+// The layer with its alpha scaled by the mask layer's, read `tile.zw` away.
+fn masked_layer(in: VertexOutput, instance: InstanceData) -> vec4<f32> {
+    let color = unpremultiply(load_layer(layer, layer_texel(in, instance)));
+    let mask_texel = vec2<i32>(floor(in.clip_position.xy - instance.tile.zw));
+    return vec4<f32>(color.rgb, color.a * load_layer(mask_layer, mask_texel).a);
+}
+
+// A layer drawn from the viewport's top left corner on, which is `tile.xy` before the layer's,
+// sampled between texels as it may be transformed.
+fn placed_layer(in: VertexOutput, instance: InstanceData) -> vec4<f32> {
+    let dimensions = vec2<f32>(textureDimensions(layer));
+    let uv = (in.uv - instance.rect_pos - instance.tile.xy) / dimensions;
+    return unpremultiply(textureSampleLevel(layer, atlas_sampler, uv, 0.0));
+}
+
+// One direction of a separable gaussian blur. Texels past the edges in `uv`, if any, repeat
+// the ones on them, others outside the layer count as transparent.
+fn layer_blur(in: VertexOutput, instance: InstanceData) -> vec4<f32> {
+    let texel = layer_texel(in, instance);
+    let edges = vec2<i32>(instance.uv.xy);
+    let sigma = instance.layer_blur;
+    let radius = min(i32(ceil(sigma * 3.0)), MAX_BLUR_RADIUS);
+    let step = select(vec2<i32>(1, 0), vec2<i32>(0, 1), instance.kind == KIND_LAYER_BLUR_Y);
+
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var i = -radius; i <= radius; i++) {
+        let weight = gaussian(f32(i), sigma);
+        var sample = texel + step * i;
+        if instance.uv.z > 0.0 {
+            sample = clamp(sample, edges, edges + vec2<i32>(instance.uv.zw) - 1);
+        }
+        sum += load_layer(layer, sample) * weight;
+        total += weight;
+    }
+    return unpremultiply(sum / total);
+}
+
+// The layer's coverage filled with `rect_color`, for drop shadows.
+fn layer_shadow(in: VertexOutput, instance: InstanceData) -> vec4<f32> {
+    let alpha = load_layer(layer, layer_texel(in, instance)).a;
+    return vec4<f32>(instance.rect_color.rgb, instance.rect_color.a * alpha);
+}
+
+// A background layer, clipped to the rounded box covered by the instance.
+fn background_layer(in: VertexOutput, instance: InstanceData) -> vec4<f32> {
+    let tile = tile_position(in.uv, instance);
+
+    var color: vec4<f32>;
+    if instance.kind == KIND_IMAGE {
+        color = image(tile.xy, instance);
+    } else {
+        color = gradient(tile.xy, instance);
+    }
+
+    let half_size = in.rect_size / 2.0;
+    let dist = sdf_rounded_rect(in.uv - in.rect_pos - half_size, half_size, in.border_radius);
+    let alpha = (1.0 - smoothstep(0.0, 1.0, dist)) * tile.z;
+
+    return vec4<f32>(color.rgb, color.a * alpha);
+}
 
-    return mat4x4<f32>(
-        vec4<f32>(red, 0.0),
-        vec4<f32>(green, 0.0),
-        vec4<f32>(blue, 0.0),
-        vec4<f32>(0.0, 0.0, 0.0, 1.0)
-    );
+// Straight alpha `top` composited over `bottom`.
+fn over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    let alpha = top.a + bottom.a * (1.0 - top.a);
+    if alpha <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>((top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a)) / alpha, alpha);
 }
 
-fn sepia(color: vec3<f32>, sepia: f32) -> vec3<f32> {
-    let sepia_matrix = vec3<f32>(
-        dot(color.rgb, vec3<f32>(0.393, 0.769, 0.189)),
-        dot(color.rgb, vec3<f32>(0.349, 0.686, 0.168)),
-        dot(color.rgb, vec3<f32>(0.272, 0.534, 0.131))
-    );
-    return mix(color.rgb, sepia_matrix, sepia);
+// Shade of a side of a 3D border: top and left are dark when sunken, bottom and right when
+// raised.
+fn bevel(side: u32, sunken: bool) -> f32 {
+    let top_left = side == 0u || side == 3u;
+    return select(1.0, 0.5, top_left == sunken);
 }
 
-fn hue_rotate(color: vec3<f32>, angle: f32) -> vec3<f32> {
-    return vec3<f32>(
-        dot(color, vec3<f32>(0.213, 0.715, -0.213)) * (1.0 - cos(angle)) + cos(angle) * color.r + sin(angle) * color.b,
-        dot(color, vec3<f32>(-0.213, 0.715, 0.715)) * (1.0 - cos(angle)) + cos(angle) * color.g + sin(angle) * color.g,
-        dot(color, vec3<f32>(0.272, -0.715, 0.213)) * (1.0 - cos(angle)) + cos(angle) * color.b + sin(angle) * color.r
-    );
+// The border of `border_box` at `p`, with each side in its own style and color. Corners are
+// split between sides along the diagonal, and dashes and dots are spaced by arc length along
+// the middle of the border so they stay even around rounded corners.
+fn border(p: vec2<f32>, border_box: vec4<f32>, instance: InstanceData) -> vec4<f32> {
+    let widths = instance.border_size;
+    let r = instance.border_radius;
+    if all(widths <= vec4<f32>(0.0)) {
+        return vec4<f32>(0.0);
+    }
+
+    let half_size = border_box.zw / 2.0;
+    let inner_half = half_size - vec2<f32>(widths.w + widths.y, widths.x + widths.z) / 2.0;
+    let inner_center = border_box.xy + half_size + vec2<f32>(widths.w - widths.y, widths.x - widths.z) / 2.0;
+    let inner_r = max(r - vec4<f32>(max(widths.x, widths.w), max(widths.x, widths.y), max(widths.z, widths.y), max(widths.z, widths.w)), vec4<f32>(0.0));
+    let outer_dist = sdf_rounded_rect(p - border_box.xy - half_size, half_size, r);
+    let inner_dist = sdf_rounded_rect(p - inner_center, max(inner_half, vec2<f32>(0.0)), inner_r);
+    let coverage = (1.0 - smoothstep(-0.5, 0.5, outer_dist)) * smoothstep(-0.5, 0.5, inner_dist);
+    if coverage <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+
+    // The side whose border `p` is relatively deepest in the least.
+    let far = border_box.xy + border_box.zw;
+    let from_edge = vec4<f32>(p.y - border_box.y, far.x - p.x, far.y - p.y, p.x - border_box.x);
+    let depth = select(from_edge / max(widths, vec4<f32>(0.00001)), vec4<f32>(1e9), widths <= vec4<f32>(0.0));
+    var side = 0u;
+    var least = depth.x;
+    if depth.y < least { side = 1u; least = depth.y; }
+    if depth.z < least { side = 2u; least = depth.z; }
+    if depth.w < least { side = 3u; }
+
+    // `q` is `p` in a frame where the side is on top, running left to right clockwise.
+    var q: vec2<f32>;
+    var side_length: f32;
+    var radii: vec2<f32>;
+    var width: f32;
+    var side_color: vec4<f32>;
+    switch side {
+        case 0u: {
+            q = p - border_box.xy;
+            side_length = border_box.z;
+            radii = r.xy;
+            width = widths.x;
+            side_color = instance.border_top_color;
+        }
+        case 1u: {
+            q = vec2<f32>(p.y - border_box.y, far.x - p.x);
+            side_length = border_box.w;
+            radii = r.yz;
+            width = widths.y;
+            side_color = instance.border_right_color;
+        }
+        case 2u: {
+            q = vec2<f32>(far.x - p.x, far.y - p.y);
+            side_length = border_box.z;
+            radii = r.zw;
+            width = widths.z;
+            side_color = instance.border_bottom_color;
+        }
+        default: {
+            q = vec2<f32>(far.y - p.y, p.x - border_box.x);
+            side_length = border_box.w;
+            radii = r.wx;
+            width = widths.w;
+            side_color = instance.border_left_color;
+        }
+    }
+    let style = (instance.border_style >> (side * 8u)) & 0xffu;
+
+    // Position along the middle of the side, from the diagonal at its start, and depth from
+    // the outer edge.
+    let middle = max(radii - width / 2.0, vec2<f32>(0.0));
+    let end_center = side_length - radii.y;
+    var along: f32;
+    var across: f32;
+    if q.x < radii.x && q.y < radii.x {
+        let d = vec2<f32>(radii.x) - q;
+        along = -atan2(d.x, d.y) * middle.x;
+        across = radii.x - length(d);
+    } else if q.x > end_center && q.y < radii.y {
+        let d = vec2<f32>(q.x - end_center, radii.y - q.y);
+        along = end_center - radii.x + atan2(d.x, d.y) * middle.y;
+        across = radii.y - length(d);
+    } else {
+        along = q.x - radii.x;
+        across = q.y;
+    }
+    let start = -0.785398 * middle.x;
+    let total = end_center - radii.x + 0.785398 * middle.y - start;
+    along -= start;
+
+    // Edge coverage leaks next to sides without a border, which stay empty.
+    var alpha = 1.0 - smoothstep(-0.5, 0.5, across - width - 0.5);
+    var shade = 1.0;
+    switch style {
+        case BORDER_DASHED, BORDER_DOTTED: {
+            // Whole periods only, with a dash or dot centered on both ends so corners match.
+            let dotted = style == BORDER_DOTTED;
+            let period = select(width * 6.0, width * 2.0, dotted);
+            let step = total / max(round(total / max(period, 0.00001)), 1.0);
+            let local = along - round(along / step) * step;
+            if dotted {
+                alpha *= 1.0 - smoothstep(-0.5, 0.5, length(vec2<f32>(local, across - width / 2.0)) - width / 2.0);
+            } else {
+                alpha *= 1.0 - smoothstep(-0.5, 0.5, abs(local) - step / 4.0);
+            }
+        }
+        case BORDER_DOUBLE: {
+            let line = width / 3.0;
+            alpha *= max(1.0 - smoothstep(-0.5, 0.5, across - line), smoothstep(-0.5, 0.5, across - (width - line)));
+        }
+        case BORDER_GROOVE, BORDER_RIDGE: {
+            // The outer half is sunken for grooves and raised for ridges, the inner half the
+            // opposite.
+            let outer_half = across < width / 2.0;
+            shade = bevel(side, (style == BORDER_GROOVE) == outer_half);
+        }
+        case BORDER_INSET: {
+            shade = bevel(side, true);
+        }
+        case BORDER_OUTSET: {
+            shade = bevel(side, false);
+        }
+        default: {}
+    }
+
+    return vec4<f32>(side_color.rgb * shade, side_color.a * alpha * coverage);
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let instance = instance_data[in.instance_index];
 
-    // Rectangle
-    var pos: vec2<f32> = in.rect_pos;
-    var size: vec2<f32> = in.rect_size;
-    var dist: f32 = sdf_rounded_rect(
-        in.uv - pos - (size / 2.0),
-        size / 2.0,
-        in.border_radius
-    );
-    let rect_alpha = 1.0 - smoothstep(0.0, 2.0, dist);
-    var color: vec4<f32> = vec4<f32>(instance.rect_color.rgb, instance.rect_color.a * rect_alpha);
-
-    // Border
-    size += vec2<f32>((in.border_size.x + in.border_size.z), (in.border_size.y + in.border_size.w)) / 2.0;
-    pos -= vec2<f32>(in.border_size.x, in.border_size.y) / 2.0;
-    let border_dist = sdf_rounded_rect(
-        in.uv - pos - (size / 2.0),
-        size / 2.0,
-        in.border_radius
-    );
-    let border_alpha = 1.0 - smoothstep(0.0, 2.0, border_dist);
-    let is_top = (in.uv.y > pos.y + size.y - in.border_size.y);
-    let is_bottom = (in.uv.y < pos.y + in.border_size.w);
-    let is_left = (in.uv.x < pos.x + in.border_size.x);
-    let is_right = (in.uv.x > pos.x + size.x - in.border_size.z);
-    let border_color = select(
-        select(
-            instance.border_top_color,
-            instance.border_bottom_color,
-            is_bottom
-        ),
-        select(
-            instance.border_left_color,
-            instance.border_right_color,
-            is_right
-        ),
-        is_left || is_right
-    );
-
-    color = mix(color, vec4<f32>(border_color.rgb, border_color.a * border_alpha), smoothstep(0.0, 1.0, dist));
-    dist = border_dist;
-
-    // Outline offset
-    size += in.outline_offset * 2.0;
-    pos -= in.outline_offset;
-    color = mix(color, vec4<f32>(0.0), smoothstep(0.0, 1.0, dist));
-    dist = sdf_rounded_rect(
-        in.uv - pos - (size / 2.0),
-        size / 2.0,
-        in.border_radius
-    );
-    
-    // Outline width
-    size += in.outline_width * 2.0;
-    pos -= in.outline_width;
-    let outline_dist = sdf_rounded_rect(
-        in.uv - pos - (size / 2.0),
-        size / 2.0,
-        in.border_radius
-    );
-    let outline_alpha = 1.0 - smoothstep(0.0, 2.0, outline_dist);
-    let outline_color = vec4<f32>(instance.outline_color.rgb, instance.outline_color.a * outline_alpha);
-    color = mix(color, outline_color, smoothstep(0.0, 1.0, dist));
-    dist = outline_dist;
-
-    color = brightness_matrix(instance.brightness) 
-          * contrast_matrix(instance.contrast) 
-          * saturation_matrix(instance.saturate) 
-          * color;
-    let hue_rotate = hue_rotate(color.rgb, instance.hue_rotate);
-    let sepia_color = sepia(hue_rotate, instance.sepia);
-
-    return vec4<f32>(mix(sepia_color, vec3<f32>(1.0) - sepia_color, instance.invert), color.a);
+    switch instance.kind {
+        case KIND_LAYER, KIND_LAYER_ELLIPSE, KIND_LAYER_MASK: {
+            return composite(in, instance);
+        }
+        case KIND_LAYER_BLUR_X, KIND_LAYER_BLUR_Y: {
+            return layer_blur(in, instance);
+        }
+        case KIND_LAYER_SHADOW: {
+            return layer_shadow(in, instance);
+        }
+        case KIND_LAYER_PLACED: {
+            return placed_layer(in, instance);
+        }
+        case KIND_LAYER_LUMINANCE: {
+            return luminance_layer(in, instance);
+        }
+        case KIND_LAYER_MASKED: {
+            return masked_layer(in, instance);
+        }
+        default: {}
+    }
+    if instance.kind == KIND_OUTER_SHADOW || instance.kind == KIND_INNER_SHADOW {
+        return box_shadow(in, instance);
+    }
+    if instance.kind != KIND_BOX {
+        return background_layer(in, instance);
+    }
+
+    // Background, border and outline.
+    let p = in.uv;
+    let outline = instance.outline_width + instance.outline_offset;
+    let border_box = vec4<f32>(instance.rect_pos + outline, instance.rect_size - outline * 2.0);
+    let half_size = border_box.zw / 2.0;
+    let center = border_box.xy + half_size;
+    let dist = sdf_rounded_rect(p - center, half_size, instance.border_radius);
+
+    var color = vec4<f32>(instance.rect_color.rgb, instance.rect_color.a * (1.0 - smoothstep(-0.5, 0.5, dist)));
+    color = over(border(p, border_box, instance), color);
+
+    // The outline follows the border box's corners, `outline_offset` away from it.
+    let outline_inner = sdf_rounded_rect(p - center, half_size + instance.outline_offset, instance.border_radius);
+    let outline_outer = sdf_rounded_rect(p - center, half_size + outline, instance.border_radius);
+    let outline_alpha = smoothstep(-0.5, 0.5, outline_inner) * (1.0 - smoothstep(-0.5, 0.5, outline_outer));
+    if instance.outline_width > 0.0 {
+        color = over(vec4<f32>(instance.outline_color.rgb, instance.outline_color.a * outline_alpha), color);
+    }
+
+    return color;
 }
@@ -0,0 +1,177 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::{Rc, Weak},
+};
+
+use crate::tree::node::Node;
+
+thread_local! {
+    // Counts changes of bound signals, so trees only look for bindings to apply after one.
+    static VERSION: Cell<u64> = const { Cell::new(0) };
+}
+
+struct Inner<T> {
+    value: RefCell<T>,
+    subscribers: RefCell<Vec<Weak<dyn Fn()>>>,
+    // Keeps a computed signal subscribed to its sources for as long as it lives.
+    sources: RefCell<Vec<Rc<dyn Fn()>>>,
+}
+
+/// A shared, observable value. Clones refer to the same value.
+///
+/// Nodes follow a signal through `Node::bind`; setting the signal flags their bindings and the
+/// next `Tree::relayout` of the tree holding the nodes re-applies them.
+pub struct Signal<T> {
+    inner: Rc<Inner<T>>,
+}
+
+impl<T> Clone for Signal<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Anything a computed signal can depend on.
+pub trait Observable {
+    /// Calls `subscriber` after every change, for as long as it is alive elsewhere.
+    fn subscribe(&self, subscriber: &Rc<dyn Fn()>);
+}
+
+impl<T> Observable for Signal<T> {
+    fn subscribe(&self, subscriber: &Rc<dyn Fn()>) {
+        self.inner
+            .subscribers
+            .borrow_mut()
+            .push(Rc::downgrade(subscriber));
+    }
+}
+
+impl<T: 'static> Signal<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: Rc::new(Inner {
+                value: RefCell::new(value),
+                subscribers: RefCell::new(Vec::new()),
+                sources: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// A signal holding the result of `f`, recomputed whenever one of `sources` changes.
+    pub fn computed<F>(sources: &[&dyn Observable], f: F) -> Self
+    where
+        F: Fn() -> T + 'static,
+    {
+        let signal = Self::new(f());
+
+        let weak = Rc::downgrade(&signal.inner);
+        let subscriber: Rc<dyn Fn()> = Rc::new(move || {
+            if let Some(inner) = weak.upgrade() {
+                Signal { inner }.set(f());
+            }
+        });
+
+        sources
+            .iter()
+            .for_each(|source| source.subscribe(&subscriber));
+        signal.inner.sources.borrow_mut().push(subscriber);
+
+        signal
+    }
+
+    /// A signal derived from this one through `f`.
+    pub fn map<U, F>(&self, f: F) -> Signal<U>
+    where
+        U: 'static,
+        F: Fn(&T) -> U + 'static,
+    {
+        let source = self.clone();
+        Signal::computed(&[self], move || source.with(&f))
+    }
+
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.inner.value.borrow().clone()
+    }
+
+    pub fn with<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        f(&self.inner.value.borrow())
+    }
+
+    pub fn set(&self, value: T) {
+        *self.inner.value.borrow_mut() = value;
+        self.notify();
+    }
+
+    pub fn update<F>(&self, f: F)
+    where
+        F: FnOnce(&mut T),
+    {
+        f(&mut self.inner.value.borrow_mut());
+        self.notify();
+    }
+
+    fn notify(&self) {
+        let subscribers = {
+            let mut subscribers = self.inner.subscribers.borrow_mut();
+            subscribers.retain(|subscriber| subscriber.strong_count() > 0);
+            subscribers
+                .iter()
+                .filter_map(Weak::upgrade)
+                .collect::<Vec<_>>()
+        };
+
+        subscribers.iter().for_each(|subscriber| subscriber());
+    }
+}
+
+/// Ties a node to a signal. Lives on the node, so dropping the node ends the subscription.
+pub(crate) struct Binding {
+    // Set when the signal changed since the binding was last applied.
+    pub(crate) pending: Rc<Cell<bool>>,
+    apply: Box<dyn Fn(Node) -> Node>,
+    _subscription: Rc<dyn Fn()>,
+}
+
+impl Binding {
+    pub(crate) fn new<T, F>(signal: &Signal<T>, f: F) -> Self
+    where
+        T: 'static,
+        F: Fn(Node, &T) -> Node + 'static,
+    {
+        let pending = Rc::new(Cell::new(false));
+
+        let weak = Rc::downgrade(&pending);
+        let subscription: Rc<dyn Fn()> = Rc::new(move || {
+            if let Some(pending) = weak.upgrade() {
+                pending.set(true);
+                VERSION.set(VERSION.get() + 1);
+            }
+        });
+        signal.subscribe(&subscription);
+
+        let signal = signal.clone();
+        Self {
+            pending,
+            apply: Box::new(move |node| signal.with(|value| f(node, value))),
+            _subscription: subscription,
+        }
+    }
+
+    pub(crate) fn apply(&self, node: Node) -> Node {
+        self.pending.set(false);
+        (self.apply)(node)
+    }
+}
+
+// Changes when a bound signal does.
+pub(crate) fn version() -> u64 {
+    VERSION.get()
+}
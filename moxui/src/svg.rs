@@ -0,0 +1,107 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use image::RgbaImage;
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{Options, Tree},
+};
+
+pub use resvg::usvg::Error as SvgError;
+
+use crate::images::Image;
+
+static NEXT_SVG: AtomicU64 = AtomicU64::new(0);
+
+/// A parsed SVG document. The tree rasterizes it at the device-pixel size it is drawn at, so
+/// it stays sharp at any size, with `currentColor` following the node's font color.
+#[derive(Clone, Debug)]
+pub struct Svg {
+    id: u64,
+    source: Rc<[u8]>,
+    size: [f32; 2],
+    // The document parsed with `currentColor` set to the color it was last drawn in, so only
+    // a change of color parses it again.
+    tree: Rc<RefCell<([u8; 4], Rc<Tree>)>>,
+}
+
+impl PartialEq for Svg {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Svg {
+    /// Parses an SVG document, optionally gzip-compressed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SvgError> {
+        let color = [0, 0, 0, 255];
+        let tree = parse(bytes, color)?;
+
+        Ok(Self {
+            id: NEXT_SVG.fetch_add(1, Ordering::Relaxed),
+            source: bytes.into(),
+            size: [tree.size().width(), tree.size().height()],
+            tree: Rc::new(RefCell::new((color, Rc::new(tree)))),
+        })
+    }
+
+    /// Intrinsic width from the document's `width` attribute or `viewBox`.
+    pub fn width(&self) -> f32 {
+        self.size[0]
+    }
+
+    pub fn height(&self) -> f32 {
+        self.size[1]
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Renders the document stretched to `width` by `height` pixels, with `currentColor`
+    /// resolving to `color`. `None` for an empty size.
+    pub fn rasterize(&self, width: u32, height: u32, color: [u8; 4]) -> Option<Image> {
+        let tree = {
+            let mut cached = self.tree.borrow_mut();
+            if cached.0 != color {
+                *cached = (color, Rc::new(parse(&self.source, color).ok()?));
+            }
+            cached.1.clone()
+        };
+
+        let mut pixmap = Pixmap::new(width, height)?;
+        let transform = Transform::from_scale(
+            width as f32 / tree.size().width(),
+            height as f32 / tree.size().height(),
+        );
+        resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+        // The atlas holds straight alpha like decoded images, pixmaps are premultiplied.
+        let pixels = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect();
+
+        RgbaImage::from_raw(width, height, pixels).map(Image::from_rgba)
+    }
+}
+
+// Parses `bytes` with `currentColor` resolving to `color`.
+fn parse(bytes: &[u8], [r, g, b, a]: [u8; 4]) -> Result<Tree, SvgError> {
+    // Injected CSS wins over the document's own `color` attributes.
+    let options = Options {
+        style_sheet: Some(format!(
+            "svg {{ color: rgba({r}, {g}, {b}, {}) }}",
+            a as f32 / 255.0
+        )),
+        ..Options::default()
+    };
+    Tree::from_data(bytes, &options)
+}
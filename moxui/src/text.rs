@@ -0,0 +1,227 @@
+use calc_units::Units;
+use glyphon::{cosmic_text::Align, Color, FamilyOwned, Weight};
+use hypher::Lang;
+use unicode_linebreak::BreakOpportunity;
+
+/// Ends a line wherever it is in a paragraph, whatever the `white-space`, like `<br>`.
+pub const LINE_BREAK: char = '\u{2029}';
+
+/// A run of text in a paragraph with its own style, see `Node::set_rich_text`. Properties left
+/// `None` are those of the node, and `font_size` is relative to the node's font size.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TextSpan {
+    pub text: String,
+    pub color: Option<Color>,
+    pub font_family: Option<Vec<FamilyOwned>>,
+    pub font_weight: Option<Weight>,
+    pub font_style: Option<glyphon::Style>,
+    pub font_size: Option<Units>,
+}
+
+impl TextSpan {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// `text-align`: where lines of text and rows of inline boxes sit in the content box. Rows of
+/// boxes run left to right, so `Start` is `Left` and `End` is `Right` for them, and `Justify`
+/// only stretches the spaces of text.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum TextAlign {
+    #[default]
+    Start,
+    End,
+    Left,
+    Right,
+    Center,
+    Justify,
+}
+
+impl TextAlign {
+    // Alignment of shaped lines, `None` following the direction of each line.
+    pub(crate) fn align(self) -> Option<Align> {
+        match self {
+            TextAlign::Start => None,
+            TextAlign::End => Some(Align::End),
+            TextAlign::Left => Some(Align::Left),
+            TextAlign::Right => Some(Align::Right),
+            TextAlign::Center => Some(Align::Center),
+            TextAlign::Justify => Some(Align::Justified),
+        }
+    }
+
+    // Share of the free space of a row of inline boxes that goes before it.
+    pub(crate) fn offset(self) -> f32 {
+        match self {
+            TextAlign::Start | TextAlign::Left | TextAlign::Justify => 0.0,
+            TextAlign::Center => 0.5,
+            TextAlign::End | TextAlign::Right => 1.0,
+        }
+    }
+}
+
+/// Where a line can break inside a word, drawing a hyphen at its end unless `hyphens` is
+/// `none`.
+pub const SOFT_HYPHEN: char = '\u{ad}';
+
+// Keeps the letters around it on one line.
+const WORD_JOINER: char = '\u{2060}';
+
+/// `white-space`: which spaces and line breaks of the text are kept, and whether lines wrap.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum WhiteSpace {
+    #[default]
+    Normal,
+    Nowrap,
+    Pre,
+    PreWrap,
+    PreLine,
+    /// Like `PreWrap`, but spaces take room at the end of lines instead of hanging, and lines
+    /// can break after any of them.
+    BreakSpaces,
+}
+
+impl WhiteSpace {
+    pub fn wraps(self) -> bool {
+        !matches!(self, WhiteSpace::Nowrap | WhiteSpace::Pre)
+    }
+
+    /// Whether runs of spaces are kept as written.
+    pub fn preserves_spaces(self) -> bool {
+        matches!(
+            self,
+            WhiteSpace::Pre | WhiteSpace::PreWrap | WhiteSpace::BreakSpaces
+        )
+    }
+
+    // Collapses the white space of `text` that isn't kept. `space` tells whether the text
+    // before ends in a collapsible space, which swallows the spaces at the start of `text`,
+    // and is updated for the text after.
+    pub(crate) fn collapse(self, text: &str, space: &mut bool) -> String {
+        if self.preserves_spaces() {
+            *space = false;
+            // Shaping hangs spaces at the end of a line, but not no-break spaces, which a
+            // zero width space after each lets lines break after.
+            return match self {
+                WhiteSpace::BreakSpaces => text.replace(' ', "\u{a0}\u{200b}"),
+                _ => text.to_string(),
+            };
+        }
+
+        let mut collapsed = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                // Spaces around kept line breaks go too.
+                '\n' | LINE_BREAK if c == LINE_BREAK || self == WhiteSpace::PreLine => {
+                    if *space && collapsed.ends_with(' ') {
+                        collapsed.pop();
+                    }
+                    collapsed.push(c);
+                    *space = true;
+                }
+                ' ' | '\t' | '\n' | '\r' | '\x0c' => {
+                    if !*space {
+                        collapsed.push(' ');
+                    }
+                    *space = true;
+                }
+                c => {
+                    collapsed.push(c);
+                    *space = false;
+                }
+            }
+        }
+
+        collapsed
+    }
+}
+
+/// `word-break`. `KeepAll` only breaks lines at spaces and punctuation, keeping words of
+/// scripts like Chinese, Japanese and Korean together.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum WordBreak {
+    #[default]
+    Normal,
+    BreakAll,
+    KeepAll,
+    BreakWord,
+}
+
+impl WordBreak {
+    // Joins the letters of `text` that lines could break between, unless they may.
+    pub(crate) fn join(self, text: String) -> String {
+        if self != WordBreak::KeepAll {
+            return text;
+        }
+
+        let mut joined = String::with_capacity(text.len());
+        let mut start = 0;
+        for (index, opportunity) in unicode_linebreak::linebreaks(&text) {
+            joined.push_str(&text[start..index]);
+            start = index;
+
+            let before = text[..index].chars().next_back();
+            let after = text[index..].chars().next();
+            if opportunity == BreakOpportunity::Allowed
+                && before.is_some_and(char::is_alphanumeric)
+                && after.is_some_and(char::is_alphanumeric)
+            {
+                joined.push(WORD_JOINER);
+            }
+        }
+
+        joined
+    }
+}
+
+/// `overflow-wrap`: whether words too long for a line are broken between letters.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum OverflowWrap {
+    #[default]
+    Normal,
+    BreakWord,
+    Anywhere,
+}
+
+/// `hyphens`: lines can break at soft hyphens unless `None`, with a hyphen drawn at the end of
+/// the line. `Auto` adds soft hyphens between the syllables of words, with English
+/// hyphenation patterns.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Hyphens {
+    None,
+    #[default]
+    Manual,
+    Auto,
+}
+
+impl Hyphens {
+    // Drops the soft hyphens of `text`, or adds them, as the value asks.
+    pub(crate) fn apply(self, text: String) -> String {
+        match self {
+            Hyphens::None => text.replace(SOFT_HYPHEN, ""),
+            Hyphens::Manual => text,
+            Hyphens::Auto => {
+                let mut hyphenated = String::with_capacity(text.len());
+                let mut rest = text.as_str();
+                while let Some(start) = rest.find(char::is_alphabetic) {
+                    hyphenated.push_str(&rest[..start]);
+                    rest = &rest[start..];
+
+                    let end = rest
+                        .find(|c: char| !c.is_alphabetic())
+                        .unwrap_or(rest.len());
+                    let syllables = hypher::hyphenate(&rest[..end], Lang::English);
+                    hyphenated.push_str(&syllables.join(&SOFT_HYPHEN.to_string()));
+                    rest = &rest[end..];
+                }
+                hyphenated.push_str(rest);
+
+                hyphenated
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use calc_units::{Context, Units};
+
+use crate::math::{Mat4, Matrix};
+
+/// One function of `transform`. The list applies left to right around `transform-origin`,
+/// so the last function is the first to move the element.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Transform {
+    /// Percentages refer to the size of the border box.
+    Translate(Units, Units),
+    /// Like `Translate`, the z offset can't be a percentage.
+    Translate3d(Units, Units, Units),
+    Scale(f32, f32),
+    Scale3d(f32, f32, f32),
+    /// Clockwise, in degrees.
+    Rotate(f32),
+    /// Clockwise around the axis when looking along it, in degrees.
+    Rotate3d([f32; 3], f32),
+    /// Angles in degrees along x and y.
+    Skew(f32, f32),
+    /// `perspective()`, the distance of the viewer from the z = 0 plane.
+    Perspective(Units),
+    /// `matrix(a, b, c, d, e, f)`.
+    Matrix([f32; 6]),
+    /// `matrix3d()`, column major.
+    Matrix3d([f32; 16]),
+}
+
+/// `transform-style`: whether children are flattened into the plane of the element or share
+/// its 3D space. Boxes are painted in tree order either way, there is no depth sorting.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TransformStyle {
+    Flat,
+    Preserve3d,
+}
+
+/// `backface-visibility`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BackfaceVisibility {
+    Visible,
+    Hidden,
+}
+
+/// The matrix of `transforms` for a border box of `size`, around `origin`.
+pub(crate) fn matrix(
+    transforms: &[Transform],
+    origin: [f32; 2],
+    size: [f32; 2],
+    context: &Context,
+) -> Mat4 {
+    if transforms.is_empty() {
+        return Mat4::IDENTITY;
+    }
+
+    let length = |units: &Units, reference_size: f32| {
+        units.to_px(&Context {
+            root_font_size: context.root_font_size,
+            reference_size,
+            parent_font_size: context.parent_font_size,
+            viewport: context.viewport,
+            auto: 0.0,
+            dpi: context.dpi,
+        })
+    };
+    let matrix = transforms.iter().fold(
+        Mat4::translate(origin[0], origin[1], 0.0),
+        |matrix, transform| {
+            let next = match transform {
+                Transform::Translate(x, y) => {
+                    Mat4::translate(length(x, size[0]), length(y, size[1]), 0.0)
+                }
+                Transform::Translate3d(x, y, z) => {
+                    Mat4::translate(length(x, size[0]), length(y, size[1]), length(z, 0.0))
+                }
+                Transform::Scale(x, y) => Mat4::scale(*x, *y, 1.0),
+                Transform::Scale3d(x, y, z) => Mat4::scale(*x, *y, *z),
+                Transform::Rotate(degrees) => Mat4::rotate([0.0, 0.0, 1.0], *degrees),
+                Transform::Rotate3d(axis, degrees) => Mat4::rotate(*axis, *degrees),
+                Transform::Skew(x, y) => Mat4::skew(*x, *y),
+                Transform::Perspective(distance) => Mat4::perspective(length(distance, 0.0)),
+                Transform::Matrix(matrix) => Mat4::affine(*matrix),
+                Transform::Matrix3d(matrix) => {
+                    [0, 1, 2, 3].map(|column| [0, 1, 2, 3].map(|row| matrix[column * 4 + row]))
+                }
+            };
+            matrix.multiply(&next)
+        },
+    );
+
+    matrix.multiply(&Mat4::translate(-origin[0], -origin[1], 0.0))
+}
+
+/// `perspective` seen from `origin`, for the children of an element.
+pub(crate) fn perspective(distance: f32, origin: [f32; 2]) -> Mat4 {
+    Mat4::translate(origin[0], origin[1], 0.0)
+        .multiply(&Mat4::perspective(distance))
+        .multiply(&Mat4::translate(-origin[0], -origin[1], 0.0))
+}
+
+/// A node's transform from layout to document coordinates, in two parts: `flat` up to the
+/// 3D rendering context the node is in, then `context`, built up through `preserve-3d`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Accumulated {
+    flat: Mat4,
+    context: Mat4,
+}
+
+impl Accumulated {
+    pub const IDENTITY: Self = Self {
+        flat: Mat4::IDENTITY,
+        context: Mat4::IDENTITY,
+    };
+
+    /// `local` applied before `self`.
+    pub fn then(&self, local: &Mat4) -> Self {
+        Self {
+            flat: self.flat,
+            context: self.context.multiply(local),
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        self.flat.multiply(&self.context)
+    }
+
+    /// Whether the back of the z = 0 plane faces the viewer within the 3D rendering context.
+    pub fn back_facing(&self) -> bool {
+        self.context
+            .inverse()
+            .is_some_and(|inverse| inverse[2][2] < 0.0)
+    }
+
+    /// Starts a new 3D rendering context drawn onto the plane of `self`.
+    pub fn flatten(&self) -> Self {
+        let mut flatten = Mat4::IDENTITY;
+        flatten[2][2] = 0.0;
+        Self {
+            flat: self.matrix().multiply(&flatten),
+            context: Mat4::IDENTITY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTEXT: Context = Context {
+        root_font_size: 16.0,
+        reference_size: 0.0,
+        parent_font_size: 16.0,
+        viewport: (800.0, 600.0),
+        auto: 0.0,
+        dpi: 96.0,
+    };
+
+    fn assert_close(actual: [f32; 2], expected: [f32; 2]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-3,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    // Where `point` of a 100x40 border box goes.
+    fn apply(transforms: &[Transform], origin: [f32; 2], point: [f32; 2]) -> [f32; 2] {
+        matrix(transforms, origin, [100.0, 40.0], &CONTEXT).apply(point)
+    }
+
+    #[test]
+    fn test_transform_functions() {
+        assert_eq!(
+            matrix(&[], [50.0, 20.0], [100.0, 40.0], &CONTEXT),
+            Mat4::IDENTITY
+        );
+
+        // Percentages of translations refer to the border box.
+        let translate = [Transform::Translate(Units::Px(10.0), Units::Perc(50.0))];
+        assert_close(apply(&translate, [50.0, 20.0], [0.0, 0.0]), [10.0, 20.0]);
+
+        // Rotations are clockwise around the origin.
+        let rotate = [Transform::Rotate(90.0)];
+        assert_close(apply(&rotate, [50.0, 20.0], [0.0, 0.0]), [70.0, -30.0]);
+        assert_close(apply(&rotate, [0.0, 0.0], [10.0, 0.0]), [0.0, 10.0]);
+
+        let scale = [Transform::Scale(2.0, 3.0)];
+        assert_close(apply(&scale, [50.0, 20.0], [0.0, 0.0]), [-50.0, -40.0]);
+        let skew = [Transform::Skew(45.0, 0.0)];
+        assert_close(apply(&skew, [0.0, 0.0], [0.0, 10.0]), [10.0, 10.0]);
+
+        let affine = [Transform::Matrix([1.0, 2.0, 3.0, 4.0, 5.0, 6.0])];
+        let matrix3d = [Transform::Matrix3d([
+            1.0, 2.0, 0.0, 0.0, 3.0, 4.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 5.0, 6.0, 0.0, 1.0,
+        ])];
+        assert_close(apply(&affine, [0.0, 0.0], [1.0, 1.0]), [9.0, 12.0]);
+        assert_close(apply(&matrix3d, [0.0, 0.0], [1.0, 1.0]), [9.0, 12.0]);
+    }
+
+    #[test]
+    fn test_transform_order() {
+        // The last function moves the element first.
+        let translate = Transform::Translate(Units::Px(10.0), Units::Px(0.0));
+        let scale = Transform::Scale(2.0, 2.0);
+        let translated = [translate.clone(), scale.clone()];
+        assert_close(apply(&translated, [0.0, 0.0], [1.0, 0.0]), [12.0, 0.0]);
+        let scaled = [scale, translate];
+        assert_close(apply(&scaled, [0.0, 0.0], [1.0, 0.0]), [22.0, 0.0]);
+    }
+
+    #[test]
+    fn test_accumulated() {
+        let turned = Accumulated::IDENTITY.then(&Mat4::rotate([0.0, 1.0, 0.0], 180.0));
+        assert!(turned.back_facing());
+        assert!(!turned
+            .then(&Mat4::rotate([1.0, 0.0, 0.0], 180.0))
+            .back_facing());
+        // A new 3D rendering context faces the viewer.
+        assert!(!turned.flatten().back_facing());
+
+        // Flattened, children stay on the plane instead of coming closer.
+        let seen = Accumulated::IDENTITY.then(&perspective(100.0, [0.0, 0.0]));
+        let closer = Mat4::translate(0.0, 0.0, 50.0);
+        assert_close(
+            seen.then(&closer).matrix().apply([10.0, 10.0]),
+            [20.0, 20.0],
+        );
+        let flat = seen.flatten().then(&closer);
+        assert_close(flat.matrix().apply([10.0, 10.0]), [10.0, 10.0]);
+    }
+}
@@ -1,20 +1,211 @@
+pub(crate) mod atlas;
+mod layers;
 pub mod node;
 mod text;
 
-use crate::buffers;
-use calc_units::{Context, Units};
-use glyphon::{TextArea, TextBounds};
+use crate::{
+    buffers, fonts::Fonts, gradient::GradientStop, rectangle::InstanceData, signal,
+    transform::Accumulated,
+};
+use calc_units::Units;
+use glyphon::{FontSystem, TextArea, TextBounds};
 use node::Node;
-use std::ops::{Deref, DerefMut};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut, Range},
+};
+
+// How many layouts a shaped run is kept in the cache without being used.
+const SHAPE_RUN_CACHE_AGES: u64 = 8;
 
 pub struct Tree {
     pub render_pipeline: wgpu::RenderPipeline,
     pub projection_uniform: buffers::ProjectionUniform,
     pub index_buffer: buffers::IndexBuffer,
     pub generic_rect: buffers::VertexBuffer,
-    pub node: node::Node,
+    // Reached through `Deref`, mutably only with a redraw.
+    node: node::Node,
     pub text: text::TextContext,
-    pub state: State,
+    pub atlas: atlas::ImageAtlas,
+    pub layers: layers::LayerTargets,
+    pub instance_buffer: buffers::StorageBuffer<InstanceData>,
+    pub instance_bind_group_layout: wgpu::BindGroupLayout,
+    pub instance_bind_group: wgpu::BindGroup,
+    pub gradient_buffer: buffers::StorageBuffer<GradientStop>,
+    pub paint_bind_group_layout: wgpu::BindGroupLayout,
+    // Changed through `scroll`, `set_viewport`, `set_dpi` and `set_root_font_size`, which
+    // lay out and redraw what they affect.
+    state: State,
+    /// Fonts and shaping caches shared by all text of the tree.
+    pub fonts: Fonts,
+    // The generation of `fonts` the text was last shaped with.
+    fonts_generation: u64,
+    // The version of bound signals the nodes were last synced with.
+    signals_version: u64,
+    // The last frame collected and what it is drawn with, reused until `redraw` is set by
+    // anything that may change it.
+    frame: Option<Frame>,
+    redraw: bool,
+}
+
+// The items of every layer of a frame, with the bind groups they are drawn with and the
+// projections of the offscreen layers.
+struct Frame {
+    items: Vec<Vec<DrawItem>>,
+    projections: Vec<buffers::ProjectionUniform>,
+    bind_groups: Vec<wgpu::BindGroup>,
+    masked_bind_groups: HashMap<(usize, usize), wgpu::BindGroup>,
+    atlas_bind_group: wgpu::BindGroup,
+}
+
+// Everything collected from the nodes for one frame. The first layer is drawn straight into
+// the render pass, the others offscreen in `order`, each before the layers compositing it.
+pub(crate) struct DrawList {
+    pub instance_data: Vec<InstanceData>,
+    pub gradient_stops: Vec<GradientStop>,
+    pub layers: Vec<DrawLayer>,
+    pub order: Vec<usize>,
+    current: usize,
+}
+
+#[derive(Default)]
+pub(crate) struct DrawLayer {
+    pub items: Vec<DrawItem>,
+    pub text_data: Vec<text::TextData>,
+}
+
+// Draws in paint order. Text goes on top of a layer's items.
+pub(crate) enum DrawItem {
+    Instances(Range<u32>),
+    Layer {
+        layer: usize,
+        instance: u32,
+    },
+    Masked {
+        layer: usize,
+        mask: usize,
+        instance: u32,
+    },
+}
+
+impl Default for DrawList {
+    fn default() -> Self {
+        Self {
+            instance_data: Vec::new(),
+            gradient_stops: Vec::new(),
+            layers: vec![DrawLayer::default()],
+            order: Vec::new(),
+            current: 0,
+        }
+    }
+}
+
+impl DrawList {
+    pub fn extend_instances(&mut self, instances: impl IntoIterator<Item = InstanceData>) {
+        let start = self.instance_data.len() as u32;
+        self.instance_data.extend(instances);
+        let end = self.instance_data.len() as u32;
+        if start == end {
+            return;
+        }
+
+        let items = &mut self.layers[self.current].items;
+        match items.last_mut() {
+            Some(DrawItem::Instances(range)) if range.end == start => range.end = end,
+            _ => items.push(DrawItem::Instances(start..end)),
+        }
+    }
+
+    pub fn push_text(&mut self, text_data: text::TextData) {
+        self.layers[self.current].text_data.push(text_data);
+    }
+
+    /// Collects into a new layer until `end_layer`, returning the layer to go back to.
+    pub fn begin_layer(&mut self) -> usize {
+        let parent = self.current;
+        self.layers.push(DrawLayer::default());
+        self.current = self.layers.len() - 1;
+        parent
+    }
+
+    /// Closes the current layer and goes back to `parent`, returning the closed layer unless
+    /// nothing was drawn into it. Empty layers are dropped, any layer opened inside one is
+    /// empty too and already gone.
+    pub fn end_layer(&mut self, parent: usize) -> Option<usize> {
+        let layer = self.current;
+        self.current = parent;
+
+        if self.layers[layer].items.is_empty() && self.layers[layer].text_data.is_empty() {
+            self.layers.pop();
+            return None;
+        }
+
+        self.order.push(layer);
+        Some(layer)
+    }
+
+    /// Adds a layer drawing each of `sources` with its instance, in order, and returns it.
+    pub fn pass(&mut self, sources: &[(usize, InstanceData)]) -> usize {
+        let mut pass = DrawLayer::default();
+        for &(layer, instance) in sources {
+            pass.items.push(DrawItem::Layer {
+                layer,
+                instance: self.instance_data.len() as u32,
+            });
+            self.instance_data.push(instance);
+        }
+
+        self.layers.push(pass);
+        self.order.push(self.layers.len() - 1);
+        self.layers.len() - 1
+    }
+
+    /// Adds a layer drawing `layer` with `instance`, which reads `mask` along with it, and
+    /// returns it.
+    pub fn masked_pass(&mut self, layer: usize, mask: usize, instance: InstanceData) -> usize {
+        let pass = DrawLayer {
+            items: vec![DrawItem::Masked {
+                layer,
+                mask,
+                instance: self.instance_data.len() as u32,
+            }],
+            text_data: Vec::new(),
+        };
+        self.instance_data.push(instance);
+
+        self.layers.push(pass);
+        self.order.push(self.layers.len() - 1);
+        self.layers.len() - 1
+    }
+
+    /// Moves everything drawn into the current layer so far to a layer of its own, drawn back
+    /// with `composite` as the first item, and returns it so it can be read from. `None` if
+    /// nothing was drawn.
+    pub fn snapshot(&mut self, composite: InstanceData) -> Option<usize> {
+        let current = &mut self.layers[self.current];
+        if current.items.is_empty() && current.text_data.is_empty() {
+            return None;
+        }
+        let snapshot = DrawLayer {
+            items: std::mem::take(&mut current.items),
+            text_data: std::mem::take(&mut current.text_data),
+        };
+
+        self.layers.push(snapshot);
+        let layer = self.layers.len() - 1;
+        self.order.push(layer);
+        self.composite(layer, composite);
+        Some(layer)
+    }
+
+    /// Draws a closed `layer` into the current one with `instance`.
+    pub fn composite(&mut self, layer: usize, instance: InstanceData) {
+        self.layers[self.current].items.push(DrawItem::Layer {
+            layer,
+            instance: self.instance_data.len() as u32,
+        });
+        self.instance_data.push(instance);
+    }
 }
 
 pub struct Config {
@@ -33,7 +224,24 @@ pub struct State {
 }
 
 impl Tree {
+    /// Builds a tree with `f`, shaping its text with the system fonts.
     pub fn new<F>(device: &wgpu::Device, queue: &wgpu::Queue, config: &Config, f: F) -> Self
+    where
+        F: Fn(node::Node) -> node::Node,
+    {
+        Self::with_font_system(device, queue, config, FontSystem::new(), f)
+    }
+
+    /// Like `new`, shaping text with the fonts of `font_system`. Scanning the system fonts is
+    /// slow, so embedders with several trees can load a `fontdb::Database` once and give each
+    /// tree a `FontSystem` made from a clone of it, or load only the fonts they ship.
+    pub fn with_font_system<F>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &Config,
+        font_system: FontSystem,
+        f: F,
+    ) -> Self
     where
         F: Fn(node::Node) -> node::Node,
     {
@@ -53,9 +261,9 @@ impl Tree {
         let projection_uniform =
             buffers::ProjectionUniform::new(device, 0.0, config.width, 0.0, config.height);
 
-        let storage_buffer_layout =
+        let instance_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Bind Group Layout"),
+                label: Some("Instance Bind Group Layout"),
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
@@ -68,12 +276,68 @@ impl Tree {
                 }],
             });
 
+        // Gradient stops and the image atlas, shared by every instance of a frame, the layer
+        // composites draw from and the mask layer of masked passes.
+        let paint_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Paint Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
                 bind_group_layouts: &[
                     &projection_uniform.bind_group_layout,
-                    &storage_buffer_layout,
+                    &instance_bind_group_layout,
+                    &paint_bind_group_layout,
                 ],
                 push_constant_ranges: &[],
             });
@@ -107,7 +371,8 @@ impl Tree {
                 topology: wgpu::PrimitiveTopology::TriangleList,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
+                // Transforms can mirror quads, flipping their winding.
+                cull_mode: None,
                 polygon_mode: wgpu::PolygonMode::Fill,
                 unclipped_depth: false,
                 conservative: false,
@@ -134,17 +399,37 @@ impl Tree {
             },
         ];
 
+        let instance_buffer = buffers::StorageBuffer::new(device, "Instance buffer");
+        let instance_bind_group =
+            create_instance_bind_group(device, &instance_bind_group_layout, &instance_buffer);
+
         Self {
             text,
+            atlas: atlas::ImageAtlas::new(device, config.format),
+            layers: layers::LayerTargets::new(config.format),
+            instance_buffer,
+            instance_bind_group_layout,
+            instance_bind_group,
+            gradient_buffer: buffers::StorageBuffer::new(device, "Gradient stop buffer"),
+            paint_bind_group_layout,
             render_pipeline,
             index_buffer: buffers::IndexBuffer::new(device, &[0, 1, 3, 1, 2, 3]),
             generic_rect: buffers::VertexBuffer::new(device, &generic_rect_vertices),
             projection_uniform,
             node,
             state,
+            fonts: Fonts::new(font_system),
+            fonts_generation: 0,
+            signals_version: signal::version(),
+            frame: None,
+            redraw: true,
         }
     }
 
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
     pub fn scroll(&mut self, device: &wgpu::Device, x: f32, y: f32) {
         let new_x = self.state.scroll.0 + x;
         let new_y = self.state.scroll.1 + y;
@@ -159,6 +444,7 @@ impl Tree {
             self.state.scroll.1,
             self.state.scroll.1 + self.state.viewport.1,
         );
+        self.redraw = true;
     }
 
     pub fn set_viewport(&mut self, device: &wgpu::Device, width: f32, height: f32) {
@@ -170,93 +456,307 @@ impl Tree {
             self.state.scroll.1 + height,
         );
         self.state.viewport = (width, height);
+        self.node.dirty.node = true;
+        self.redraw = true;
+    }
+
+    /// Sets the pixels per inch that physical lengths like `pt` and `mm` resolve with, laying
+    /// every node out again on the next `relayout`.
+    pub fn set_dpi(&mut self, dpi: f32) {
+        if self.state.dpi != dpi {
+            self.state.dpi = dpi;
+            self.node.mark_all_dirty();
+            self.redraw = true;
+        }
     }
 
+    /// Sets the font size `rem` lengths refer to, laying every node out again on the next
+    /// `relayout`.
+    pub fn set_root_font_size(&mut self, size: f32) {
+        if self.state.root_font_size != size {
+            self.state.root_font_size = size;
+            self.node.mark_all_dirty();
+            self.redraw = true;
+        }
+    }
+
+    /// Draws the tree into `render_pass`. The instances, layers and text are only collected
+    /// and uploaded again after the tree changed, otherwise the last frame is drawn again.
     pub fn render(
         &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         render_pass: &mut wgpu::RenderPass,
     ) {
-        let mut instance_data = Vec::new();
-        let mut text_data = Vec::new();
+        if self.redraw || self.frame.is_none() {
+            self.atlas.repack();
+            let frame = self.prepare(device, queue);
+            self.frame = Some(frame);
+            // Images left out for lack of space make it into the next frame, after a repack.
+            self.redraw = self.atlas.missed();
+        }
 
-        self.collect_instances(
-            &mut instance_data,
-            &mut text_data,
-            &self.get_state(),
+        if let Some(frame) = &self.frame {
+            self.draw(frame, render_pass, 0);
+        }
+    }
+
+    /// Collects the tree, uploads what changed and draws the offscreen layers.
+    fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> Frame {
+        let mut draw_list = DrawList::default();
+
+        self.node.collect_instances(
+            &mut draw_list,
+            &mut self.atlas,
+            &self.node.get_state(),
+            &Accumulated::IDENTITY,
             &self.state,
         );
+        self.atlas.prepare(device, queue);
+
+        // Layers cover what is drawn into them, text goes where its layer starts.
+        let viewport = [self.state.viewport.0, self.state.viewport.1];
+        let scroll = [self.state.scroll.0, self.state.scroll.1];
+        let bounds = layers::place(&mut draw_list, viewport, scroll);
+        let DrawList {
+            instance_data,
+            gradient_stops,
+            layers,
+            order,
+            ..
+        } = draw_list;
+
+        self.text.prepare(
+            device,
+            queue,
+            &mut self.fonts.font_system,
+            layers
+                .iter()
+                .zip(&bounds)
+                .map(|(layer, &[x, y, width, height])| {
+                    let text = layer
+                        .text_data
+                        .iter()
+                        .map(|text_data| {
+                            let [left, top] = [text_data.x - x, text_data.y - y];
+                            TextArea {
+                                buffer: &text_data.buffer,
+                                left,
+                                top,
+                                scale: text_data.scale,
+                                bounds: TextBounds {
+                                    left: left as i32,
+                                    top: top as i32,
+                                    right: (left + text_data.width * text_data.scale) as i32,
+                                    bottom: (top + text_data.height * text_data.scale) as i32,
+                                },
+                                default_color: text_data.color,
+                                custom_glyphs: &[],
+                            }
+                        })
+                        .collect();
+                    ([width.ceil() as u32, height.ceil() as u32], text)
+                }),
+        );
+
+        if self.instance_buffer.write(device, queue, instance_data) {
+            self.instance_bind_group = create_instance_bind_group(
+                device,
+                &self.instance_bind_group_layout,
+                &self.instance_buffer,
+            );
+        }
+        self.gradient_buffer.write(device, queue, gradient_stops);
+
+        let paint_bind_group = |layer: &wgpu::TextureView, mask: &wgpu::TextureView| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Paint bind group"),
+                layout: &self.paint_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: self.gradient_buffer.buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&self.atlas.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&self.atlas.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(layer),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: wgpu::BindingResource::TextureView(mask),
+                    },
+                ],
+            })
+        };
+
+        // Composites bind the layer they draw, and masked passes their mask too. Textures an
+        // instance doesn't read are filled in with the atlas.
+        let offscreen = &bounds[1..];
+        self.layers.prepare(
+            device,
+            offscreen
+                .iter()
+                .map(|bounds| [bounds[2] as u32, bounds[3] as u32]),
+        );
+        let views = self.layers.views().collect::<Vec<_>>();
+        let bind_groups = views
+            .iter()
+            .map(|view| paint_bind_group(view, &self.atlas.view))
+            .collect::<Vec<_>>();
+        let masked_bind_groups = layers
+            .iter()
+            .flat_map(|layer| &layer.items)
+            .filter_map(|item| match *item {
+                DrawItem::Masked { layer, mask, .. } => Some((
+                    (layer, mask),
+                    paint_bind_group(views[layer - 1], views[mask - 1]),
+                )),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let atlas_bind_group = paint_bind_group(&self.atlas.view, &self.atlas.view);
 
-        let text_data = text_data
+        let projections = offscreen
             .iter()
-            .map(|text_data| TextArea {
-                buffer: &text_data.buffer,
-                left: text_data.x,
-                top: text_data.y,
-                scale: 1.0,
-                bounds: TextBounds {
-                    left: text_data.x as i32,
-                    top: text_data.y as i32,
-                    right: (text_data.x + text_data.width) as i32,
-                    bottom: (text_data.y + text_data.height) as i32,
-                },
-                default_color: text_data.color,
-                custom_glyphs: &[],
+            .map(|&[x, y, width, height]| {
+                let [left, top] = [scroll[0] + x, scroll[1] + y];
+                buffers::ProjectionUniform::new(device, left, left + width, top, top + height)
             })
             .collect();
 
-        let storage_buffer = buffers::StorageBuffer::new(device, instance_data.into());
+        let frame = Frame {
+            items: layers.into_iter().map(|layer| layer.items).collect(),
+            projections,
+            bind_groups,
+            masked_bind_groups,
+            atlas_bind_group,
+        };
+
+        // Layer textures keep their contents, so they are only drawn when the frame changes.
+        if !order.is_empty() {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Layer encoder"),
+            });
+            for index in order {
+                let mut layer_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Layer pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: views[index - 1],
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                self.draw(&frame, &mut layer_pass, index);
+            }
+            queue.submit([encoder.finish()]);
+        }
+
+        frame
+    }
 
+    fn draw(&self, frame: &Frame, render_pass: &mut wgpu::RenderPass, index: usize) {
+        let projection = match index {
+            0 => &self.projection_uniform,
+            index => &frame.projections[index - 1],
+        };
         render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_bind_group(0, &self.projection_uniform.bind_group, &[]);
-        render_pass.set_bind_group(1, &storage_buffer.bind_group, &[]);
+        render_pass.set_bind_group(0, &projection.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.instance_bind_group, &[]);
         render_pass.set_vertex_buffer(0, self.generic_rect.slice(..));
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..self.index_buffer.size(), 0, 0..storage_buffer.len());
 
-        self.text.render(device, queue, render_pass, text_data);
+        for item in &frame.items[index] {
+            let (bind_group, instances) = match item {
+                DrawItem::Instances(range) => (&frame.atlas_bind_group, range.clone()),
+                DrawItem::Layer { layer, instance } => {
+                    (&frame.bind_groups[layer - 1], *instance..instance + 1)
+                }
+                DrawItem::Masked {
+                    layer,
+                    mask,
+                    instance,
+                } => (
+                    &frame.masked_bind_groups[&(*layer, *mask)],
+                    *instance..instance + 1,
+                ),
+            };
+            render_pass.set_bind_group(2, bind_group, &[]);
+            render_pass.draw_indexed(0..self.index_buffer.size(), 0, instances);
+        }
+
+        self.text.render(index, render_pass);
     }
 
     pub fn finish(mut self) -> Self {
-        let state = self.state.clone();
-
-        self.compute_static_properties(
-            &node::ParentState {
-                x: 0.0,
-                y: 0.0,
-                width: state.viewport.0,
-                height: state.viewport.1,
-                font_size: state.root_font_size,
-            },
-            &state,
-        );
-        let auto = self.compute_layout(&state);
-
-        let context = Context {
-            root_font_size: self.state.root_font_size,
-            dpi: self.state.dpi,
-            parent_font_size: self.state.root_font_size,
-            viewport: self.state.viewport,
-            reference_size: 0.0,
-            auto: 0.0,
-        };
+        self.relayout();
+        self
+    }
 
-        self.width = self.style.width(&Context {
-            auto: auto.0,
-            reference_size: state.viewport.0,
-            ..context
-        });
-        self.height = self.style.height(&Context {
-            auto: auto.1,
-            reference_size: state.viewport.1,
-            ..context
-        });
+    /// Builds a fresh description of the tree with `f`, as in `new`, patches the live tree to
+    /// match it and lays out whatever changed.
+    pub fn reconcile<F>(&mut self, f: F)
+    where
+        F: Fn(node::Node) -> node::Node,
+    {
+        let mut node = node::Node::default();
+        node.style.width = Units::Perc(100.0);
+        self.node.reconcile(f(node));
+        self.redraw = true;
+        self.relayout();
+    }
 
-        self.offset_children();
+    /// Recomputes layout for the nodes changed through `update`, `insert_child`,
+    /// `remove_child`, `move_child` or bound signals since the last layout, reusing the rest.
+    pub fn relayout(&mut self) {
+        self.redraw |= relayout(
+            &mut self.node,
+            &self.state,
+            &mut self.fonts,
+            &mut self.fonts_generation,
+            &mut self.signals_version,
+        );
+    }
+}
 
-        self
+// Lays `node` out for `Tree::relayout`, given the generation of the fonts and the version of
+// the signals it was last laid out with, and returns whether anything may look different.
+fn relayout(
+    node: &mut Node,
+    state: &State,
+    fonts: &mut Fonts,
+    fonts_generation: &mut u64,
+    signals_version: &mut u64,
+) -> bool {
+    // Fonts loaded since the last layout may change any text.
+    if fonts.generation != *fonts_generation {
+        *fonts_generation = fonts.generation;
+        node.mark_all_dirty();
+    }
+    // Nodes added since the last layout may have missed changes of their signals too.
+    let mut changed = false;
+    let dirty = node.dirty.node || node.dirty.descendants;
+    if dirty || *signals_version != signal::version() {
+        *signals_version = signal::version();
+        changed |= node.apply_signals();
     }
+    changed |= node.dirty.node || node.dirty.descendants;
+    node.layout(state, fonts);
+    fonts.font_system.shape_run_cache.trim(SHAPE_RUN_CACHE_AGES);
+    changed
 }
 
 impl Deref for Tree {
@@ -266,8 +766,131 @@ impl Deref for Tree {
     }
 }
 
+// Anything done to the nodes may change how they look, so it takes a redraw.
 impl DerefMut for Tree {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        self.redraw = true;
         &mut self.node
     }
 }
+
+fn create_instance_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffer: &buffers::StorageBuffer<InstanceData>,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Instance bind group"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 1,
+            resource: buffer.buffer.as_entire_binding(),
+        }],
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{filter::ColorMatrix, signal::Signal};
+    use glyphon::fontdb;
+
+    // Any instance covering `rect`.
+    pub(crate) fn quad(rect: [f32; 4]) -> InstanceData {
+        InstanceData::layer(rect, &ColorMatrix::IDENTITY)
+    }
+
+    pub(crate) fn state() -> State {
+        State {
+            root_font_size: 16.0,
+            viewport: (200.0, 100.0),
+            scroll: (0.0, 0.0),
+            dpi: 96.0,
+        }
+    }
+
+    // The items of `layer`, as instance ranges and the layers they draw.
+    pub(crate) fn items(draw_list: &DrawList, layer: usize) -> Vec<String> {
+        draw_list.layers[layer]
+            .items
+            .iter()
+            .map(|item| match item {
+                DrawItem::Instances(range) => format!("{range:?}"),
+                DrawItem::Layer { layer, .. } => format!("layer {layer}"),
+                DrawItem::Masked { layer, mask, .. } => format!("layer {layer} masked by {mask}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_list_layers() {
+        let mut draw_list = DrawList::default();
+        draw_list.extend_instances([quad([0.0; 4])]);
+        draw_list.extend_instances([quad([0.0; 4])]);
+
+        // Empty layers are dropped along with the layers opened inside them.
+        let parent = draw_list.begin_layer();
+        let inner = draw_list.begin_layer();
+        assert_eq!(draw_list.end_layer(inner), None);
+        assert_eq!(draw_list.end_layer(parent), None);
+        assert_eq!(draw_list.layers.len(), 1);
+
+        let parent = draw_list.begin_layer();
+        let inner = draw_list.begin_layer();
+        draw_list.extend_instances([quad([0.0; 4])]);
+        assert_eq!(draw_list.end_layer(inner), Some(2));
+        draw_list.composite(2, quad([0.0; 4]));
+        assert_eq!(draw_list.end_layer(parent), Some(1));
+        draw_list.composite(1, quad([0.0; 4]));
+
+        // Layers are drawn before the layers they are composited into.
+        assert_eq!(draw_list.order, [2, 1]);
+        assert_eq!(items(&draw_list, 0), ["0..2", "layer 1"]);
+        assert_eq!(items(&draw_list, 1), ["layer 2"]);
+        assert_eq!(items(&draw_list, 2), ["2..3"]);
+    }
+
+    #[test]
+    fn test_relayout_redraw() {
+        let alpha = Signal::new(1.0);
+        let unbound = Signal::new(1.0);
+        let mut node = Node::default().add_child(|node| {
+            node.set_size(Units::Auto, Units::Px(10.0))
+                .bind(&alpha, |node, alpha| {
+                    node.set_background_color(0.0, 0.0, 0.0, *alpha)
+                })
+        });
+        let handle = node.children[0].handle;
+        // No fonts, as there is no text to shape.
+        let mut fonts = Fonts::new(FontSystem::new_with_locale_and_db(
+            "en-US".to_string(),
+            fontdb::Database::new(),
+        ));
+        let (mut fonts_generation, mut signals_version) = (0, signal::version());
+        let mut relayout = |node: &mut Node| {
+            relayout(
+                node,
+                &state(),
+                &mut fonts,
+                &mut fonts_generation,
+                &mut signals_version,
+            )
+        };
+
+        assert!(relayout(&mut node));
+        // Nothing changed, so the last frame can be drawn again.
+        assert!(!relayout(&mut node));
+
+        unbound.set(0.5);
+        assert!(!relayout(&mut node));
+        // Paint-only changes take a redraw but no layout.
+        alpha.set(0.5);
+        assert!(relayout(&mut node));
+        assert!(!relayout(&mut node));
+
+        assert!(node.update(handle, |node| node.set_size(Units::Auto, Units::Px(20.0))));
+        assert!(relayout(&mut node));
+        assert_eq!(node.height, 20.0);
+        assert!(!relayout(&mut node));
+    }
+}
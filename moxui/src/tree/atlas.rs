@@ -0,0 +1,412 @@
+use std::{
+    collections::{hash_map, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    rc::{Rc, Weak},
+};
+
+use image::RgbaImage;
+
+use crate::{images::Image, svg::Svg};
+
+const INITIAL_SIZE: u32 = 1024;
+// Gap between images so filtering never samples a neighbour.
+const PADDING: u32 = 1;
+
+struct Entry {
+    rect: [u32; 4],
+    pixels: Weak<RgbaImage>,
+}
+
+// An SVG rendered at one size and color, kept while it is drawn every frame.
+struct Raster {
+    image: Image,
+    used: bool,
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    width: u32,
+}
+
+/// Packs every image drawn by the tree into one texture. Space is handed out while
+/// collecting instances and the pixels are uploaded in `prepare`, so rectangles are in texels
+/// and stay valid when the texture grows. Images only move when `repack` runs before a
+/// collection, so the instances of a frame never refer to stale rectangles.
+pub struct ImageAtlas {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    format: wgpu::TextureFormat,
+    size: u32,
+    packing: Packing,
+    // Keyed by SVG, size in pixels and `currentColor`.
+    rasters: HashMap<(u64, u32, u32, [u8; 4]), Raster>,
+    // Keyed by a hash of the SVG source and size in pixels.
+    masks: HashMap<(u64, u32, u32), Raster>,
+}
+
+// Where the images go in the texture, apart from the texture itself.
+struct Packing {
+    // Size the texture has to be at the next `prepare`, rebuilt from scratch when it changed.
+    size: u32,
+    max_size: u32,
+    entries: HashMap<u64, Entry>,
+    shelves: Vec<Shelf>,
+    pending: Vec<u64>,
+    // Set when space ran out while images nobody holds anymore still take some, so the next
+    // `repack` frees it.
+    stale: bool,
+    // Set when an image was left out for lack of space that `repack` may free.
+    missed: bool,
+}
+
+impl ImageAtlas {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let format = match format.is_srgb() {
+            true => wgpu::TextureFormat::Rgba8UnormSrgb,
+            false => wgpu::TextureFormat::Rgba8Unorm,
+        };
+        let max_size = device.limits().max_texture_dimension_2d;
+        let packing = Packing::new(INITIAL_SIZE.min(max_size), max_size);
+        let (texture, view) = create_texture(device, format, packing.size);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Image atlas sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+            format,
+            size: packing.size,
+            packing,
+            rasters: HashMap::new(),
+            masks: HashMap::new(),
+        }
+    }
+
+    /// Returns the texel rectangle (x, y, width, height) holding `image`, reserving one if it
+    /// isn't in the atlas yet. `None` when the image can't fit even in the largest texture.
+    pub fn allocate(&mut self, image: &Image) -> Option<[f32; 4]> {
+        self.packing
+            .allocate(image)
+            .map(|rect| rect.map(|v| v as f32))
+    }
+
+    /// Like `allocate` for `svg` rasterized at `size` pixels with `currentColor` set to
+    /// `color`. A raster is dropped once a frame goes by without drawing it, so a node that
+    /// changes size or color renders the document again instead of scaling stale pixels.
+    pub fn allocate_svg(&mut self, svg: &Svg, size: [f32; 2], color: [u8; 4]) -> Option<[f32; 4]> {
+        let max_size = self.packing.max_size;
+        let [width, height] = size.map(|size| (size.ceil() as u32).min(max_size));
+
+        let raster = match self.rasters.entry((svg.id(), width, height, color)) {
+            hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            hash_map::Entry::Vacant(entry) => entry.insert(Raster {
+                image: svg.rasterize(width, height, color)?,
+                used: false,
+            }),
+        };
+        raster.used = true;
+
+        let image = raster.image.clone();
+        self.allocate(&image)
+    }
+
+    /// Forgets images nobody holds anymore and repacks the rest, if space ran out while they
+    /// took some. Runs before collecting a frame, as it moves images.
+    pub fn repack(&mut self) {
+        self.packing.repack();
+    }
+
+    /// Whether an image was left out of the last frame for lack of space that `repack` may
+    /// free, so it is worth collecting another.
+    pub fn missed(&self) -> bool {
+        self.packing.missed
+    }
+
+    /// Like `allocate_svg` for the SVG document `source`, drawn in white, for masks that are
+    /// generated every frame rather than loaded once.
+    pub fn allocate_mask(&mut self, source: &str, size: [f32; 2]) -> Option<[f32; 4]> {
+        let max_size = self.packing.max_size;
+        let [width, height] = size.map(|size| (size.ceil() as u32).min(max_size));
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+
+        let raster = match self.masks.entry((hasher.finish(), width, height)) {
+            hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            hash_map::Entry::Vacant(entry) => {
+                let svg = Svg::from_bytes(source.as_bytes()).ok()?;
+                entry.insert(Raster {
+                    image: svg.rasterize(width, height, [255; 4])?,
+                    used: false,
+                })
+            }
+        };
+        raster.used = true;
+
+        let image = raster.image.clone();
+        self.allocate(&image)
+    }
+
+    /// Grows the texture if needed and uploads the images allocated since the last call.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.rasters
+            .retain(|_, raster| std::mem::take(&mut raster.used));
+        self.masks
+            .retain(|_, raster| std::mem::take(&mut raster.used));
+
+        let packing = &mut self.packing;
+        if packing.size != self.size {
+            let (texture, view) = create_texture(device, self.format, packing.size);
+            self.texture = texture;
+            self.view = view;
+            self.size = packing.size;
+            packing.pending = packing.entries.keys().copied().collect();
+        }
+
+        std::mem::take(&mut packing.pending)
+            .into_iter()
+            .for_each(|id| {
+                let Some(entry) = packing.entries.get(&id) else {
+                    return;
+                };
+                let Some(pixels) = entry.pixels.upgrade() else {
+                    return;
+                };
+                let [x, y, width, height] = entry.rect;
+
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &self.texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d { x, y, z: 0 },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    &pixels,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(width * 4),
+                        rows_per_image: Some(height),
+                    },
+                    wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            });
+    }
+}
+
+impl Packing {
+    fn new(size: u32, max_size: u32) -> Self {
+        Self {
+            size,
+            max_size,
+            entries: HashMap::new(),
+            shelves: Vec::new(),
+            pending: Vec::new(),
+            stale: false,
+            missed: false,
+        }
+    }
+
+    fn allocate(&mut self, image: &Image) -> Option<[u32; 4]> {
+        if let Some(entry) = self.entries.get(&image.id()) {
+            return Some(entry.rect);
+        }
+
+        let (width, height) = (image.width(), image.height());
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        // Repacking would move the images already handed out this frame, so the texture
+        // only grows until the next `repack`.
+        let rect = loop {
+            if let Some(rect) = self.pack(width, height) {
+                break rect;
+            }
+            self.stale |= self
+                .entries
+                .values()
+                .any(|entry| entry.pixels.strong_count() == 0);
+            if self.size >= self.max_size {
+                self.missed |= self.stale;
+                return None;
+            }
+            self.size = (self.size * 2).min(self.max_size);
+        };
+
+        self.entries.insert(
+            image.id(),
+            Entry {
+                rect,
+                pixels: Rc::downgrade(image.pixels()),
+            },
+        );
+        self.pending.push(image.id());
+
+        Some(rect)
+    }
+
+    // Shelf packing: images go left to right on the first shelf tall enough, new shelves open
+    // below the last one.
+    fn pack(&mut self, width: u32, height: u32) -> Option<[u32; 4]> {
+        let size = self.size;
+        let (padded_width, padded_height) = (width + PADDING, height + PADDING);
+
+        if let Some(shelf) = self
+            .shelves
+            .iter_mut()
+            .find(|shelf| shelf.height >= padded_height && shelf.width + padded_width <= size)
+        {
+            let rect = [shelf.width, shelf.y, width, height];
+            shelf.width += padded_width;
+            return Some(rect);
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        if y + padded_height > size || padded_width > size {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            y,
+            height: padded_height,
+            width: padded_width,
+        });
+        Some([0, y, width, height])
+    }
+
+    fn repack(&mut self) {
+        if std::mem::take(&mut self.stale) {
+            self.missed = false;
+            self.evict();
+        }
+    }
+
+    fn evict(&mut self) {
+        let mut live = std::mem::take(&mut self.entries)
+            .into_iter()
+            .filter(|(_, entry)| entry.pixels.strong_count() > 0)
+            .collect::<Vec<_>>();
+        live.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.rect[3]));
+
+        self.shelves.clear();
+        self.pending.clear();
+        // Repacking moves images, so everything is uploaded again.
+        for (id, mut entry) in live {
+            let [_, _, width, height] = entry.rect;
+            let rect = loop {
+                if let Some(rect) = self.pack(width, height) {
+                    break Some(rect);
+                }
+                if self.size >= self.max_size {
+                    break None;
+                }
+                self.size = (self.size * 2).min(self.max_size);
+            };
+
+            if let Some(rect) = rect {
+                entry.rect = rect;
+                self.entries.insert(id, entry);
+                self.pending.push(id);
+            }
+        }
+    }
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    size: u32,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Image atlas"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    (texture, view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32) -> Image {
+        Image::from_rgba(RgbaImage::new(width, height))
+    }
+
+    #[test]
+    fn test_pack_shelves() {
+        let mut packing = Packing::new(64, 64);
+        let (a, b, c) = (image(20, 10), image(20, 8), image(20, 12));
+
+        assert_eq!(packing.allocate(&a), Some([0, 0, 20, 10]));
+        // Shorter images share the shelf, padded apart, taller ones open a new one.
+        assert_eq!(packing.allocate(&b), Some([21, 0, 20, 8]));
+        assert_eq!(packing.allocate(&c), Some([0, 11, 20, 12]));
+        // Images already in the atlas keep their place.
+        assert_eq!(packing.allocate(&a), Some([0, 0, 20, 10]));
+        assert_eq!(packing.pending.len(), 3);
+        assert_eq!(packing.allocate(&image(0, 10)), None);
+    }
+
+    #[test]
+    fn test_grow_then_repack() {
+        let mut packing = Packing::new(32, 128);
+        let kept = image(30, 30);
+        assert_eq!(packing.allocate(&kept), Some([0, 0, 30, 30]));
+        let dropped = image(30, 30);
+        assert_eq!(packing.allocate(&dropped), Some([31, 0, 30, 30]));
+        drop(dropped);
+
+        // Mid frame the texture grows instead of moving what was handed out, even though a
+        // repack would free the space of the dropped image.
+        let grown = image(60, 60);
+        assert_eq!(packing.allocate(&grown), Some([0, 31, 60, 60]));
+        assert_eq!(packing.size, 128);
+        assert!(packing.stale);
+
+        // Once at the largest size, an image that doesn't fit is missed until the repack.
+        let missed = image(120, 60);
+        assert_eq!(packing.allocate(&missed), None);
+        assert!(packing.missed);
+        packing.repack();
+        assert!(!packing.stale && !packing.missed);
+        assert_eq!(packing.allocate(&grown), Some([0, 0, 60, 60]));
+        assert_eq!(packing.allocate(&kept), Some([61, 0, 30, 30]));
+        assert_eq!(packing.allocate(&missed), Some([0, 61, 120, 60]));
+        assert_eq!(packing.entries.len(), 3);
+    }
+
+    #[test]
+    fn test_too_large() {
+        let mut packing = Packing::new(32, 64);
+        assert_eq!(packing.allocate(&image(64, 10)), None);
+        // Nothing was dropped, so a repack wouldn't help.
+        assert!(!packing.missed);
+        assert_eq!(packing.size, 64);
+    }
+}
@@ -0,0 +1,265 @@
+use super::{DrawItem, DrawList};
+
+// Layer sizes go up in steps of this many texels, so textures can be reused while what is
+// drawn into them moves and grows a little.
+const SIZE_STEP: f32 = 64.0;
+
+/// Offscreen textures, one for each layer of a frame, sized to what is drawn into it. Layers
+/// are drawn with the same pipeline as the screen, blending into a transparent texture leaves
+/// premultiplied colors in it.
+pub struct LayerTargets {
+    format: wgpu::TextureFormat,
+    targets: Vec<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl LayerTargets {
+    pub fn new(format: wgpu::TextureFormat) -> Self {
+        Self {
+            format,
+            targets: Vec::new(),
+        }
+    }
+
+    /// Makes sure there is a texture of each of `sizes` texels, in order, reusing the textures
+    /// of earlier frames of the same size and dropping the others.
+    pub fn prepare(&mut self, device: &wgpu::Device, sizes: impl Iterator<Item = [u32; 2]>) {
+        let mut unused = std::mem::take(&mut self.targets);
+        self.targets = sizes
+            .map(|size| {
+                let same = unused
+                    .iter()
+                    .position(|(texture, _)| [texture.width(), texture.height()] == size);
+                match same {
+                    Some(index) => unused.swap_remove(index),
+                    None => create_texture(device, self.format, size),
+                }
+            })
+            .collect();
+    }
+
+    /// The views, as prepared.
+    pub fn views(&self) -> impl Iterator<Item = &wgpu::TextureView> {
+        self.targets.iter().map(|(_, view)| view)
+    }
+}
+
+/// Gives every layer of `draw_list` the box (x, y, width, height) it covers in whole viewport
+/// pixels, and lines the instances reading layers up with them. The first layer is the
+/// viewport, the others are bounded by what is drawn into them, within it.
+pub(crate) fn place(
+    draw_list: &mut DrawList,
+    viewport: [f32; 2],
+    scroll: [f32; 2],
+) -> Vec<[f32; 4]> {
+    let screen = [0.0, 0.0, viewport[0], viewport[1]];
+    let mut bounds = vec![screen; draw_list.layers.len()];
+
+    // Layers are drawn after the layers they read.
+    for &layer in &draw_list.order {
+        let instance = |index: u32| &draw_list.instance_data[index as usize];
+        let quad = |index: u32| instance(index).bounds(scroll).unwrap_or(screen);
+
+        let items = draw_list.layers[layer]
+            .items
+            .iter()
+            .map(|item| match *item {
+                DrawItem::Instances(ref range) => range.clone().map(quad).fold(EMPTY, union),
+                DrawItem::Layer {
+                    layer,
+                    instance: index,
+                } => {
+                    let reach = instance(index).reach(bounds[layer]).unwrap_or(screen);
+                    intersection(quad(index), reach)
+                }
+                DrawItem::Masked {
+                    layer,
+                    mask,
+                    instance: index,
+                } => intersection(quad(index), intersection(bounds[layer], bounds[mask])),
+            });
+        let text = draw_list.layers[layer]
+            .text_data
+            .iter()
+            .map(|text| text.bounds());
+        let drawn = match items.chain(text).fold(EMPTY, union) {
+            EMPTY => [0.0; 4],
+            drawn => intersection(drawn, screen),
+        };
+        bounds[layer] = snap(drawn, viewport);
+    }
+
+    let origin = |layer: usize| [bounds[layer][0], bounds[layer][1]];
+    for (target, layer) in draw_list.layers.iter().enumerate() {
+        for item in &layer.items {
+            let (source, mask, index) = match *item {
+                DrawItem::Instances(_) => continue,
+                DrawItem::Layer { layer, instance } => (layer, layer, instance),
+                DrawItem::Masked {
+                    layer,
+                    mask,
+                    instance,
+                } => (layer, mask, instance),
+            };
+            draw_list.instance_data[index as usize].place(
+                origin(target),
+                origin(source),
+                origin(mask),
+            );
+        }
+    }
+
+    bounds
+}
+
+// Nothing, as the start of a union.
+const EMPTY: [f32; 4] = [
+    f32::INFINITY,
+    f32::INFINITY,
+    f32::NEG_INFINITY,
+    f32::NEG_INFINITY,
+];
+
+fn union(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    if a == EMPTY || b == EMPTY {
+        return if a == EMPTY { b } else { a };
+    }
+    let [left, top] = [a[0].min(b[0]), a[1].min(b[1])];
+    let [right, bottom] = [
+        (a[0] + a[2]).max(b[0] + b[2]),
+        (a[1] + a[3]).max(b[1] + b[3]),
+    ];
+    [left, top, right - left, bottom - top]
+}
+
+fn intersection(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    let [left, top] = [a[0].max(b[0]), a[1].max(b[1])];
+    let [right, bottom] = [
+        (a[0] + a[2]).min(b[0] + b[2]),
+        (a[1] + a[3]).min(b[1] + b[3]),
+    ];
+    [left, top, (right - left).max(0.0), (bottom - top).max(0.0)]
+}
+
+// Out to whole pixels, with the size rounded up to a step but no larger than the viewport.
+fn snap([x, y, width, height]: [f32; 4], viewport: [f32; 2]) -> [f32; 4] {
+    let [left, top] = [x.floor(), y.floor()];
+    let size = |start: f32, end: f32, viewport: f32| {
+        let size = (end.ceil() - start).max(1.0);
+        ((size / SIZE_STEP).ceil() * SIZE_STEP).min(viewport.ceil().max(size))
+    };
+    [
+        left,
+        top,
+        size(left, x + width, viewport[0]),
+        size(top, y + height, viewport[1]),
+    ]
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    size: [u32; 2],
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Layer"),
+        size: wgpu::Extent3d {
+            width: size[0].max(1),
+            height: size[1].max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    (texture, view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        filter::ColorMatrix,
+        math::{Mat4, Matrix},
+        rectangle::InstanceData,
+        tree::tests::quad,
+    };
+
+    const VIEWPORT: [f32; 2] = [200.0, 100.0];
+    // What passes cover.
+    const SCREEN: [f32; 4] = [0.0, 0.0, 200.0, 100.0];
+
+    // A closed layer drawing `rect`.
+    fn layer(draw_list: &mut DrawList, rect: [f32; 4]) -> usize {
+        let parent = draw_list.begin_layer();
+        draw_list.extend_instances([quad(rect)]);
+        draw_list.end_layer(parent).unwrap()
+    }
+
+    #[test]
+    fn test_layer_bounds() {
+        let mut draw_list = DrawList::default();
+        let inside = layer(&mut draw_list, [10.5, 20.0, 30.0, 10.0]);
+        let outside = layer(&mut draw_list, [-50.0, 90.0, 100.0, 100.0]);
+        let wide = layer(&mut draw_list, [2.0, 0.0, 195.0, 10.0]);
+
+        let bounds = place(&mut draw_list, VIEWPORT, [0.0, 0.0]);
+        assert_eq!(bounds[0], SCREEN);
+        // Out to whole pixels, sizes in steps, within the viewport.
+        assert_eq!(bounds[inside], [10.0, 20.0, 64.0, 64.0]);
+        assert_eq!(bounds[outside], [0.0, 90.0, 64.0, 64.0]);
+        assert_eq!(bounds[wide], [2.0, 0.0, 200.0, 64.0]);
+
+        // Transformed instances count with the box around their corners.
+        let mut draw_list = DrawList::default();
+        let parent = draw_list.begin_layer();
+        let turn = Mat4::translate(50.0, 50.0, 0.0).multiply(&Mat4::rotate([0.0, 0.0, 1.0], 90.0));
+        draw_list.extend_instances([quad([0.0, 0.0, 20.0, 10.0]).with_transform(&turn)]);
+        let rotated = draw_list.end_layer(parent).unwrap();
+        let bounds = place(&mut draw_list, VIEWPORT, [0.0, 0.0]);
+        assert_eq!(bounds[rotated], [40.0, 50.0, 64.0, 64.0]);
+
+        // Scrolling moves what is drawn up the viewport.
+        let mut draw_list = DrawList::default();
+        let scrolled = layer(&mut draw_list, [10.0, 120.0, 30.0, 10.0]);
+        let bounds = place(&mut draw_list, VIEWPORT, [0.0, 100.0]);
+        assert_eq!(bounds[scrolled], [10.0, 20.0, 64.0, 64.0]);
+    }
+
+    #[test]
+    fn test_pass_bounds() {
+        let mut draw_list = DrawList::default();
+        let source = layer(&mut draw_list, [100.0, 40.0, 20.0, 10.0]);
+
+        // Blurs spread the layer's texture by three standard deviations each way, shadows move
+        // it.
+        let blur = InstanceData::layer_blur(SCREEN, 2.0, false, false);
+        let blurred = draw_list.pass(&[(source, blur)]);
+        // Wider blurs are cut down to what blur passes reach.
+        let wide = InstanceData::layer_blur(SCREEN, 100.0, false, false);
+        let widened = draw_list.pass(&[(source, wide)]);
+        let shadow = InstanceData::layer_shadow(SCREEN, [-30.0, 5.0], [0.0; 4]);
+        let shadowed = draw_list.pass(&[(source, shadow)]);
+        // Composites are clipped to their quad.
+        let clip = InstanceData::layer([110.0, 0.0, 5.0, 100.0], &ColorMatrix::IDENTITY);
+        let clipped = draw_list.pass(&[(source, clip)]);
+        // Placed layers go wherever their quad is transformed to.
+        let place_at = InstanceData::placed_layer([30.0, 20.0, 20.0, 10.0]);
+        let placed = draw_list.pass(&[(source, place_at)]);
+        // Masked passes only cover where the layer and its mask overlap.
+        let masked = draw_list.masked_pass(shadowed, blurred, InstanceData::masked_pass(SCREEN));
+
+        let bounds = place(&mut draw_list, VIEWPORT, [0.0, 0.0]);
+        assert_eq!(bounds[source], [100.0, 40.0, 64.0, 64.0]);
+        assert_eq!(bounds[blurred], [94.0, 40.0, 128.0, 64.0]);
+        assert_eq!(bounds[widened], [4.0, 40.0, 200.0, 64.0]);
+        assert_eq!(bounds[shadowed], [70.0, 45.0, 64.0, 64.0]);
+        assert_eq!(bounds[clipped], [110.0, 40.0, 64.0, 64.0]);
+        assert_eq!(bounds[placed], [30.0, 20.0, 64.0, 64.0]);
+        assert_eq!(bounds[masked], [94.0, 45.0, 64.0, 64.0]);
+    }
+}
@@ -1,12 +1,32 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-use crate::rectangle::{BoxSizing, Display, InstanceData, Position, Rectangle};
+use crate::{
+    background::{BackgroundBox, BackgroundImage, BackgroundRepeat, BackgroundSize},
+    clip::ClipPath,
+    filter::{self, Filter},
+    fonts::{self, FontVariant, Fonts},
+    images::{Image, ImageCache, ObjectFit},
+    mask::MaskMode,
+    math::{Mat4, Matrix},
+    parser,
+    rectangle::{BorderStyle, BoxShadow, BoxSizing, Display, InstanceData, Position, Rectangle},
+    signal::{Binding, Signal},
+    svg::Svg,
+    text::{Hyphens, OverflowWrap, TextAlign, TextSpan, WhiteSpace, WordBreak, SOFT_HYPHEN},
+    transform::{Accumulated, BackfaceVisibility, Transform, TransformStyle},
+};
 use calc_units::{Context, Units};
-use glyphon::{Attrs, Color, FamilyOwned, FontSystem};
+use glyphon::{Attrs, AttrsOwned, Buffer, Color, FamilyOwned, Metrics, Stretch, Weight};
+use std::collections::{HashMap, VecDeque};
 
 use super::{
-    text::{Text, TextData},
-    State,
+    atlas::ImageAtlas,
+    text::{Span, Text, TextData},
+    DrawList, State,
 };
 
 #[derive(Clone, Copy)]
@@ -23,11 +43,67 @@ pub struct ParentState {
     pub font_size: f32,
 }
 
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(0);
+
+/// Stable identifier of a node, used to address it after the tree is built.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Handle(u64);
+
+impl Default for Handle {
+    fn default() -> Self {
+        Self(NEXT_HANDLE.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Tracks what needs recomputing on the next layout. New nodes start out dirty.
+pub struct Dirty {
+    /// The node's own style, text, image or children changed.
+    pub node: bool,
+    /// Some descendant is dirty, or children were added, moved or removed, so the node lays
+    /// them out again.
+    pub descendants: bool,
+}
+
+impl Default for Dirty {
+    fn default() -> Self {
+        Self {
+            node: true,
+            descendants: true,
+        }
+    }
+}
+
+// Inputs and result of the last `compute_layout`, reused while the subtree is clean.
+struct LayoutCache {
+    size: (f32, f32, f32),
+    viewport: (f32, f32),
+    auto: (f32, f32),
+}
+
 #[derive(Default)]
 pub struct Node {
     pub children: Vec<Node>,
     pub data: Rectangle,
     pub text: Option<Text>,
+    pub content: Option<String>,
+    /// Styled runs of text following `content` in the node's paragraph.
+    pub spans: Vec<TextSpan>,
+    /// Makes the node a replaced element showing this image, sized by it where `auto`.
+    pub image: Option<Image>,
+    /// Like `image`, for an SVG rasterized at the size it is drawn.
+    pub svg: Option<Svg>,
+    pub id: Option<String>,
+    pub class: Vec<String>,
+    /// Identifies the node among its siblings when reconciling, instead of its position.
+    pub key: Option<String>,
+    pub handle: Handle,
+    pub dirty: Dirty,
+    // Parent offset already added to `x` and `y`, so offsetting can be repeated.
+    origin: (f32, f32),
+    // The node's text is shaped as part of an ancestor's paragraph, so it takes no space.
+    absorbed: bool,
+    layout_cache: Option<LayoutCache>,
+    bindings: Vec<Binding>,
 }
 
 impl Deref for Node {
@@ -57,6 +133,43 @@ pub fn collect_children(children: &mut [Node]) -> Vec<&mut Node> {
         .collect()
 }
 
+// Flags the longest increasing subsequence of `indices`, preferring earlier indices among those
+// of the same length.
+fn in_order(indices: impl Iterator<Item = usize>) -> Vec<bool> {
+    let indices = indices.collect::<Vec<_>>();
+
+    // Length of the longest increasing subsequence starting at each index, found from the back.
+    // `firsts[l]` is the greatest first index of one of length `l + 1` so far.
+    let mut firsts: Vec<usize> = Vec::new();
+    let lengths = indices
+        .iter()
+        .rev()
+        .map(|&index| {
+            let l = firsts.partition_point(|&first| first > index);
+            match firsts.get_mut(l) {
+                Some(first) => *first = index,
+                None => firsts.push(index),
+            }
+            l + 1
+        })
+        .collect::<Vec<_>>();
+
+    let mut remaining = firsts.len();
+    let mut last = None;
+    indices
+        .iter()
+        .zip(lengths.into_iter().rev())
+        .map(|(&index, length)| {
+            let kept = length == remaining && last.is_none_or(|last| index > last);
+            if kept {
+                remaining -= 1;
+                last = Some(index);
+            }
+            kept
+        })
+        .collect()
+}
+
 impl Node {
     pub fn get_state(&self) -> ParentState {
         ParentState {
@@ -68,7 +181,35 @@ impl Node {
         }
     }
 
-    pub fn compute_static_properties(&mut self, parent_state: &ParentState, state: &State) {
+    pub fn compute_static_properties(
+        &mut self,
+        parent_state: &ParentState,
+        state: &State,
+        fonts: &mut Fonts,
+    ) {
+        self.update_static_properties(parent_state, state, true);
+        self.update_text(state, fonts, self.absorbed, true);
+    }
+
+    // Like `compute_static_properties`, but skips clean subtrees unless `force` is set.
+    fn update_static_properties(&mut self, parent_state: &ParentState, state: &State, force: bool) {
+        let force = force || self.dirty.node;
+        if force {
+            self.compute_own_static_properties(parent_state, state);
+        }
+
+        if !force && !self.dirty.descendants {
+            return;
+        }
+
+        let parent_state = self.get_state();
+
+        self.children
+            .iter_mut()
+            .for_each(|child| child.update_static_properties(&parent_state, state, force));
+    }
+
+    fn compute_own_static_properties(&mut self, parent_state: &ParentState, state: &State) {
         let box_context = Context {
             root_font_size: state.root_font_size,
             reference_size: parent_state.width,
@@ -78,15 +219,7 @@ impl Node {
             auto: 0.0,
         };
 
-        self.outline.width = self.style.outline_width.to_px(&box_context);
-        self.outline.offset = self.style.outline_offset.to_px(&box_context);
-
-        (0..4).for_each(|i| {
-            self.padding[i] = self.style.padding[i].to_px(&box_context);
-            self.border.size[i] = self.style.border_size[i].to_px(&box_context);
-            self.border.radius[i] = self.style.border_radius[i].to_px(&box_context);
-            self.margin[i] = self.style.margin[i].to_px(&box_context);
-        });
+        self.resolve_box(parent_state, state);
 
         self.font_size = self.style.font_size.to_px(&Context {
             reference_size: parent_state.font_size,
@@ -98,27 +231,275 @@ impl Node {
             ..box_context
         });
 
-        let font_size = self.font_size;
-        let line_height = self.line_height;
-        if let Some(text) = &mut self.text {
-            text.buffer.set_metrics(
-                &mut text.font_system,
-                glyphon::Metrics::new(font_size, line_height),
-            );
-        }
+        // Only block containers indent their first line.
+        self.text_indent = match self.style.display {
+            Display::Inline => 0.0,
+            _ => self.style.text_indent.to_px(&box_context).max(0.0),
+        };
+    }
 
-        let parent_state = self.get_state();
+    // Shapes the text of the dirty parts of the subtree, once font sizes are known. Inline
+    // children that only hold text join the paragraph of their parent instead of shaping
+    // their own, so it wraps as a whole.
+    fn update_text(&mut self, state: &State, fonts: &mut Fonts, absorbed: bool, force: bool) {
+        let force = force || self.dirty.node || self.absorbed != absorbed;
+        if !force && !self.dirty.descendants {
+            return;
+        }
+        self.absorbed = absorbed;
+
+        let paragraph =
+            !absorbed && !self.children.is_empty() && self.children.iter().all(Node::is_text_span);
+        if absorbed {
+            self.text = None;
+            (self.width, self.height) = (0.0, 0.0);
+        } else {
+            // Spaces at the start of a block's text collapse away, while those of inline text
+            // may follow text before it on the line.
+            let mut space = self.style.display != Display::Inline;
+            let mut spans = Vec::new();
+            self.collect_spans(state, paragraph, &mut space, &mut spans);
+            self.shape_text(fonts, spans);
+        }
 
         self.children
             .iter_mut()
-            .for_each(|child| child.compute_static_properties(&parent_state, state));
+            .for_each(|child| child.update_text(state, fonts, absorbed || paragraph, force));
+    }
+
+    // Inline text without a box of its own to draw, which can join the paragraph of its
+    // parent.
+    fn is_text_span(&self) -> bool {
+        self.style.display == Display::Inline
+            && self.style.position == Position::Static
+            && self.image.is_none()
+            && self.svg.is_none()
+            && self.background_color[3] == 0.0
+            && self.background.image.is_empty()
+            && self
+                .border
+                .size
+                .iter()
+                .chain(&self.padding)
+                .chain(&self.margin)
+                .all(|size| *size == 0.0)
+            && self.outline.width == 0.0
+            && self.box_shadow.is_empty()
+            && self.opacity == 1.0
+            && self.filter.is_empty()
+            && self.backdrop_filter.is_empty()
+            && self.clip_path.is_none()
+            && self.mask.image.is_empty()
+            && self.transform.is_empty()
+            && self.children.iter().all(Node::is_text_span)
     }
 
-    pub fn compute_layout(&mut self, state: &State) -> (f32, f32) {
+    // Appends the styled runs of the node's text, and of its descendants with `children`, with
+    // their white space collapsed. `space` tells whether the text before ends in a collapsible
+    // space.
+    fn collect_spans(
+        &self,
+        state: &State,
+        children: bool,
+        space: &mut bool,
+        spans: &mut Vec<Span>,
+    ) {
+        let context = Context {
+            root_font_size: state.root_font_size,
+            reference_size: self.font_size,
+            viewport: state.viewport,
+            dpi: state.dpi,
+            parent_font_size: self.font_size,
+            auto: 0.0,
+        };
+        let attrs = self
+            .style
+            .font_attrs()
+            .color(self.style.font_color)
+            .metrics(Metrics::new(self.font_size, self.line_height));
+        let variant = self.style.font_variant;
+        let mut flow = |text: &str| {
+            let text = self.style.white_space.collapse(text, space);
+            self.style.word_break.join(self.style.hyphens.apply(text))
+        };
+
+        if let Some(content) = &self.content {
+            spans.push(Span {
+                text: flow(content),
+                family: self.style.font_family.clone(),
+                attrs: AttrsOwned::new(attrs),
+                variant,
+            });
+        }
+
+        spans.extend(self.spans.iter().map(|span| {
+            let font_size = span
+                .font_size
+                .as_ref()
+                .map_or(self.font_size, |size| size.to_px(&context));
+            let line_height = self.style.line_height.to_px(&Context {
+                reference_size: font_size,
+                ..context
+            });
+
+            let mut attrs = attrs.metrics(Metrics::new(font_size, line_height));
+            if let Some(color) = span.color {
+                attrs = attrs.color(color);
+            }
+            if let Some(weight) = span.font_weight {
+                attrs = attrs.weight(weight);
+            }
+            if let Some(style) = span.font_style {
+                attrs = attrs.style(style);
+            }
+
+            Span {
+                text: flow(&span.text),
+                family: span
+                    .font_family
+                    .clone()
+                    .unwrap_or_else(|| self.style.font_family.clone()),
+                attrs: AttrsOwned::new(attrs),
+                variant,
+            }
+        }));
+
+        if children {
+            self.children
+                .iter()
+                .for_each(|child| child.collect_spans(state, true, space, spans));
+        }
+    }
+
+    // Shapes `spans` into the text buffer, reusing the previous shaping when neither they, the
+    // indent nor the fonts changed.
+    fn shape_text(&mut self, fonts: &mut Fonts, spans: Vec<Span>) {
+        if spans.iter().all(|span| span.text.is_empty()) {
+            self.text = None;
+            return;
+        }
+
+        let metrics = Metrics::new(self.font_size, self.line_height);
+        let text = self.text.get_or_insert_with(|| Text {
+            buffer: Rc::new(Buffer::new(&mut fonts.font_system, metrics)),
+            spans: Vec::new(),
+            indent: 0.0,
+            generation: fonts.generation,
+            paragraphs: Vec::new(),
+            wrapped: false,
+        });
+        let buffer = Rc::make_mut(&mut text.buffer);
+        buffer.set_metrics(&mut fonts.font_system, metrics);
+
+        if text.spans != spans
+            || text.indent != self.data.text_indent
+            || text.generation != fonts.generation
+        {
+            let mut shaped = String::new();
+            let mut runs = Vec::new();
+            for span in &spans {
+                let resolved = fonts.resolve(&span.text, span.attrs.as_attrs(), &span.family);
+                let metrics = span.attrs.metrics_opt.map_or(metrics, Metrics::from);
+                let (text, span_runs) =
+                    fonts::small_caps(&span.text, resolved, span.variant, metrics);
+
+                let offset = shaped.len();
+                shaped.push_str(&text);
+                runs.extend(
+                    span_runs
+                        .into_iter()
+                        .map(|(range, attrs)| (range.start + offset..range.end + offset, attrs)),
+                );
+            }
+            if self.data.text_indent > 0.0 {
+                (shaped, runs) = fonts.indent(&shaped, runs, self.data.text_indent, metrics);
+            }
+
+            buffer.set_rich_text(
+                &mut fonts.font_system,
+                runs.iter()
+                    .map(|(range, attrs)| (&shaped[range.clone()], attrs.as_attrs())),
+                Attrs::new(),
+                glyphon::Shaping::Advanced,
+            );
+            text.spans = spans;
+            text.indent = self.data.text_indent;
+            text.generation = fonts.generation;
+            text.paragraphs = match shaped.contains(SOFT_HYPHEN) {
+                true => buffer
+                    .lines
+                    .iter()
+                    .map(|line| (line.text().to_string(), line.attrs_list().clone()))
+                    .collect(),
+                false => Vec::new(),
+            };
+            text.wrapped = false;
+        }
+        let wrap = self.data.style.text_wrap();
+        if buffer.wrap() != wrap {
+            buffer.set_wrap(&mut fonts.font_system, wrap);
+            text.wrapped = false;
+        }
+
+        // Aligning only lays the lines out again.
+        let align = self.data.style.text_align.align();
+        let mut aligned = false;
+        for line in &mut buffer.lines {
+            aligned |= line.set_align(align);
+        }
+        if aligned {
+            buffer.shape_until_scroll(&mut fonts.font_system, false);
+        }
+    }
+
+    // Resolves the padding, border, radius, margin and outline of the node, which are
+    // percentages of the width of its containing block. Laying out the parent resolves them
+    // again once that width is known, as it may have changed since the last layout.
+    fn resolve_box(&mut self, parent_state: &ParentState, state: &State) {
+        let context = Context {
+            root_font_size: state.root_font_size,
+            reference_size: parent_state.width,
+            viewport: state.viewport,
+            dpi: state.dpi,
+            parent_font_size: parent_state.font_size,
+            auto: 0.0,
+        };
+
+        self.outline.width = self.style.outline_width.to_px(&context);
+        self.outline.offset = self.style.outline_offset.to_px(&context);
+
+        (0..4).for_each(|i| {
+            self.padding[i] = self.style.padding[i].to_px(&context);
+            self.border.size[i] = match self.style.border_style[i] {
+                BorderStyle::None | BorderStyle::Hidden => 0.0,
+                _ => self.style.border_size[i].to_px(&context),
+            };
+            self.border.radius[i] = self.style.border_radius[i].to_px(&context);
+            self.margin[i] = self.style.margin[i].to_px(&context);
+        });
+    }
+
+    pub fn compute_layout(&mut self, state: &State, fonts: &mut Fonts) -> (f32, f32) {
+        let size = (self.width, self.height, self.font_size);
+        if !self.dirty.node && !self.dirty.descendants {
+            if let Some(cache) = &self.layout_cache {
+                if cache.size == size && cache.viewport == state.viewport {
+                    return cache.auto;
+                }
+            }
+        }
+
         let parent_state = self.get_state();
 
+        // Rows of inline boxes share out the free space of the content box, which they fill
+        // when it shrinks to fit them.
+        let offset = self.style.text_align.offset();
+        let shrinks = self.style.width.is_auto() && self.style.display != Display::Block;
+        let width = self.width;
+
+        // A first row of inline boxes starts indented.
         let mut current_pos = Dimensions {
-            width: 0.0,
+            width: self.text_indent,
             height: 0.0,
         };
         let mut total_size = Dimensions {
@@ -126,15 +507,63 @@ impl Node {
             height: 0.0,
         };
 
-        collect_children(&mut self.children)
-            .iter_mut()
-            .for_each(|child| {
-                child.resolve_sizes(&parent_state, state, &mut current_pos, &mut total_size);
-                child.update_position(&parent_state, state, current_pos);
-                child.apply_auto(&parent_state, state, current_pos);
-            });
+        let mut children = collect_children(&mut self.children)
+            .into_iter()
+            .filter(|child| !child.absorbed)
+            .collect::<Vec<_>>();
+        children.iter_mut().for_each(|child| {
+            child.resolve_box(&parent_state, state);
+            child.resolve_sizes(
+                &parent_state,
+                state,
+                fonts,
+                &mut current_pos,
+                &mut total_size,
+            );
+            child.update_position(&parent_state, state, current_pos);
+            child.apply_auto(&parent_state, state, current_pos);
+        });
+
+        if offset > 0.0 {
+            let width = if shrinks { total_size.width } else { width };
+            let mut rows: Vec<Vec<&mut Node>> = Vec::new();
+            for child in children.into_iter().filter(|child| child.in_inline_row()) {
+                match rows.last_mut() {
+                    // Wrapping starts the next row at the left again.
+                    Some(row) if row.last().is_some_and(|last| last.x <= child.x) => {
+                        row.push(child)
+                    }
+                    _ => rows.push(vec![child]),
+                }
+            }
+
+            for row in rows {
+                let right = row
+                    .iter()
+                    .map(|child| child.x + child.get_extents(state).width)
+                    .fold(0.0, f32::max);
+                let shift = (width - right).max(0.0) * offset;
+                row.into_iter().for_each(|child| child.x += shift);
+            }
+        }
 
-        (total_size.width, total_size.height)
+        let auto = (total_size.width, total_size.height);
+        self.layout_cache = Some(LayoutCache {
+            size,
+            viewport: state.viewport,
+            auto,
+        });
+
+        auto
+    }
+
+    // Whether the node is laid out in rows of inline boxes.
+    fn in_inline_row(&self) -> bool {
+        matches!(self.style.display, Display::Inline | Display::InlineBlock)
+            && matches!(
+                self.style.position,
+                Position::Static | Position::Sticky | Position::Relative
+            )
     }
 
     fn update_position(
@@ -208,7 +637,9 @@ impl Node {
 
                 (x, y)
             }
-        }
+        };
+
+        self.origin = (0.0, 0.0);
     }
 
     fn apply_auto(&mut self, parent_state: &ParentState, state: &State, current_pos: Dimensions) {
@@ -312,14 +743,10 @@ impl Node {
         &mut self,
         parent_state: &ParentState,
         state: &State,
+        fonts: &mut Fonts,
         current_pos: &mut Dimensions,
         total_size: &mut Dimensions,
     ) {
-        let (width, height) = match &self.text {
-            None => (0.0, 0.0),
-            Some(text) => text.extents(),
-        };
-
         let context = Context {
             root_font_size: state.root_font_size,
             parent_font_size: parent_state.font_size,
@@ -328,20 +755,29 @@ impl Node {
             reference_size: 0.0,
             auto: 0.0,
         };
+        let replaced = self.replaced_size(parent_state, &context);
 
         match self.style.display {
             Display::Block => {
-                self.width = self.style.width(&Context {
-                    reference_size: parent_state.width,
-                    auto: parent_state.width,
-                    ..context
-                });
-                let auto = self.compute_layout(state).1.max(height);
-                self.height = self.style.height(&Context {
-                    reference_size: parent_state.height,
-                    auto,
-                    ..context
-                });
+                match replaced {
+                    Some(size) => (self.width, self.height) = size,
+                    None => {
+                        self.width = self.style.width(&Context {
+                            reference_size: parent_state.width,
+                            auto: parent_state.width,
+                            ..context
+                        });
+                        // Text wraps at the width of the block, unless it is not known yet.
+                        let width = Some(self.width).filter(|width| *width > 0.0);
+                        let (_, height) = self.text_extents(fonts, width);
+                        let auto = self.compute_layout(state, fonts).1.max(height);
+                        self.height = self.style.height(&Context {
+                            reference_size: parent_state.height,
+                            auto,
+                            ..context
+                        });
+                    }
+                }
 
                 let self_extents = self.get_extents(state);
                 current_pos.width = 0.0;
@@ -350,7 +786,16 @@ impl Node {
                 total_size.height = current_pos.height.max(total_size.height);
             }
             Display::Inline => {
-                (self.width, self.height) = self.compute_layout(state);
+                // `width` and `height` don't apply to inline boxes, which take the size of
+                // their own text and their children.
+                (self.width, self.height) = match replaced {
+                    Some(size) => size,
+                    None => {
+                        let (text_width, text_height) = self.text_extents(fonts, None);
+                        let (width, height) = self.compute_layout(state, fonts);
+                        (width.max(text_width), height.max(text_height))
+                    }
+                };
 
                 let self_extents = self.get_extents(state);
 
@@ -360,17 +805,34 @@ impl Node {
                     (current_pos.height + self_extents.height).max(total_size.height);
             }
             Display::InlineBlock => {
-                let auto = self.compute_layout(state);
-                self.width = self.style.width(&Context {
-                    reference_size: parent_state.width,
-                    auto: auto.0.max(width),
-                    ..context
-                });
-                self.height = self.style.height(&Context {
-                    reference_size: parent_state.height,
-                    auto: auto.1.max(height),
-                    ..context
-                });
+                match replaced {
+                    Some(size) => (self.width, self.height) = size,
+                    None => {
+                        // Children are aligned against a given width, so it is needed up
+                        // front.
+                        if !self.style.width.is_auto() {
+                            self.width = self.style.width(&Context {
+                                reference_size: parent_state.width,
+                                ..context
+                            });
+                        }
+                        let (width, _) = self.text_extents(fonts, None);
+                        let auto = self.compute_layout(state, fonts);
+                        self.width = self.style.width(&Context {
+                            reference_size: parent_state.width,
+                            auto: auto.0.max(width),
+                            ..context
+                        });
+                        // Text wraps and aligns at the width of the box.
+                        let width = Some(self.width).filter(|width| *width > 0.0);
+                        let (_, height) = self.text_extents(fonts, width);
+                        self.height = self.style.height(&Context {
+                            reference_size: parent_state.height,
+                            auto: auto.1.max(height),
+                            ..context
+                        });
+                    }
+                }
 
                 let self_extents = self.get_extents(state);
 
@@ -383,88 +845,842 @@ impl Node {
         }
     }
 
+    // Size of the node's text wrapped at `width`, if any.
+    fn text_extents(&mut self, fonts: &mut Fonts, width: Option<f32>) -> (f32, f32) {
+        match &mut self.text {
+            Some(text) => {
+                text.wrap(&mut fonts.font_system, width);
+                text.extents()
+            }
+            None => (0.0, 0.0),
+        }
+    }
+
+    // Intrinsic size of an image node's picture.
+    fn intrinsic_size(&self) -> Option<[f32; 2]> {
+        match (&self.image, &self.svg) {
+            (Some(image), _) => Some([image.width() as f32, image.height() as f32]),
+            (None, Some(svg)) => Some([svg.width(), svg.height()]),
+            (None, None) => None,
+        }
+    }
+
+    // Size of an image node: `auto` takes the intrinsic size, or keeps the intrinsic aspect
+    // ratio when the other dimension is given.
+    fn replaced_size(&self, parent_state: &ParentState, context: &Context) -> Option<(f32, f32)> {
+        let [intrinsic_width, intrinsic_height] =
+            self.intrinsic_size()?.map(|size| size.max(f32::EPSILON));
+
+        let height_context = Context {
+            reference_size: parent_state.height,
+            auto: intrinsic_height,
+            ..*context
+        };
+        let width = self.style.width(&Context {
+            reference_size: parent_state.width,
+            auto: match self.style.height.is_auto() {
+                true => intrinsic_width,
+                false => self.style.height(&height_context) * intrinsic_width / intrinsic_height,
+            },
+            ..*context
+        });
+        let height = self.style.height(&Context {
+            auto: width * intrinsic_height / intrinsic_width,
+            ..height_context
+        });
+
+        Some((width, height))
+    }
+
     pub fn offset_children(&mut self) {
         let x = self.x + self.margin[3] + self.padding[3] + self.border.size[3];
         let y = self.y + self.margin[0] + self.padding[0] + self.border.size[0];
 
         self.children.iter_mut().for_each(|child| {
-            child.x += x;
-            child.y += y;
+            child.x += x - child.origin.0;
+            child.y += y - child.origin.1;
+            child.origin = (x, y);
             child.offset_children();
         });
     }
 
+    /// Appends a child built by `f`, starting from a node with the inherited text properties
+    /// of this one.
     pub fn add_child<F>(mut self, f: F) -> Self
     where
         F: Fn(Node) -> Node,
     {
-        let node = f(Node::default());
+        let node = f(self.inherit());
         self.children.push(node);
 
         self
     }
 
+    /// Appends a run of text after the children added so far, as an anonymous inline child
+    /// with the inherited text properties of the node.
+    pub fn add_text(mut self, text: &str) -> Self {
+        let mut node = self.inherit();
+        node.style.display = Display::Inline;
+        self.children.push(node.set_content(text));
+
+        self
+    }
+
+    // Starts a node with the inherited text properties of this one.
+    pub(crate) fn inherit(&self) -> Node {
+        let mut node = Node::default();
+        node.style.font_size = Units::Em(1.0);
+        node.style.font_color = self.style.font_color;
+        node.style.font_family = self.style.font_family.clone();
+        node.style.font_weight = self.style.font_weight;
+        node.style.font_style = self.style.font_style;
+        node.style.font_stretch = self.style.font_stretch;
+        node.style.font_variant = self.style.font_variant;
+        node.style.line_height = self.style.line_height.clone();
+        node.style.text_align = self.style.text_align;
+        node.style.text_indent = self.style.text_indent.clone();
+        node.style.white_space = self.style.white_space;
+        node.style.word_break = self.style.word_break;
+        node.style.overflow_wrap = self.style.overflow_wrap;
+        node.style.hyphens = self.style.hyphens;
+        node
+    }
+
+    /// Lays out the dirty parts of the tree rooted at this node, which spans the viewport,
+    /// shaping text with `fonts`.
+    pub fn layout(&mut self, state: &State, fonts: &mut Fonts) {
+        if !self.dirty.node && !self.dirty.descendants {
+            return;
+        }
+
+        self.update_static_properties(
+            &ParentState {
+                x: 0.0,
+                y: 0.0,
+                width: state.viewport.0,
+                height: state.viewport.1,
+                font_size: state.root_font_size,
+            },
+            state,
+            false,
+        );
+        self.update_text(state, fonts, false, false);
+
+        let context = Context {
+            root_font_size: state.root_font_size,
+            dpi: state.dpi,
+            parent_font_size: state.root_font_size,
+            viewport: state.viewport,
+            reference_size: 0.0,
+            auto: 0.0,
+        };
+
+        // Inline children wrap against the root's width, so it is needed up front unless it
+        // shrinks to fit them.
+        if !self.style.width.is_auto() {
+            self.width = self.style.width(&Context {
+                reference_size: state.viewport.0,
+                ..context
+            });
+        }
+        let auto = self.compute_layout(state, fonts);
+
+        self.width = self.style.width(&Context {
+            auto: auto.0,
+            reference_size: state.viewport.0,
+            ..context
+        });
+        self.height = self.style.height(&Context {
+            auto: auto.1,
+            reference_size: state.viewport.1,
+            ..context
+        });
+
+        self.offset_children();
+        self.clear_dirty();
+    }
+
+    // Marks every node for relayout, for changes that can affect any of them.
+    pub(crate) fn mark_all_dirty(&mut self) {
+        self.dirty.node = true;
+        self.dirty.descendants = !self.children.is_empty();
+        self.children.iter_mut().for_each(Node::mark_all_dirty);
+    }
+
+    fn clear_dirty(&mut self) {
+        if !self.dirty.node && !self.dirty.descendants {
+            return;
+        }
+
+        self.dirty.node = false;
+        self.dirty.descendants = false;
+        self.children.iter_mut().for_each(Node::clear_dirty);
+    }
+
+    /// Patches this node to match `description`, keeping runtime state such as the handle,
+    /// text shaping and cached layout of nodes that survive. Children are matched by key, or
+    /// by position among their unkeyed siblings. Siblings sharing a key are matched by their
+    /// order among each other. Only what changed is marked for relayout.
+    pub fn reconcile(&mut self, description: Node) {
+        self.data.copy_paint_properties(&description.data);
+
+        if self.style != description.style {
+            self.data.style = description.data.style;
+            self.dirty.node = true;
+        }
+
+        if self.content != description.content || self.spans != description.spans {
+            self.content = description.content;
+            self.spans = description.spans;
+            self.dirty.node = true;
+        }
+
+        if self.image != description.image || self.svg != description.svg {
+            self.image = description.image;
+            self.svg = description.svg;
+            self.dirty.node = true;
+        }
+
+        self.id = description.id;
+        self.class = description.class;
+        self.key = description.key;
+
+        self.bindings = description.bindings;
+
+        let mut keyed = HashMap::<_, VecDeque<_>>::new();
+        let mut unkeyed = VecDeque::new();
+        self.children
+            .iter()
+            .enumerate()
+            .for_each(|(i, child)| match &child.key {
+                Some(key) => keyed.entry(key.clone()).or_default().push_back(i),
+                None => unkeyed.push_back(i),
+            });
+
+        let mut old = std::mem::take(&mut self.children)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+
+        let matched = description
+            .children
+            .into_iter()
+            .map(|child| {
+                let matched = match &child.key {
+                    Some(key) => keyed.get_mut(key).and_then(VecDeque::pop_front),
+                    None => unkeyed.pop_front(),
+                };
+                (
+                    matched.and_then(|i| old[i].take().map(|node| (i, node))),
+                    child,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Children that kept their order among each other stay put, the others moved.
+        let mut in_place = in_order(
+            matched
+                .iter()
+                .filter_map(|(node, _)| Some(node.as_ref()?.0)),
+        )
+        .into_iter();
+        let mut reordered = old.iter().any(Option::is_some);
+
+        self.children = matched
+            .into_iter()
+            .map(|(node, child)| match node {
+                Some((_, mut node)) => {
+                    let span = node.is_text_span();
+                    node.reconcile(child);
+                    if !in_place.next().unwrap_or_default() {
+                        reordered = true;
+                        node.dirty.node = true;
+                    }
+                    self.join_paragraph(&mut node, span);
+                    node
+                }
+                None => {
+                    reordered = true;
+                    child
+                }
+            })
+            .collect();
+
+        // Only new and moved children are marked, the others keep their shaping and layout
+        // while this node lays them out again.
+        self.dirty.descendants |= reordered;
+    }
+
+    pub fn find(&self, handle: Handle) -> Option<&Node> {
+        if self.handle == handle {
+            return Some(self);
+        }
+
+        self.children.iter().find_map(|child| child.find(handle))
+    }
+
+    pub fn find_by_id(&self, id: &str) -> Option<&Node> {
+        if self.id.as_deref() == Some(id) {
+            return Some(self);
+        }
+
+        self.children.iter().find_map(|child| child.find_by_id(id))
+    }
+
+    fn path_to(&self, handle: Handle) -> Option<Vec<usize>> {
+        if self.handle == handle {
+            return Some(Vec::new());
+        }
+
+        self.children.iter().enumerate().find_map(|(i, child)| {
+            child.path_to(handle).map(|mut path| {
+                path.insert(0, i);
+                path
+            })
+        })
+    }
+
+    // Walks down `path`, flagging every node on the way as having a dirty descendant.
+    fn descend_mut(&mut self, path: &[usize]) -> &mut Node {
+        match path.split_first() {
+            Some((&i, rest)) => {
+                self.dirty.descendants = true;
+                self.children[i].descend_mut(rest)
+            }
+            None => self,
+        }
+    }
+
+    /// Re-applies the bindings of nodes whose signals changed since they were last applied.
+    /// Only nodes whose style, content, image or children changed are marked for relayout,
+    /// paint-only changes such as colors just show up on the next render. Returns whether any
+    /// binding was applied.
+    pub fn apply_signals(&mut self) -> bool {
+        let mut applied = false;
+        if self.bindings.iter().any(|binding| binding.pending.get()) {
+            self.apply_bindings();
+            applied = true;
+        }
+
+        let mut children = std::mem::take(&mut self.children);
+        for child in &mut children {
+            let span = child.is_text_span();
+            applied |= child.apply_signals();
+            self.join_paragraph(child, span);
+        }
+        self.children = children;
+
+        applied
+    }
+
+    // Takes note of changes to `child`, which was a text span before them if `span`. Paint
+    // properties decide whether it joins this node's paragraph too, so when it joins or leaves
+    // the paragraph, both are laid out again.
+    fn join_paragraph(&mut self, child: &mut Node, span: bool) {
+        if child.is_text_span() != span {
+            child.dirty.node = true;
+            self.dirty.node = true;
+        }
+        self.dirty.descendants |= child.dirty.node || child.dirty.descendants;
+    }
+
+    fn apply_bindings(&mut self) {
+        let style = self.style.clone();
+        let content = self.content.clone();
+        let spans = self.spans.clone();
+        let image = self.image.clone();
+        let svg = self.svg.clone();
+        let children = self
+            .children
+            .iter()
+            .map(|child| child.handle)
+            .collect::<Vec<_>>();
+
+        let mut bindings = std::mem::take(&mut self.bindings);
+        let mut node = bindings
+            .iter()
+            .fold(std::mem::take(self), |node, binding| binding.apply(node));
+        bindings.append(&mut node.bindings);
+        node.bindings = bindings;
+
+        let changed = node.style != style
+            || node.content != content
+            || node.spans != spans
+            || node.image != image
+            || node.svg != svg
+            || !node.children.iter().map(|child| child.handle).eq(children);
+        *self = node;
+
+        if changed {
+            self.dirty.node = true;
+        }
+    }
+
+    /// Rebuilds the node with `handle` through `f` and marks it for relayout.
+    pub fn update<F>(&mut self, handle: Handle, f: F) -> bool
+    where
+        F: FnOnce(Node) -> Node,
+    {
+        let Some(path) = self.path_to(handle) else {
+            return false;
+        };
+
+        let target = self.descend_mut(&path);
+        let node = std::mem::take(target);
+        *target = f(node);
+        // Rebuilding the node from scratch keeps it reachable by the same handle.
+        target.handle = handle;
+        target.dirty.node = true;
+
+        true
+    }
+
+    /// Inserts a child built by `f` at `index` (clamped to the number of children) of the
+    /// node with handle `parent`, starting from a node with its inherited text properties.
+    pub fn insert_child<F>(&mut self, parent: Handle, index: usize, f: F) -> Option<Handle>
+    where
+        F: FnOnce(Node) -> Node,
+    {
+        let path = self.path_to(parent)?;
+        let parent = self.descend_mut(&path);
+        let node = f(parent.inherit());
+        let handle = node.handle;
+
+        let index = index.min(parent.children.len());
+        parent.children.insert(index, node);
+        parent.dirty.node = true;
+
+        Some(handle)
+    }
+
+    /// Detaches the node with `handle` from the tree. The root itself can't be removed.
+    pub fn remove_child(&mut self, handle: Handle) -> Option<Node> {
+        let path = self.path_to(handle)?;
+        let (&index, parent_path) = path.split_last()?;
+
+        let parent = self.descend_mut(parent_path);
+        parent.dirty.node = true;
+
+        let mut node = parent.children.remove(index);
+        node.dirty.node = true;
+        Some(node)
+    }
+
+    /// Moves the node with `handle` to `index` among the children of `parent`.
+    pub fn move_child(&mut self, handle: Handle, parent: Handle, index: usize) -> bool {
+        let (Some(path), Some(parent_path)) = (self.path_to(handle), self.path_to(parent)) else {
+            return false;
+        };
+
+        if parent_path.starts_with(&path) {
+            return false;
+        }
+
+        let Some(node) = self.remove_child(handle) else {
+            return false;
+        };
+
+        self.insert_child(parent, index, |_| node).is_some()
+    }
+
+    /// The node's accumulated transform, from layout to document coordinates, given what its
+    /// parent's children build on.
+    pub(crate) fn transform(
+        &self,
+        parent: &Accumulated,
+        parent_state: &ParentState,
+        state: &State,
+    ) -> Accumulated {
+        match self.style.display == Display::Contents || self.data.transform.is_empty() {
+            true => *parent,
+            false => parent.then(&self.data.local_transform(parent_state, state)),
+        }
+    }
+
+    // What the children's transforms build on: flattened into the plane of the node unless it
+    // preserves 3D, and seen through its `perspective`.
+    fn children_transform(
+        &self,
+        transform: &Accumulated,
+        parent_state: &ParentState,
+        state: &State,
+    ) -> Accumulated {
+        if self.style.display == Display::Contents {
+            return *transform;
+        }
+
+        let transform = match self.transform_style {
+            TransformStyle::Flat => transform.flatten(),
+            TransformStyle::Preserve3d => *transform,
+        };
+        transform.then(&self.data.children_perspective(parent_state, state))
+    }
+
+    // Whether the node's own box is hidden by `backface-visibility`. Children in its 3D
+    // rendering context face their own way, flattened ones are hidden with it.
+    fn backface_hidden(&self, transform: &Accumulated) -> bool {
+        self.style.display != Display::Contents
+            && self.backface_visibility == BackfaceVisibility::Hidden
+            && transform.back_facing()
+    }
+
     pub(crate) fn collect_instances(
         &self,
-        instance_data: &mut Vec<InstanceData>,
-        text_data: &mut Vec<TextData>,
+        draw_list: &mut DrawList,
+        atlas: &mut ImageAtlas,
         parent_state: &ParentState,
+        parent_transform: &Accumulated,
         state: &State,
     ) {
-        if self.style.display == Display::None {
+        if self.style.display == Display::None || self.opacity <= 0.0 {
             return;
         }
 
-        if self.style.display != Display::Contents {
-            instance_data.push(self.data.get_instance_data(parent_state, state));
+        let transform = self.transform(parent_transform, parent_state, state);
+        let hidden = self.backface_hidden(&transform);
+        if hidden && self.transform_style == TransformStyle::Flat {
+            return;
+        }
+        let matrix = transform.matrix();
+
+        // `display: contents` has no box for opacity, filters or clips to apply to.
+        let boxed = self.style.display != Display::Contents;
+        let clip = match self.data.clip_shape(parent_state, state).filter(|_| boxed) {
+            Some(shape) => match self.data.get_clip_instance(&shape, atlas) {
+                Some(instance) => Some(instance.with_transform(&matrix)),
+                None => return,
+            },
+            None => None,
+        };
+
+        let backdrop = (!self.backdrop_filter.is_empty() && boxed && !hidden)
+            .then(|| filter::snapshot(draw_list, state))
+            .flatten();
+        let masked = !self.mask.image.is_empty();
+        let layer = ((self.opacity < 1.0 || !self.filter.is_empty() || clip.is_some() || masked)
+            && boxed)
+            .then(|| draw_list.begin_layer());
+
+        if let Some(snapshot) = backdrop {
+            let backdrop_clip = clip.unwrap_or_else(|| {
+                self.data
+                    .get_backdrop_instance(parent_state, state)
+                    .with_transform(&matrix)
+            });
+            filter::backdrop(draw_list, snapshot, &self.data, backdrop_clip, state);
+        }
+
+        if self.style.display != Display::Contents && !hidden {
+            let instances =
+                self.data
+                    .get_instances(parent_state, state, &mut draw_list.gradient_stops, atlas);
+            draw_list.extend_instances(
+                instances
+                    .into_iter()
+                    .map(|instance| instance.with_transform(&matrix)),
+            );
+            if let Some(intrinsic) = self.intrinsic_size() {
+                let rect = self.data.object_rect(intrinsic, parent_state, state);
+                let uv = match (&self.image, &self.svg) {
+                    (Some(image), _) => atlas.allocate(image),
+                    (None, Some(svg)) => {
+                        let color = self.style.font_color;
+                        let color = [color.r(), color.g(), color.b(), color.a()];
+                        // Rasterized at the size it covers once transformed, so it stays sharp.
+                        atlas.allocate_svg(svg, matrix.drawn_size(rect), color)
+                    }
+                    (None, None) => None,
+                };
+                draw_list.extend_instances(uv.map(|uv| {
+                    self.data
+                        .get_image_instance(rect, uv, parent_state, state)
+                        .with_transform(&matrix)
+                }));
+            }
             if let Some(text) = &self.text {
                 let (width, height) = text.extents();
-
-                text_data.push(TextData {
-                    x: self.data.x,
-                    y: self.data.y,
+                // Text starts at the content box, where children are placed too.
+                let left = self.data.x + self.margin[3] + self.padding[3] + self.border.size[3];
+                let top = self.data.y + self.margin[0] + self.padding[0] + self.border.size[0];
+                let text_data = |x, y, scale| TextData {
+                    x,
+                    y,
                     width,
                     height,
+                    scale,
                     buffer: text.buffer.clone(),
                     color: self.style.font_color,
-                });
+                };
+
+                match matrix.uniform_scale() {
+                    Some(scale) => {
+                        let [x, y] = matrix.apply([left, top]);
+                        draw_list.push_text(text_data(x, y, scale));
+                    }
+                    // Glyphs can only be moved and scaled evenly, anything else draws them into
+                    // a layer that is transformed as a whole.
+                    None => {
+                        let parent = draw_list.begin_layer();
+                        draw_list.push_text(text_data(0.0, 0.0, 1.0));
+                        if let Some(layer) = draw_list.end_layer(parent) {
+                            let rect = [left, top, width, height];
+                            draw_list.composite(
+                                layer,
+                                InstanceData::placed_layer(rect).with_transform(&matrix),
+                            );
+                        }
+                    }
+                }
             }
         }
 
+        let children_transform = self.children_transform(&transform, parent_state, state);
         self.children.iter().for_each(|child| {
-            child.collect_instances(instance_data, text_data, &self.get_state(), state)
+            child.collect_instances(
+                draw_list,
+                atlas,
+                &self.get_state(),
+                &children_transform,
+                state,
+            )
+        });
+
+        if let Some(layer) = layer.and_then(|parent| draw_list.end_layer(parent)) {
+            let mask = match masked {
+                // A mask without anything drawn into it hides everything.
+                true => match self.collect_mask(draw_list, atlas, parent_state, &matrix, state) {
+                    Some(mask) => Some(mask),
+                    None => return,
+                },
+                false => None,
+            };
+            filter::composite(draw_list, layer, &self.data, clip, mask, state);
+        }
+    }
+
+    /// Draws the mask layers into a layer of their own and returns it, `None` if nothing was
+    /// drawn.
+    fn collect_mask(
+        &self,
+        draw_list: &mut DrawList,
+        atlas: &mut ImageAtlas,
+        parent_state: &ParentState,
+        matrix: &Mat4,
+        state: &State,
+    ) -> Option<usize> {
+        let instances =
+            self.data
+                .get_mask_instances(parent_state, state, &mut draw_list.gradient_stops, atlas);
+
+        let parent = draw_list.begin_layer();
+        for (instance, luminance) in instances {
+            let instance = instance.with_transform(matrix);
+            match luminance {
+                true => {
+                    let mask = draw_list.begin_layer();
+                    draw_list.extend_instances([instance]);
+                    if let Some(layer) = draw_list.end_layer(mask) {
+                        filter::luminance(draw_list, layer, state);
+                    }
+                }
+                false => draw_list.extend_instances([instance]),
+            }
+        }
+        draw_list.end_layer(parent)
+    }
+
+    /// The deepest node drawn at `x`, `y` in document coordinates, going through transforms
+    /// like rendering does. Later siblings are on top.
+    pub fn hit_test(&self, x: f32, y: f32, state: &State) -> Option<Handle> {
+        let parent_state = ParentState {
+            x: 0.0,
+            y: 0.0,
+            width: state.viewport.0,
+            height: state.viewport.1,
+            font_size: state.root_font_size,
+        };
+        self.hit_test_from([x, y], &parent_state, &Accumulated::IDENTITY, state)
+    }
+
+    fn hit_test_from(
+        &self,
+        point: [f32; 2],
+        parent_state: &ParentState,
+        parent_transform: &Accumulated,
+        state: &State,
+    ) -> Option<Handle> {
+        if self.style.display == Display::None {
+            return None;
+        }
+
+        let transform = self.transform(parent_transform, parent_state, state);
+        let hidden = self.backface_hidden(&transform);
+        if hidden && self.transform_style == TransformStyle::Flat {
+            return None;
+        }
+        // `clip-path` cuts off the node and its descendants alike.
+        if let Some(shape) = self
+            .data
+            .clip_shape(parent_state, state)
+            .filter(|_| self.style.display != Display::Contents)
+        {
+            let local = transform.matrix().unproject(point);
+            if !local.is_some_and(|local| shape.contains(local)) {
+                return None;
+            }
+        }
+
+        let children_transform = self.children_transform(&transform, parent_state, state);
+        let hit = self.children.iter().rev().find_map(|child| {
+            child.hit_test_from(point, &self.get_state(), &children_transform, state)
         });
+        if hit.is_some() || self.style.display == Display::Contents || hidden {
+            return hit;
+        }
+
+        let local = transform.matrix().unproject(point)?;
+        self.data
+            .contains(local, parent_state, state)
+            .then_some(self.handle)
     }
 }
 
 impl Node {
     pub fn set_content(mut self, content: &str) -> Self {
-        if self.text.is_none() {
-            let mut font_system = FontSystem::new();
-
-            self.text = Some(Text {
-                buffer: glyphon::Buffer::new(
-                    &mut font_system,
-                    glyphon::Metrics::new(self.font_size, self.line_height),
-                ),
-                font_system,
-            })
-        }
+        self.content = Some(content.to_string());
+        self
+    }
 
-        let family = self.style.font_family.clone();
-        let text = self.text.as_mut().unwrap();
-        text.buffer.set_text(
-            &mut text.font_system,
-            content,
-            Attrs::new().family(family.as_family()),
-            glyphon::Shaping::Advanced,
-        );
+    /// Sets runs of text with their own color, weight, style, size or family, following the
+    /// content in one paragraph.
+    pub fn set_rich_text(mut self, spans: Vec<TextSpan>) -> Self {
+        self.spans = spans;
+        self
+    }
 
+    /// Turns the node into an image element. The image is uploaded to the GPU the first time
+    /// it is drawn.
+    pub fn set_image(mut self, image: Image) -> Self {
+        self.image = Some(image);
+        self
+    }
+
+    /// Turns the node into an image element drawing `svg`, rasterized at its laid out size
+    /// with `currentColor` following the font color.
+    pub fn set_svg(mut self, svg: Svg) -> Self {
+        self.svg = Some(svg);
+        self
+    }
+
+    pub fn set_object_fit(mut self, object_fit: ObjectFit) -> Self {
+        self.style.object_fit = object_fit;
+        self
+    }
+
+    pub fn set_object_position(mut self, x: Units, y: Units) -> Self {
+        self.style.object_position = [x, y];
+        self
+    }
+
+    /// Keeps the node in sync with `signal`: `f` is applied now and again with the new value
+    /// whenever the signal changes.
+    pub fn bind<T, F>(self, signal: &Signal<T>, f: F) -> Self
+    where
+        T: 'static,
+        F: Fn(Node, &T) -> Node + 'static,
+    {
+        let binding = Binding::new(signal, f);
+        let mut node = binding.apply(self);
+        node.bindings.push(binding);
+        node
+    }
+
+    pub fn set_key(mut self, key: &str) -> Self {
+        self.key = Some(key.to_string());
+        self
+    }
+
+    pub fn set_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn set_class(mut self, class: &str) -> Self {
+        self.class = class.split_whitespace().map(String::from).collect();
+        self
+    }
+
+    /// Applies the declarations of an inline style like a `style` attribute, skipping those it
+    /// doesn't understand. Its `url()`s are relative to the working directory.
+    pub fn set_style(mut self, style: &str) -> Self {
+        thread_local! {
+            // Rebuilt descriptions share the images they name, so reconciling keeps them.
+            static IMAGES: ImageCache = ImageCache::new("");
+        }
+
+        let declarations = parser::parse_declarations(style);
+        IMAGES.with(|images| parser::cascade(&mut self, &declarations, images));
         self
     }
 
     pub fn set_font_family(mut self, font_family: FamilyOwned) -> Self {
-        self.style.font_family = font_family;
+        self.style.font_family = vec![font_family];
+        self
+    }
+
+    /// Sets a fallback list of families, tried in order for each character.
+    pub fn set_font_families(mut self, font_families: Vec<FamilyOwned>) -> Self {
+        self.style.font_family = font_families;
+        self
+    }
+
+    pub fn set_font_weight(mut self, font_weight: Weight) -> Self {
+        self.style.font_weight = font_weight;
+        self
+    }
+
+    pub fn set_font_style(mut self, font_style: glyphon::Style) -> Self {
+        self.style.font_style = font_style;
+        self
+    }
+
+    pub fn set_font_stretch(mut self, font_stretch: Stretch) -> Self {
+        self.style.font_stretch = font_stretch;
+        self
+    }
+
+    pub fn set_font_variant(mut self, font_variant: FontVariant) -> Self {
+        self.style.font_variant = font_variant;
+        self
+    }
+
+    pub fn set_text_align(mut self, text_align: TextAlign) -> Self {
+        self.style.text_align = text_align;
+        self
+    }
+
+    pub fn set_text_indent(mut self, text_indent: Units) -> Self {
+        self.style.text_indent = text_indent;
+        self
+    }
+
+    pub fn set_white_space(mut self, white_space: WhiteSpace) -> Self {
+        self.style.white_space = white_space;
+        self
+    }
+
+    pub fn set_word_break(mut self, word_break: WordBreak) -> Self {
+        self.style.word_break = word_break;
+        self
+    }
+
+    pub fn set_overflow_wrap(mut self, overflow_wrap: OverflowWrap) -> Self {
+        self.style.overflow_wrap = overflow_wrap;
+        self
+    }
+
+    pub fn set_hyphens(mut self, hyphens: Hyphens) -> Self {
+        self.style.hyphens = hyphens;
         self
     }
 
@@ -534,6 +1750,36 @@ impl Node {
         self
     }
 
+    pub fn set_background_image(mut self, image: Vec<BackgroundImage>) -> Self {
+        self.background.image = image;
+        self
+    }
+
+    pub fn set_background_size(mut self, size: Vec<BackgroundSize>) -> Self {
+        self.background.size = size;
+        self
+    }
+
+    pub fn set_background_position(mut self, position: Vec<[Units; 2]>) -> Self {
+        self.background.position = position;
+        self
+    }
+
+    pub fn set_background_repeat(mut self, repeat: Vec<[BackgroundRepeat; 2]>) -> Self {
+        self.background.repeat = repeat;
+        self
+    }
+
+    pub fn set_background_origin(mut self, origin: Vec<BackgroundBox>) -> Self {
+        self.background.origin = origin;
+        self
+    }
+
+    pub fn set_background_clip(mut self, clip: Vec<BackgroundBox>) -> Self {
+        self.background.clip = clip;
+        self
+    }
+
     pub fn set_margin(mut self, top: Units, right: Units, bottom: Units, left: Units) -> Self {
         self.style.margin = [top, right, bottom, left];
         self
@@ -544,23 +1790,34 @@ impl Node {
         self
     }
 
+    pub fn set_border_style(
+        mut self,
+        top: BorderStyle,
+        right: BorderStyle,
+        bottom: BorderStyle,
+        left: BorderStyle,
+    ) -> Self {
+        self.style.border_style = [top, right, bottom, left];
+        self
+    }
+
     pub fn set_border_top_color(mut self, r: f32, g: f32, b: f32, a: f32) -> Self {
-        self.border.top_color = [r, g, b, a];
+        self.border.top_color = Some([r, g, b, a]);
         self
     }
 
     pub fn set_border_bottom_color(mut self, r: f32, g: f32, b: f32, a: f32) -> Self {
-        self.border.bottom_color = [r, g, b, a];
+        self.border.bottom_color = Some([r, g, b, a]);
         self
     }
 
     pub fn set_border_left_color(mut self, r: f32, g: f32, b: f32, a: f32) -> Self {
-        self.border.left_color = [r, g, b, a];
+        self.border.left_color = Some([r, g, b, a]);
         self
     }
 
     pub fn set_border_right_color(mut self, r: f32, g: f32, b: f32, a: f32) -> Self {
-        self.border.right_color = [r, g, b, a];
+        self.border.right_color = Some([r, g, b, a]);
         self
     }
 
@@ -590,63 +1847,83 @@ impl Node {
         self
     }
 
-    pub fn set_sepia(mut self, sepia: f32) -> Self {
-        self.sepia = sepia;
+    pub fn set_box_shadow(mut self, box_shadow: Vec<BoxShadow>) -> Self {
+        self.box_shadow = box_shadow;
         self
     }
 
     pub fn set_opacity(mut self, opacity: f32) -> Self {
-        self.background_color[3] = opacity;
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn set_filter(mut self, filter: Vec<Filter>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn set_backdrop_filter(mut self, backdrop_filter: Vec<Filter>) -> Self {
+        self.backdrop_filter = backdrop_filter;
+        self
+    }
+
+    pub fn set_clip_path(mut self, clip_path: Option<ClipPath>) -> Self {
+        self.clip_path = clip_path;
+        self
+    }
+
+    pub fn set_mask_image(mut self, image: Vec<BackgroundImage>) -> Self {
+        self.mask.image = image;
         self
     }
 
-    pub fn set_brightness(mut self, brightness: f32) -> Self {
-        self.brightness = brightness;
+    pub fn set_mask_size(mut self, size: Vec<BackgroundSize>) -> Self {
+        self.mask.size = size;
         self
     }
 
-    pub fn set_contrast(mut self, contrast: f32) -> Self {
-        self.contrast = contrast;
+    pub fn set_mask_position(mut self, position: Vec<[Units; 2]>) -> Self {
+        self.mask.position = position;
         self
     }
 
-    pub fn set_grayscale(mut self, grayscale: f32) -> Self {
-        self.grayscale = grayscale;
+    pub fn set_mask_repeat(mut self, repeat: Vec<[BackgroundRepeat; 2]>) -> Self {
+        self.mask.repeat = repeat;
         self
     }
 
-    pub fn set_hue_rotate(mut self, hue_rotate: f32) -> Self {
-        self.hue_rotate = hue_rotate;
+    pub fn set_mask_mode(mut self, mode: Vec<MaskMode>) -> Self {
+        self.mask.mode = mode;
         self
     }
 
-    pub fn set_invert(mut self, invert: f32) -> Self {
-        self.invert = invert;
+    pub fn set_transform(mut self, transform: Vec<Transform>) -> Self {
+        self.transform = transform;
         self
     }
 
-    pub fn set_saturate(mut self, saturate: f32) -> Self {
-        self.saturate = saturate;
+    pub fn set_transform_origin(mut self, x: Units, y: Units) -> Self {
+        self.transform_origin = [x, y];
         self
     }
 
-    pub fn set_scale(mut self, x: f32, y: f32) -> Self {
-        self.scale = [x, y];
+    pub fn set_perspective(mut self, perspective: Option<Units>) -> Self {
+        self.perspective = perspective;
         self
     }
 
-    pub fn set_skew(mut self, x: f32, y: f32) -> Self {
-        self.skew = [x, y];
+    pub fn set_perspective_origin(mut self, x: Units, y: Units) -> Self {
+        self.perspective_origin = [x, y];
         self
     }
 
-    pub fn set_rotate(mut self, rotation: f32) -> Self {
-        self.rotate = rotation;
+    pub fn set_transform_style(mut self, transform_style: TransformStyle) -> Self {
+        self.transform_style = transform_style;
         self
     }
 
-    pub fn set_translate(mut self, translate: [f32; 2]) -> Self {
-        self.translate = translate;
+    pub fn set_backface_visibility(mut self, backface_visibility: BackfaceVisibility) -> Self {
+        self.backface_visibility = backface_visibility;
         self
     }
 }
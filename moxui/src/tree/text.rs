@@ -1,8 +1,14 @@
+use std::rc::Rc;
+
 use glyphon::{
-    Buffer, Cache, Color, FontSystem, SwashCache, TextArea, TextAtlas, TextRenderer, Viewport,
+    cosmic_text::{AttrsList, BufferLine},
+    AttrsOwned, Buffer, Cache, Color, FamilyOwned, FontSystem, Shaping, SwashCache, TextArea,
+    TextAtlas, TextRenderer, Viewport,
 };
 use wgpu::MultisampleState;
 
+use crate::{fonts::FontVariant, text::SOFT_HYPHEN};
+
 use super::Config;
 
 pub struct TextData {
@@ -10,82 +16,197 @@ pub struct TextData {
     pub y: f32,
     pub width: f32,
     pub height: f32,
-    pub buffer: Buffer,
+    /// How much to scale the glyphs by, around `x`, `y`.
+    pub scale: f32,
+    /// Shared with the node, which only copies it when shaping again while a frame still
+    /// holds it.
+    pub buffer: Rc<Buffer>,
     pub color: Color,
 }
 
+impl TextData {
+    /// The box the glyphs are drawn in, in viewport pixels.
+    pub fn bounds(&self) -> [f32; 4] {
+        [
+            self.x,
+            self.y,
+            self.width * self.scale,
+            self.height * self.scale,
+        ]
+    }
+}
+
+/// A run of a paragraph in one style, as it is shaped. `attrs` carries the color and metrics
+/// of the run.
+#[derive(Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub family: Vec<FamilyOwned>,
+    pub attrs: AttrsOwned,
+    pub variant: FontVariant,
+}
+
 pub struct Text {
-    pub buffer: Buffer,
-    pub font_system: FontSystem,
+    pub buffer: Rc<Buffer>,
+    // What the buffer was last shaped from.
+    pub spans: Vec<Span>,
+    pub indent: f32,
+    pub generation: u64,
+    // The lines as shaped when they have soft hyphens, which wrapping draws hyphens into.
+    pub(crate) paragraphs: Vec<(String, AttrsList)>,
+    // Whether the lines were wrapped since they were shaped.
+    pub(crate) wrapped: bool,
 }
 
 impl Text {
+    // Wraps the lines at `width`, a line broken at a soft hyphen ending in a hyphen. The
+    // buffer may be shared with what was last drawn, so it is only copied when it changes.
+    pub(crate) fn wrap(&mut self, font_system: &mut FontSystem, width: Option<f32>) {
+        if self.wrapped && self.buffer.size() == (width, None) {
+            return;
+        }
+        self.wrapped = true;
+
+        let buffer = Rc::make_mut(&mut self.buffer);
+        buffer.set_size(font_system, width, None);
+        for (i, (text, attrs)) in self.paragraphs.iter().enumerate() {
+            let line = &mut buffer.lines[i];
+            if text.contains(SOFT_HYPHEN) {
+                line.set_text(text, line.ending(), attrs.clone());
+                hyphenate(buffer, font_system, i);
+            }
+        }
+    }
+
+    /// Size of the text, up to the right of aligned lines.
     pub fn extents(&self) -> (f32, f32) {
-        let (width, total_lines) = self
-            .buffer
+        self.buffer
             .layout_runs()
-            .fold((0.0, 0.0), |(width, total_lines), run| {
-                (run.line_w.max(width), total_lines + 1.0)
-            });
+            .fold((0.0, 0.0), |(width, _), run| {
+                let right = run
+                    .glyphs
+                    .iter()
+                    .map(|glyph| glyph.x + glyph.w)
+                    .fold(run.line_w, f32::max);
+                (right.max(width), run.line_top + run.line_height)
+            })
+    }
+}
 
-        (width, total_lines * self.buffer.metrics().line_height)
+// Draws a hyphen before the soft hyphen each line of paragraph `i` breaks at. The hyphen may
+// not fit on the line, pushing the syllable before it to the next one, in which case the line
+// can't break there and the soft hyphen goes instead.
+fn hyphenate(buffer: &mut Buffer, font_system: &mut FontSystem, i: usize) {
+    let mut from = 0;
+    while let Some(end) = soft_break(buffer, font_system, i, from) {
+        let at = end - SOFT_HYPHEN.len_utf8();
+        let line = &mut buffer.lines[i];
+        let hyphen = BufferLine::new(
+            "-",
+            line.ending(),
+            AttrsList::new(line.attrs_list().get_span(at)),
+            Shaping::Advanced,
+        );
+        let rest = line.split_off(at);
+        line.append(hyphen);
+        line.append(rest);
+
+        if line_ends(buffer, font_system, i).contains(&(end + 1)) {
+            from = end + 1;
+        } else {
+            let line = &mut buffer.lines[i];
+            let mut rest = line.split_off(at);
+            line.append(rest.split_off(end + 1 - at));
+        }
     }
 }
 
+// The first end of a line of paragraph `i` after `from`, where the lines before are done, that
+// breaks at a soft hyphen without a hyphen before it.
+fn soft_break(
+    buffer: &mut Buffer,
+    font_system: &mut FontSystem,
+    i: usize,
+    from: usize,
+) -> Option<usize> {
+    let ends = line_ends(buffer, font_system, i);
+    let text = buffer.lines[i].text();
+    ends.into_iter().find(|&end| {
+        end > from
+            && text[..end]
+                .strip_suffix(SOFT_HYPHEN)
+                .is_some_and(|text| !text.ends_with('-'))
+    })
+}
+
+// Where the lines of paragraph `i` that wrap end in its text.
+fn line_ends(buffer: &mut Buffer, font_system: &mut FontSystem, i: usize) -> Vec<usize> {
+    let lines = buffer.line_layout(font_system, i).unwrap_or_default();
+    lines
+        .iter()
+        .take(lines.len().saturating_sub(1))
+        .filter_map(|line| line.glyphs.iter().map(|glyph| glyph.end).max())
+        .collect()
+}
+
 pub struct TextContext {
     pub swash_cache: glyphon::SwashCache,
-    pub viewport: glyphon::Viewport,
+    pub cache: glyphon::Cache,
     pub atlas: glyphon::TextAtlas,
-    pub renderer: glyphon::TextRenderer,
+    // One per layer, as every layer is drawn in its own render pass, with the viewport of its
+    // texture.
+    pub renderers: Vec<(glyphon::TextRenderer, glyphon::Viewport)>,
 }
 
 impl TextContext {
     pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, config: &Config) -> Self {
         let swash_cache = SwashCache::new();
         let cache = Cache::new(device);
-        let mut viewport = Viewport::new(device, &cache);
-        let mut atlas = TextAtlas::new(device, queue, &cache, config.format);
-        let renderer = TextRenderer::new(&mut atlas, device, MultisampleState::default(), None);
-
-        viewport.update(
-            queue,
-            glyphon::Resolution {
-                width: config.width as u32,
-                height: config.height as u32,
-            },
-        );
+        let atlas = TextAtlas::new(device, queue, &cache, config.format);
 
         Self {
             swash_cache,
-            viewport,
+            cache,
             atlas,
-            renderer,
+            renderers: Vec::new(),
         }
     }
 
-    pub fn render(
+    /// Uploads the text of every layer, in layer order, before any of them is rendered. Each
+    /// layer comes with the size of its texture, its text placed in it.
+    pub fn prepare<'a>(
         &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        render_pass: &mut wgpu::RenderPass,
-        text: Vec<TextArea>,
+        font_system: &mut FontSystem,
+        layers: impl ExactSizeIterator<Item = ([u32; 2], Vec<TextArea<'a>>)>,
     ) {
-        let mut font_system = FontSystem::new();
-
-        self.renderer
-            .prepare(
-                device,
-                queue,
-                &mut font_system,
-                &mut self.atlas,
-                &self.viewport,
-                text,
-                &mut self.swash_cache,
-            )
-            .unwrap();
-
-        self.renderer
-            .render(&self.atlas, &self.viewport, render_pass)
-            .unwrap();
+        while self.renderers.len() < layers.len() {
+            let renderer =
+                TextRenderer::new(&mut self.atlas, device, MultisampleState::default(), None);
+            self.renderers
+                .push((renderer, Viewport::new(device, &self.cache)));
+        }
+
+        for ((renderer, viewport), ([width, height], text)) in self.renderers.iter_mut().zip(layers)
+        {
+            viewport.update(queue, glyphon::Resolution { width, height });
+            renderer
+                .prepare(
+                    device,
+                    queue,
+                    font_system,
+                    &mut self.atlas,
+                    viewport,
+                    text,
+                    &mut self.swash_cache,
+                )
+                .unwrap();
+        }
+    }
+
+    pub fn render(&self, layer: usize, render_pass: &mut wgpu::RenderPass) {
+        let (renderer, viewport) = &self.renderers[layer];
+        renderer.render(&self.atlas, viewport, render_pass).unwrap();
     }
 }
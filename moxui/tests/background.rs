@@ -0,0 +1,157 @@
+use calc_units::Units;
+use moxui::{
+    background::{BackgroundBox, BackgroundImage, BackgroundRepeat, BackgroundSize},
+    html::Document,
+    images::Image,
+    tree::node::Node,
+};
+
+// A 2x1 PNG with a red and a blue pixel.
+const PNG: &[u8] = &[
+    137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 2, 0, 0, 0, 1, 8, 6, 0,
+    0, 0, 244, 34, 127, 138, 0, 0, 0, 14, 73, 68, 65, 84, 120, 156, 99, 248, 207, 192, 0, 66, 255,
+    1, 15, 249, 3, 253, 133, 17, 153, 118, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
+];
+
+#[test]
+fn test_image_decoding() {
+    let image = Image::from_bytes(PNG).unwrap();
+    assert_eq!((image.width(), image.height()), (2, 1));
+    assert_eq!(image.clone(), image);
+    assert_ne!(Image::from_bytes(PNG).unwrap(), image);
+
+    assert!(Image::from_bytes(b"not an image").is_err());
+}
+
+#[test]
+fn test_background_shorthand() {
+    let path = std::env::temp_dir().join("moxui-background-test.png");
+    std::fs::write(&path, PNG).unwrap();
+
+    let document = Document::parse(&format!(
+        r#"<div style="background: url('{}') right 10px / cover no-repeat content-box, linear-gradient(red, blue) repeat-x padding-box border-box, #00f"></div>"#,
+        path.display()
+    ))
+    .unwrap();
+    let node = document.build(Node::default());
+    let background = &node.children[0].background;
+
+    assert_eq!(background.image.len(), 2);
+    assert!(matches!(&background.image[0], BackgroundImage::Image(image) if image.width() == 2));
+    assert!(matches!(background.image[1], BackgroundImage::Gradient(_)));
+    assert_eq!(
+        background.position[0],
+        [Units::Perc(100.0), Units::Px(10.0)]
+    );
+    assert_eq!(background.size[0], BackgroundSize::Cover);
+    assert_eq!(background.repeat[0], [BackgroundRepeat::NoRepeat; 2]);
+    assert_eq!(
+        background.repeat[1],
+        [BackgroundRepeat::Repeat, BackgroundRepeat::NoRepeat]
+    );
+    assert_eq!(
+        background.origin,
+        [BackgroundBox::ContentBox, BackgroundBox::PaddingBox]
+    );
+    assert_eq!(
+        background.clip,
+        [BackgroundBox::ContentBox, BackgroundBox::BorderBox]
+    );
+    assert_eq!(node.children[0].background_color, [0.0, 0.0, 1.0, 1.0]);
+}
+
+#[test]
+fn test_background_images_shared() {
+    let directory = std::env::temp_dir().join("moxui-background-shared-test");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("tile.png"), PNG).unwrap();
+    let path = directory.join("page.html");
+    std::fs::write(
+        &path,
+        r#"<style>div { background-image: url(tile.png) }</style>
+        <div></div><div></div><img src="tile.png">
+        <p style="background-image: url(missing.png), linear-gradient(red, blue)"></p>"#,
+    )
+    .unwrap();
+
+    // Every mention of the image, relative to the document, shares one decoding.
+    let document = Document::open(&path).unwrap();
+    let node = document.build(Node::default());
+    let BackgroundImage::Image(image) = &node.children[0].background.image[0] else {
+        panic!("no background image");
+    };
+    assert_eq!(
+        node.children[1].background.image[0],
+        node.children[0].background.image[0]
+    );
+    assert_eq!(node.children[2].image.as_ref(), Some(image));
+    let rebuilt = document.build(Node::default());
+    assert_eq!(
+        rebuilt.children[0].background.image,
+        node.children[0].background.image
+    );
+
+    // An image that can't be loaded leaves no layer.
+    let layers = &node.children[3].background.image;
+    assert_eq!(layers.len(), 1);
+    assert!(matches!(layers[0], BackgroundImage::Gradient(_)));
+}
+
+#[test]
+fn test_background_longhands() {
+    let document = Document::parse(
+        r#"<div style="background-image: linear-gradient(red, blue), radial-gradient(red, blue);
+            background-size: 10px, 50% auto; background-repeat: space round;
+            background-position: center; background-clip: content-box"></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+    let background = &node.children[0].background;
+
+    assert_eq!(background.image.len(), 2);
+    assert_eq!(
+        background.size,
+        [
+            BackgroundSize::Size([Units::Px(10.0), Units::Auto]),
+            BackgroundSize::Size([Units::Perc(50.0), Units::Auto]),
+        ]
+    );
+    assert_eq!(
+        background.repeat,
+        [[BackgroundRepeat::Space, BackgroundRepeat::Round]]
+    );
+    assert_eq!(
+        background.position,
+        [[Units::Perc(50.0), Units::Perc(50.0)]]
+    );
+    assert_eq!(background.clip, [BackgroundBox::ContentBox]);
+}
+
+#[test]
+fn test_mask_longhands() {
+    use moxui::mask::MaskMode;
+
+    let document = Document::parse(
+        r#"<div style="mask-image: linear-gradient(black 80%, transparent), none;
+            mask-size: 100% 20px; mask-repeat: no-repeat repeat;
+            mask-position: bottom; mask-mode: luminance, alpha"></div>
+            <div style="mask-mode: bogus"></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+    let mask = &node.children[0].mask;
+
+    assert_eq!(mask.image.len(), 1);
+    assert!(matches!(mask.image[0], BackgroundImage::Gradient(_)));
+    assert_eq!(
+        mask.size,
+        [BackgroundSize::Size([Units::Perc(100.0), Units::Px(20.0)])]
+    );
+    assert_eq!(
+        mask.repeat,
+        [[BackgroundRepeat::NoRepeat, BackgroundRepeat::Repeat]]
+    );
+    assert_eq!(mask.position, [[Units::Perc(50.0), Units::Perc(100.0)]]);
+    assert_eq!(mask.mode, [MaskMode::Luminance, MaskMode::Alpha]);
+    assert_eq!(node.children[1].mask.mode, [MaskMode::MatchSource]);
+}
@@ -0,0 +1,8 @@
+use calc_macro::ui;
+
+fn main() {
+    let style = "padding: 4px";
+    let _ = ui! {
+        div(style = {style})
+    };
+}
@@ -0,0 +1,8 @@
+use calc_macro::ui;
+
+fn main() {
+    let _ = ui! {
+        "text"
+        div
+    };
+}
@@ -0,0 +1,7 @@
+use calc_macro::ui;
+
+fn main() {
+    let _ = ui! {
+        div(style = "colour: red")
+    };
+}
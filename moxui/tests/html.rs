@@ -0,0 +1,743 @@
+use calc_units::Units;
+use glyphon::{fontdb, FamilyOwned};
+use moxui::{
+    fonts::{FontFace, FontSource, FontVariant, Fonts},
+    html::Document,
+    rectangle::Display,
+    text::{Hyphens, OverflowWrap, TextAlign, WhiteSpace, WordBreak},
+    tree::{node::Node, State},
+};
+
+#[test]
+fn test_html_structure() {
+    let document = Document::parse(
+        r#"<!DOCTYPE html>
+        <div id="main" class="row wide">
+            <h1>Title</h1>
+            <p>Hello <span>big</span> world</p>
+            <br>
+            <img/>
+        </div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    assert_eq!(node.children.len(), 1);
+    let div = &node.children[0];
+    assert_eq!(div.id.as_deref(), Some("main"));
+    assert_eq!(div.class, ["row", "wide"]);
+    assert_eq!(div.children.len(), 5);
+
+    let h1 = &div.children[0];
+    assert!(h1.content.is_some());
+    assert_eq!(h1.style.font_size, Units::Em(2.0));
+
+    let p = &div.children[1];
+    assert!(p.content.is_none());
+    assert_eq!(p.children.len(), 3);
+    assert!(p.children.iter().all(|child| child.content.is_some()));
+    assert!(p.children[0].style.display == Display::Inline);
+
+    // A line break is inline text, followed by the space before the image.
+    let br = &div.children[2];
+    assert!(br.style.display == Display::Inline);
+    assert_eq!(br.content.as_deref(), Some("\u{2029}"));
+    assert!(div.children[4].style.display == Display::InlineBlock);
+}
+
+#[test]
+fn test_html_line_break() {
+    let document = Document::parse("<p>one <br> two<br></p><div><br></div>").unwrap();
+    let mut node = document.build(Node::default());
+    let state = State {
+        root_font_size: 16.0,
+        viewport: (800.0, 600.0),
+        scroll: (0.0, 0.0),
+        dpi: 96.0,
+    };
+    node.layout(&state, &mut Fonts::new(glyphon::FontSystem::new()));
+
+    // The spaces around the break collapse away, and a break at the end adds no line.
+    let lines = |node: &Node| {
+        let buffer = &node.text.as_ref().unwrap().buffer;
+        buffer
+            .lines
+            .iter()
+            .map(|line| line.text().trim_end().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(lines(&node.children[0]), ["one", "two"]);
+    // A break on its own is an empty line.
+    assert_eq!(lines(&node.children[1]), [""]);
+    assert!(node.children[1].height > 0.0);
+}
+
+#[test]
+fn test_html_style_cascade() {
+    let document = Document::parse(
+        r#"<style>
+            div span { color: red; padding: 1px }
+            .box > span { padding: 2px }
+            #special { padding: 3px }
+        </style>
+        <div class="box">
+            <span id="special" style="width: 10px">a</span>
+            <span>b</span>
+            <p><span>c</span></p>
+        </div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let div = &node.children[0];
+    assert_eq!(div.children[0].style.padding[0], Units::Px(3.0));
+    assert_eq!(div.children[0].style.width, Units::Px(10.0));
+    // The space between the spans is kept.
+    assert_eq!(div.children[1].content.as_deref(), Some(" "));
+    assert_eq!(div.children[2].style.padding[0], Units::Px(2.0));
+    assert_eq!(
+        div.children[2].style.font_color,
+        glyphon::Color::rgb(255, 0, 0)
+    );
+
+    let nested = &div.children[3].children[0];
+    assert_eq!(nested.style.padding[0], Units::Px(1.0));
+}
+
+#[test]
+fn test_html_nested_at_rules() {
+    let document = Document::parse(
+        r#"<style>
+            @import "theme.css";
+            @media (min-width: 600px) { p { padding: 9px } div { padding: 9px } }
+            @supports (display: grid) { @media print { p { padding: 9px } } }
+            p { padding: 1px; font-family: "{}" }
+            div { padding: 2px }
+        </style>
+        <div><p>a</p></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let div = &node.children[0];
+    assert_eq!(div.style.padding[0], Units::Px(2.0));
+    assert_eq!(div.children[0].style.padding[0], Units::Px(1.0));
+}
+
+#[test]
+fn test_html_implied_end_tags() {
+    let document = Document::parse(
+        r#"<p>First
+        <p>Second
+        <div>Block</div>
+        <ul><li>One<li>Two</ul>
+        <table><tr><td>A<td>B<tr><th>C</table>
+        <select><option>X<option>Y</select>
+        <p>Last"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let text = node
+        .children
+        .iter()
+        .map(|child| child.content.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(text[..3], [Some("First"), Some("Second"), Some("Block")]);
+    assert_eq!(node.children.len(), 7);
+    assert_eq!(node.children[3].children.len(), 2);
+
+    let rows = &node.children[4].children;
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].children[1].content.as_deref(), Some("B"));
+    assert_eq!(rows[1].children[0].content.as_deref(), Some("C"));
+    assert_eq!(node.children[5].children.len(), 2);
+    assert_eq!(node.children[6].content.as_deref(), Some("Last"));
+}
+
+#[test]
+fn test_html_important() {
+    let document = Document::parse(
+        r#"<style>
+            p { padding: 1px !important; width: 5px; color: red ! IMPORTANT }
+            #a { padding: 2px; width: 6px; color: blue }
+        </style>
+        <p id="a" style="padding: 3px; width: 7px !important; height: 1px !important; height: 2px">
+            a
+        </p>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let p = &node.children[0];
+    assert_eq!(p.style.padding[0], Units::Px(1.0));
+    assert_eq!(p.style.width, Units::Px(7.0));
+    assert_eq!(p.style.height, Units::Px(1.0));
+    assert_eq!(p.style.font_color, glyphon::Color::rgb(255, 0, 0));
+}
+
+#[test]
+fn test_html_declarations() {
+    // Semicolons and colons in strings and functions stay in their declaration, malformed
+    // declarations are dropped without taking their neighbors along.
+    let document = Document::parse(
+        r#"<p style='font-family: "a;b", serif; width: 4px oops!; : 1px; height: 3px;;'>a</p>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let p = &node.children[0];
+    assert_eq!(
+        p.style.font_family,
+        [FamilyOwned::Name("a;b".to_string()), FamilyOwned::Serif]
+    );
+    assert_eq!(p.style.width, Units::Auto);
+    assert_eq!(p.style.height, Units::Px(3.0));
+}
+
+#[test]
+fn test_html_errors() {
+    assert!(Document::parse("<div><span></div>").is_err());
+    assert!(Document::parse("<video></video>").is_err());
+    assert!(Document::parse("<p><span>a</p>").is_err());
+    assert!(Document::parse("<div>").is_err());
+}
+
+#[test]
+fn test_html_box_shadow() {
+    let document = Document::parse(
+        r#"<div style="box-shadow: 2px 4px 6px rgba(0, 0, 0, 0.5), inset 0 0 1em 2px blue"></div>
+        <div style="box-shadow: 1px"></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let shadows = &node.children[0].box_shadow;
+    assert_eq!(shadows.len(), 2);
+    assert_eq!(shadows[0].offset, [Units::Px(2.0), Units::Px(4.0)]);
+    assert_eq!(shadows[0].blur, Units::Px(6.0));
+    assert_eq!(shadows[0].spread, Units::Px(0.0));
+    assert!(!shadows[0].inset);
+    assert!(shadows[1].inset);
+    assert_eq!(shadows[1].blur, Units::Em(1.0));
+    assert_eq!(shadows[1].spread, Units::Px(2.0));
+    assert_eq!(shadows[1].color, Some([0.0, 0.0, 1.0, 1.0]));
+
+    assert!(node.children[1].box_shadow.is_empty());
+}
+
+#[test]
+fn test_html_gradients() {
+    use moxui::{
+        background::BackgroundImage,
+        gradient::{Gradient, GradientKind, LinearDirection, RadialShape, RadialSize},
+    };
+
+    fn gradient(image: &BackgroundImage) -> &Gradient {
+        match image {
+            BackgroundImage::Gradient(gradient) => gradient,
+            BackgroundImage::Image(_) => panic!("expected a gradient"),
+        }
+    }
+
+    let document = Document::parse(
+        r#"<div style="background: linear-gradient(to top right, red, blue 80%), #fff"></div>
+        <div style="background-image: repeating-radial-gradient(circle 10px at left top, red, blue 20%, green 20% 40%)"></div>
+        <div style="background-image: conic-gradient(from 90deg, red, blue 0.5turn)"></div>
+        <div style="background-image: linear-gradient(nonsense, red, blue)"></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let linear = &node.children[0];
+    assert_eq!(linear.background_color, [1.0; 4]);
+    assert_eq!(
+        gradient(&linear.background.image[0]).kind,
+        GradientKind::Linear(LinearDirection::Corner(1.0, -1.0))
+    );
+    assert_eq!(
+        gradient(&linear.background.image[0]).stops[0].position,
+        None
+    );
+    assert_eq!(
+        gradient(&linear.background.image[0]).stops[1].position,
+        Some(Units::Perc(80.0))
+    );
+
+    let radial = gradient(&node.children[1].background.image[0]);
+    assert!(radial.repeating);
+    assert_eq!(radial.stops.len(), 4);
+    assert_eq!(
+        radial.kind,
+        GradientKind::Radial {
+            shape: RadialShape::Circle,
+            size: RadialSize::Radii([Units::Px(10.0), Units::Px(10.0)]),
+            position: [Units::Perc(0.0), Units::Perc(0.0)],
+        }
+    );
+
+    let conic = gradient(&node.children[2].background.image[0]);
+    assert_eq!(conic.stops[1].position, Some(Units::Perc(50.0)));
+    assert!(matches!(conic.kind, GradientKind::Conic { from, .. } if from == 90.0));
+
+    assert!(node.children[3].background.image.is_empty());
+}
+
+#[test]
+fn test_html_border_style() {
+    use moxui::rectangle::BorderStyle;
+
+    let document = Document::parse(
+        r#"<div style="border: 2px dashed red; border-left-style: double"></div>
+        <div style="border-width: 4px; border-style: dotted none"></div>
+        <div style="border-top: 1px blue; color: lime"></div>
+        <div style="border: 1px solid; border-left-color: blue"></div>"#,
+    )
+    .unwrap();
+    let mut node = document.build(Node::default());
+    let state = State {
+        root_font_size: 16.0,
+        viewport: (800.0, 600.0),
+        scroll: (0.0, 0.0),
+        dpi: 96.0,
+    };
+    let mut fonts = Fonts::new(glyphon::FontSystem::new_with_locale_and_db(
+        "en-US".to_string(),
+        Default::default(),
+    ));
+    node.layout(&state, &mut fonts);
+
+    assert_eq!(
+        node.children[0].style.border_style,
+        [
+            BorderStyle::Dashed,
+            BorderStyle::Dashed,
+            BorderStyle::Dashed,
+            BorderStyle::Double
+        ]
+    );
+    assert_eq!(
+        node.children[1].style.border_style,
+        [
+            BorderStyle::Dotted,
+            BorderStyle::None,
+            BorderStyle::Dotted,
+            BorderStyle::None
+        ]
+    );
+    // `none` takes the width away.
+    assert_eq!(node.children[1].border.size, [4.0, 0.0, 4.0, 0.0]);
+    assert_eq!(node.children[1].get_extents(&state).height, 8.0);
+    // The shorthands reset what they leave out: the style to `none`, the color to
+    // `currentColor`.
+    assert_eq!(node.children[2].style.border_style[0], BorderStyle::None);
+    assert_eq!(node.children[2].border.size[0], 0.0);
+    assert_eq!(
+        node.children[2].border.top_color,
+        Some([0.0, 0.0, 1.0, 1.0])
+    );
+    assert_eq!(node.children[3].style.border_style[0], BorderStyle::Solid);
+    assert_eq!(node.children[3].border.top_color, None);
+    assert_eq!(
+        node.children[3].border.left_color,
+        Some([0.0, 0.0, 1.0, 1.0])
+    );
+}
+
+#[test]
+fn test_html_opacity() {
+    let document = Document::parse(
+        r#"<div style="opacity: 0.25; background-color: red"></div>
+        <div style="opacity: 40%"></div>
+        <div style="opacity: 2"></div>
+        <div></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    assert_eq!(node.children[0].opacity, 0.25);
+    assert_eq!(node.children[0].background_color, [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(node.children[1].opacity, 0.4);
+    assert_eq!(node.children[2].opacity, 1.0);
+    assert_eq!(node.children[3].opacity, 1.0);
+}
+
+#[test]
+fn test_html_filter() {
+    use moxui::filter::Filter;
+
+    let document = Document::parse(
+        r#"<div style="filter: blur(2px) grayscale(50%) hue-rotate(0.25turn) drop-shadow(1px 2px 3px red)"></div>
+        <div style="filter: invert() opacity(0.5)"></div>
+        <div style="filter: drop-shadow(1px 2px 3px 4px)"></div>
+        <div style="filter: none"></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    assert_eq!(
+        node.children[0].filter,
+        [
+            Filter::Blur(Units::Px(2.0)),
+            Filter::Grayscale(0.5),
+            Filter::HueRotate(90.0),
+            Filter::DropShadow {
+                offset: [Units::Px(1.0), Units::Px(2.0)],
+                blur: Units::Px(3.0),
+                color: Some([1.0, 0.0, 0.0, 1.0]),
+            }
+        ]
+    );
+    assert_eq!(
+        node.children[1].filter,
+        [Filter::Invert(1.0), Filter::Opacity(0.5)]
+    );
+    assert!(node.children[2].filter.is_empty());
+    assert!(node.children[3].filter.is_empty());
+}
+
+#[test]
+fn test_html_backdrop_filter() {
+    use moxui::filter::Filter;
+
+    let document = Document::parse(
+        r#"<div style="backdrop-filter: blur(1em) saturate(180%); filter: none"></div>
+        <div style="backdrop-filter: blur(4px) bogus(1)"></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    assert_eq!(
+        node.children[0].backdrop_filter,
+        [Filter::Blur(Units::Em(1.0)), Filter::Saturate(1.8)]
+    );
+    assert!(node.children[0].filter.is_empty());
+    assert!(node.children[1].backdrop_filter.is_empty());
+}
+
+#[test]
+fn test_html_transform() {
+    use moxui::transform::Transform;
+
+    let document = Document::parse(
+        r#"<div style="transform: translate(10px, 50%) rotate(0.5turn) scale(2) skewY(45deg); transform-origin: left top"></div>
+        <div style="transform: matrix(1, 0, 0, 1, 5, 6) scaleX(150%) translateY(1em)"></div>
+        <div style="transform: rotate(45deg) bogus(1)"></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    assert_eq!(
+        node.children[0].transform,
+        [
+            Transform::Translate(Units::Px(10.0), Units::Perc(50.0)),
+            Transform::Rotate(180.0),
+            Transform::Scale(2.0, 2.0),
+            Transform::Skew(0.0, 45.0),
+        ]
+    );
+    assert_eq!(
+        node.children[0].transform_origin,
+        [Units::Perc(0.0), Units::Perc(0.0)]
+    );
+    assert_eq!(
+        node.children[1].transform,
+        [
+            Transform::Matrix([1.0, 0.0, 0.0, 1.0, 5.0, 6.0]),
+            Transform::Scale(1.5, 1.0),
+            Transform::Translate(Units::Px(0.0), Units::Em(1.0)),
+        ]
+    );
+    assert_eq!(
+        node.children[1].transform_origin,
+        [Units::Perc(50.0), Units::Perc(50.0)]
+    );
+    assert!(node.children[2].transform.is_empty());
+}
+
+#[test]
+fn test_html_transform_3d() {
+    use moxui::transform::{BackfaceVisibility, Transform, TransformStyle};
+
+    let document = Document::parse(
+        r#"<div style="perspective: 800px; perspective-origin: right 25%; transform-style: preserve-3d">
+            <div style="transform: rotateY(180deg) translateZ(2em) rotate3d(1, 1, 0, 0.5turn) scale3d(1, 2, 3); backface-visibility: hidden"></div>
+            <div style="transform: perspective(100px) matrix3d(1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1) rotateZ(90deg)"></div>
+        </div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let container = &node.children[0];
+    assert_eq!(container.perspective, Some(Units::Px(800.0)));
+    assert_eq!(
+        container.perspective_origin,
+        [Units::Perc(100.0), Units::Perc(25.0)]
+    );
+    assert_eq!(container.transform_style, TransformStyle::Preserve3d);
+
+    let card = &container.children[0];
+    assert_eq!(
+        card.transform,
+        [
+            Transform::Rotate3d([0.0, 1.0, 0.0], 180.0),
+            Transform::Translate3d(Units::Px(0.0), Units::Px(0.0), Units::Em(2.0)),
+            Transform::Rotate3d([1.0, 1.0, 0.0], 180.0),
+            Transform::Scale3d(1.0, 2.0, 3.0),
+        ]
+    );
+    assert_eq!(card.backface_visibility, BackfaceVisibility::Hidden);
+
+    let mut identity = [0.0; 16];
+    identity[0] = 1.0;
+    identity[5] = 1.0;
+    identity[10] = 1.0;
+    identity[15] = 1.0;
+    assert_eq!(
+        container.children[1].transform,
+        [
+            Transform::Perspective(Units::Px(100.0)),
+            Transform::Matrix3d(identity),
+            Transform::Rotate(90.0),
+        ]
+    );
+    assert_eq!(container.children[1].transform_style, TransformStyle::Flat);
+}
+
+#[test]
+fn test_html_clip_path() {
+    use moxui::clip::{ClipPath, FillRule, ShapeRadius};
+
+    let document = Document::parse(
+        r#"<div style="clip-path: circle(50% at right 25%)"></div>
+        <div style="clip-path: inset(10px 5% round 4px 8px)"></div>
+        <div style="clip-path: ellipse(farthest-side 2em)"></div>
+        <div style="clip-path: polygon(evenodd, 0 0, 100% 0, 80% 100%, 0 100%)"></div>
+        <div style="clip-path: path('M 0 0 L 10 0 L 0 10 Z')"></div>
+        <div style="clip-path: path('M 0 0 L 10 0 Z&quot; onload=&quot;')"></div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    assert_eq!(
+        node.children[0].clip_path,
+        Some(ClipPath::Circle {
+            radius: ShapeRadius::Length(Units::Perc(50.0)),
+            center: [Units::Perc(100.0), Units::Perc(25.0)],
+        })
+    );
+    assert_eq!(
+        node.children[1].clip_path,
+        Some(ClipPath::Inset {
+            insets: [
+                Units::Px(10.0),
+                Units::Perc(5.0),
+                Units::Px(10.0),
+                Units::Perc(5.0)
+            ],
+            radius: [
+                Units::Px(4.0),
+                Units::Px(8.0),
+                Units::Px(4.0),
+                Units::Px(8.0)
+            ],
+        })
+    );
+    assert_eq!(
+        node.children[2].clip_path,
+        Some(ClipPath::Ellipse {
+            radii: [
+                ShapeRadius::FarthestSide,
+                ShapeRadius::Length(Units::Em(2.0))
+            ],
+            center: [Units::Perc(50.0), Units::Perc(50.0)],
+        })
+    );
+    assert_eq!(
+        node.children[3].clip_path,
+        Some(ClipPath::Polygon {
+            fill_rule: FillRule::EvenOdd,
+            points: vec![
+                [Units::Px(0.0), Units::Px(0.0)],
+                [Units::Perc(100.0), Units::Px(0.0)],
+                [Units::Perc(80.0), Units::Perc(100.0)],
+                [Units::Px(0.0), Units::Perc(100.0)],
+            ],
+        })
+    );
+    assert_eq!(
+        node.children[4].clip_path,
+        Some(ClipPath::Path {
+            fill_rule: FillRule::NonZero,
+            path: "M 0 0 L 10 0 L 0 10 Z".to_string(),
+        })
+    );
+    assert_eq!(node.children[5].clip_path, None);
+}
+
+#[test]
+fn test_html_font_face() {
+    let document = Document::parse(
+        r#"<style>
+            @font-face {
+                font-family: "Brand Sans";
+                src: local(BrandSans-Regular), url("fonts/brand.woff2") format("woff2");
+                font-weight: bold;
+                font-style: italic;
+                unicode-range: U+0-7F, U+4??, U+1F600;
+            }
+            @font-face { font-family: Icons; src: url(icons.ttf); font-weight: 100 900 }
+            @font-face { font-family: Missing }
+            p { font-family: 'Brand Sans', Icons, "serif", sans-serif }
+        </style>
+        <p>Hello</p>"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        document.font_faces(),
+        [
+            FontFace {
+                family: "Brand Sans".to_string(),
+                src: vec![
+                    FontSource::Local("BrandSans-Regular".to_string()),
+                    FontSource::Url("fonts/brand.woff2".into()),
+                ],
+                weight: Some(fontdb::Weight::BOLD),
+                style: Some(fontdb::Style::Italic),
+                unicode_range: vec![0..=0x7f, 0x400..=0x4ff, 0x1f600..=0x1f600],
+            },
+            // A weight range for variable fonts keeps the weight the font declares.
+            FontFace {
+                family: "Icons".to_string(),
+                src: vec![FontSource::Url("icons.ttf".into())],
+                weight: None,
+                style: None,
+                unicode_range: Vec::new(),
+            },
+        ]
+    );
+
+    let node = document.build(Node::default());
+    assert_eq!(
+        node.children[0].style.font_family,
+        [
+            FamilyOwned::Name("Brand Sans".to_string()),
+            FamilyOwned::Name("Icons".to_string()),
+            FamilyOwned::Name("serif".to_string()),
+            FamilyOwned::SansSerif,
+        ]
+    );
+
+    // Like images, font files are found relative to the document.
+    let document = Document::parse_with_base(
+        "<style>@font-face { font-family: Icons; src: url(icons.ttf) }</style>",
+        "assets",
+    )
+    .unwrap();
+    assert_eq!(
+        document.font_faces()[0].src,
+        [FontSource::Url("assets/icons.ttf".into())]
+    );
+}
+
+#[test]
+fn test_html_font_properties() {
+    let document = Document::parse(
+        r#"<style>
+            p { font-weight: 300; font-style: oblique 10deg; font-stretch: 80% }
+            .caps { font-variant: small-caps; font-feature-settings: "c2sc" }
+            .axes { font-weight: 600; font-variation-settings: "wght" 650, "wdth" 120 }
+        </style>
+        <p>Light <b>bolder</b> <em>slanted</em></p>
+        <h1 class="caps">Title</h1>
+        <div class="axes">Axes</div>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let p = &node.children[0];
+    assert_eq!(p.style.font_weight, fontdb::Weight(300));
+    assert_eq!(p.style.font_style, fontdb::Style::Oblique);
+    assert_eq!(p.style.font_stretch, fontdb::Stretch::Condensed);
+    // Font properties are inherited, `bolder` relative to the parent's weight.
+    assert_eq!(p.children[1].style.font_weight, fontdb::Weight::NORMAL);
+    assert_eq!(p.children[1].style.font_stretch, fontdb::Stretch::Condensed);
+    assert_eq!(p.children[3].style.font_style, fontdb::Style::Italic);
+
+    let h1 = &node.children[1];
+    assert_eq!(h1.style.font_weight, fontdb::Weight::BOLD);
+    // The shaper can't apply features or vary fonts, so the settings are rejected rather
+    // than half applied.
+    assert_eq!(h1.style.font_variant, FontVariant::SmallCaps);
+    let attrs = node.children[2].style.font_attrs();
+    assert_eq!(attrs.weight, fontdb::Weight(600));
+    assert_eq!(attrs.stretch, fontdb::Stretch::Normal);
+}
+
+#[test]
+fn test_html_text_align() {
+    let document = Document::parse(
+        r#"<style>
+            div { text-align: justify; text-indent: 10% }
+            .end { text-align: end }
+        </style>
+        <div><p>Inherited</p><p class="end">End</p></div>
+        <p style="text-align: middle">Invalid</p>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let div = &node.children[0];
+    assert_eq!(div.style.text_indent, Units::Perc(10.0));
+    assert_eq!(div.children[0].style.text_align, TextAlign::Justify);
+    assert_eq!(div.children[0].style.text_indent, Units::Perc(10.0));
+    assert_eq!(div.children[1].style.text_align, TextAlign::End);
+    assert_eq!(node.children[1].style.text_align, TextAlign::Start);
+}
+
+#[test]
+fn test_html_white_space() {
+    let document = Document::parse(
+        r#"<style>
+            div { white-space: nowrap; word-break: break-all; overflow-wrap: anywhere; hyphens: none }
+            .wrap { white-space: break-spaces; word-break: keep-all; hyphens: auto }
+        </style>
+        <div><p>Inherited   text</p><p class="wrap">  Kept
+  text</p></div>
+        <pre>
+fn main() {
+    <b>loop</b> {}
+}</pre>"#,
+    )
+    .unwrap();
+    let node = document.build(Node::default());
+
+    let div = &node.children[0];
+    assert_eq!(div.children[0].style.white_space, WhiteSpace::Nowrap);
+    assert_eq!(div.children[0].style.word_break, WordBreak::BreakAll);
+    assert_eq!(div.children[0].style.overflow_wrap, OverflowWrap::Anywhere);
+    assert_eq!(div.children[0].style.hyphens, Hyphens::None);
+    assert_eq!(div.children[0].content.as_deref(), Some("Inherited text"));
+    assert_eq!(div.children[1].style.white_space, WhiteSpace::BreakSpaces);
+    assert_eq!(div.children[1].style.word_break, WordBreak::KeepAll);
+    assert_eq!(div.children[1].style.hyphens, Hyphens::Auto);
+    assert_eq!(div.children[1].content.as_deref(), Some("  Kept\n  text"));
+
+    // The line break after `<pre>` goes, the rest of its text is kept.
+    let pre = &node.children[1];
+    assert_eq!(pre.style.white_space, WhiteSpace::Pre);
+    assert_eq!(pre.style.font_family, [FamilyOwned::Monospace]);
+    let text = pre
+        .children
+        .iter()
+        .map(|child| {
+            child
+                .content
+                .clone()
+                .or_else(|| child.children[0].content.clone())
+                .unwrap()
+        })
+        .collect::<String>();
+    assert_eq!(text, "fn main() {\n    loop {}\n}");
+}
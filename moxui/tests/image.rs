@@ -0,0 +1,136 @@
+use calc_units::Units;
+use glyphon::{fontdb, FontSystem};
+use image::RgbaImage;
+use moxui::{
+    fonts::Fonts,
+    html::Document,
+    images::{Image, ObjectFit},
+    rectangle::Display,
+    tree::{node::Node, State},
+};
+
+static STATE: State = State {
+    root_font_size: 16.0,
+    viewport: (800.0, 600.0),
+    scroll: (0.0, 0.0),
+    dpi: 96.0,
+};
+
+// No fonts, as there is no text to shape, which saves scanning the system fonts.
+fn fonts() -> Fonts {
+    Fonts::new(FontSystem::new_with_locale_and_db(
+        "en-US".to_string(),
+        fontdb::Database::new(),
+    ))
+}
+
+fn image(width: u32, height: u32) -> Image {
+    Image::from_rgba(RgbaImage::new(width, height))
+}
+
+#[test]
+fn test_intrinsic_size() {
+    let icon = image(32, 16);
+    let mut root = Node::default()
+        .add_child(|node| node.set_image(icon.clone()))
+        .add_child(|node| {
+            node.set_image(icon.clone())
+                .set_size(Units::Px(64.0), Units::Auto)
+        })
+        .add_child(|node| {
+            node.set_image(icon.clone())
+                .set_size(Units::Auto, Units::Px(8.0))
+        })
+        .add_child(|node| {
+            node.set_image(icon.clone())
+                .set_size(Units::Px(10.0), Units::Px(10.0))
+        })
+        .add_child(|node| {
+            node.set_image(icon.clone())
+                .set_max_size(Units::Px(16.0), Units::Auto)
+        });
+    root.layout(&STATE, &mut fonts());
+
+    let sizes = root
+        .children
+        .iter()
+        .map(|child| (child.width, child.height))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sizes,
+        [
+            (32.0, 16.0),
+            (64.0, 32.0),
+            (16.0, 8.0),
+            (10.0, 10.0),
+            (16.0, 8.0)
+        ]
+    );
+    assert_eq!(root.height, 16.0 + 32.0 + 8.0 + 10.0 + 8.0);
+}
+
+#[test]
+fn test_inline_images() {
+    let icon = image(16, 16);
+    let mut root = Node::default()
+        .set_size(Units::Px(100.0), Units::Auto)
+        .add_child(|node| node.set_image(icon.clone()).set_display(Display::Inline))
+        .add_child(|node| {
+            node.set_image(icon.clone())
+                .set_display(Display::Inline)
+                .set_size(Units::Px(24.0), Units::Auto)
+        });
+    root.layout(&STATE, &mut fonts());
+
+    assert_eq!(root.children[1].x, 16.0);
+    assert_eq!(root.children[1].height, 24.0);
+    assert_eq!(root.height, 24.0);
+
+    root.update(root.children[0].handle, |node| node.set_image(image(40, 8)));
+    assert!(root.dirty.descendants);
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children[1].x, 40.0);
+}
+
+#[test]
+fn test_html_img() {
+    let path = std::env::temp_dir().join("moxui-image-test.png");
+    RgbaImage::new(4, 2).save(&path).unwrap();
+
+    let document = Document::parse(&format!(
+        r#"<div><img src="{}" style="display: block; height: 1em; object-fit: cover; object-position: left bottom"><img src="missing.png"></div>"#,
+        path.display()
+    ))
+    .unwrap();
+    let mut root = document.build(Node::default());
+    root.layout(&STATE, &mut fonts());
+
+    let img = &root.children[0].children[0];
+    assert!(img.image.is_some());
+    assert_eq!((img.width, img.height), (32.0, 16.0));
+    assert_eq!(img.style.object_fit, ObjectFit::Cover);
+    assert_eq!(
+        img.style.object_position,
+        [Units::Perc(0.0), Units::Perc(100.0)]
+    );
+    assert!(root.children[0].children[1].image.is_none());
+}
+
+#[test]
+fn test_html_img_base() {
+    let directory = std::env::temp_dir().join("moxui-image-base-test");
+    std::fs::create_dir_all(directory.join("images")).unwrap();
+    RgbaImage::new(4, 2)
+        .save(directory.join("images/a.png"))
+        .unwrap();
+    let path = directory.join("page.html");
+    std::fs::write(&path, r#"<img src="images/a.png">"#).unwrap();
+
+    // Paths are relative to the document, not the working directory.
+    let document = Document::open(&path).unwrap();
+    assert!(document.build(Node::default()).children[0].image.is_some());
+    let document = Document::parse(r#"<img src="images/a.png">"#).unwrap();
+    assert!(document.build(Node::default()).children[0].image.is_none());
+
+    assert!(Document::open(directory.join("missing.html")).is_err());
+}
@@ -75,10 +75,8 @@ impl<'window> ApplicationHandler for App<'window> {
                 };
 
                 match key {
-                    NamedKey::ArrowLeft => {
-                        if wgpu_ctx.index > 0 {
-                            wgpu_ctx.index -= 1;
-                        }
+                    NamedKey::ArrowLeft if wgpu_ctx.index > 0 => {
+                        wgpu_ctx.index -= 1;
                     }
                     NamedKey::ArrowRight => {
                         if wgpu_ctx.index == wgpu_ctx.trees.len() - 1 {
@@ -0,0 +1,113 @@
+use calc_units::Units;
+use glyphon::{fontdb, FontSystem};
+use moxui::{
+    fonts::Fonts,
+    signal::Signal,
+    tree::{node::Node, State},
+};
+
+static STATE: State = State {
+    root_font_size: 16.0,
+    viewport: (800.0, 600.0),
+    scroll: (0.0, 0.0),
+    dpi: 96.0,
+};
+
+// No fonts, as there is no text to shape, which saves scanning the system fonts.
+fn fonts() -> Fonts {
+    Fonts::new(FontSystem::new_with_locale_and_db(
+        "en-US".to_string(),
+        fontdb::Database::new(),
+    ))
+}
+
+#[test]
+fn test_computed_signal() {
+    let first = Signal::new(String::from("Ada"));
+    let last = Signal::new(String::from("Lovelace"));
+    let full = Signal::computed(&[&first, &last], {
+        let (first, last) = (first.clone(), last.clone());
+        move || format!("{} {}", first.get(), last.get())
+    });
+    let length = full.map(|full| full.len());
+
+    assert_eq!(full.get(), "Ada Lovelace");
+    last.set(String::from("Byron"));
+    assert_eq!(full.get(), "Ada Byron");
+    assert_eq!(length.get(), 9);
+}
+
+#[test]
+fn test_bound_nodes_marked_dirty() {
+    let height = Signal::new(10.0);
+    let alpha = Signal::new(1.0);
+
+    let mut root = Node::default()
+        .add_child(|node| {
+            node.bind(&height, |node, height| {
+                node.set_size(Units::Auto, Units::Px(*height))
+            })
+        })
+        .add_child(|node| {
+            node.set_size(Units::Auto, Units::Px(20.0))
+                .bind(&alpha, |node, alpha| {
+                    node.set_background_color(0.0, 0.0, 0.0, *alpha)
+                })
+        });
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children[1].y, 10.0);
+
+    assert!(!root.apply_signals());
+    alpha.set(0.5);
+    // Paint-only changes still report that something needs drawing.
+    assert!(root.apply_signals());
+    assert_eq!(root.children[1].background_color[3], 0.5);
+    assert!(!root.dirty.node && !root.dirty.descendants);
+    assert!(!root.children[1].dirty.node);
+
+    height.set(30.0);
+    root.apply_signals();
+    assert!(root.dirty.descendants);
+    assert!(root.children[0].dirty.node);
+    assert!(!root.children[1].dirty.node);
+
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children[1].y, 30.0);
+    assert_eq!(root.height, 50.0);
+}
+
+#[test]
+fn test_dropped_nodes_unsubscribe() {
+    let content = Signal::new("a");
+    let mut root = Node::default()
+        .add_child(|node| node.bind(&content, |node, content| node.set_content(content)));
+    let child = root.children[0].handle;
+
+    let removed = root.remove_child(child);
+    drop(removed);
+    content.set("b");
+    root.apply_signals();
+    assert!(root.children.is_empty());
+}
+
+#[test]
+fn test_signals_apply_to_their_own_tree() {
+    let content = Signal::new("a");
+    let mut bound = Node::default()
+        .add_child(|node| node.bind(&content, |node, content| node.set_content(content)));
+    let mut other = Node::default().add_child(|node| node.set_content("x"));
+
+    content.set("b");
+    assert!(!other.apply_signals());
+    assert!(bound.apply_signals());
+    assert!(!bound.apply_signals());
+
+    // A node outside any tree catches up once it is back in one.
+    let child = bound.children[0].handle;
+    let detached = bound.remove_child(child).unwrap();
+    content.set("c");
+    assert!(!bound.apply_signals());
+    bound.insert_child(bound.handle, 0, move |_| detached);
+    assert!(bound.apply_signals());
+    assert_eq!(bound.children[0].content.as_deref(), Some("c"));
+}
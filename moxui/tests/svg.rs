@@ -0,0 +1,94 @@
+use calc_units::Units;
+use glyphon::{fontdb, FontSystem};
+use moxui::{
+    fonts::Fonts,
+    html::Document,
+    svg::Svg,
+    tree::{node::Node, State},
+};
+
+static STATE: State = State {
+    root_font_size: 16.0,
+    viewport: (800.0, 600.0),
+    scroll: (0.0, 0.0),
+    dpi: 96.0,
+};
+
+// No fonts, as there is no text to shape, which saves scanning the system fonts.
+fn fonts() -> Fonts {
+    Fonts::new(FontSystem::new_with_locale_and_db(
+        "en-US".to_string(),
+        fontdb::Database::new(),
+    ))
+}
+
+const ICON: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 12" width="24" height="12">
+    <rect width="12" height="12" fill="currentColor"/>
+    <rect x="12" width="12" height="12" fill="#00ff00"/>
+</svg>"##;
+
+#[test]
+fn test_svg_parsing() {
+    let svg = Svg::from_bytes(ICON.as_bytes()).unwrap();
+    assert_eq!((svg.width(), svg.height()), (24.0, 12.0));
+    assert_eq!(svg.clone(), svg);
+
+    assert!(Svg::from_bytes(b"<div></div>").is_err());
+}
+
+#[test]
+fn test_svg_rasterize() {
+    let svg = Svg::from_bytes(ICON.as_bytes()).unwrap();
+
+    let image = svg.rasterize(48, 24, [255, 0, 0, 255]).unwrap();
+    assert_eq!((image.width(), image.height()), (48, 24));
+    assert_eq!(image.rgba().get_pixel(4, 12).0, [255, 0, 0, 255]);
+    assert_eq!(image.rgba().get_pixel(44, 12).0, [0, 255, 0, 255]);
+
+    let image = svg.rasterize(24, 12, [0, 0, 255, 255]).unwrap();
+    assert_eq!(image.rgba().get_pixel(2, 6).0, [0, 0, 255, 255]);
+    // Switching back to a color drawn before follows it too.
+    let image = svg.rasterize(12, 6, [255, 0, 0, 255]).unwrap();
+    assert_eq!(image.rgba().get_pixel(2, 3).0, [255, 0, 0, 255]);
+
+    assert!(svg.rasterize(0, 12, [0; 4]).is_none());
+}
+
+#[test]
+fn test_svg_sizing() {
+    let icon = Svg::from_bytes(ICON.as_bytes()).unwrap();
+    let mut root = Node::default()
+        .add_child(|node| node.set_svg(icon.clone()))
+        .add_child(|node| {
+            node.set_svg(icon.clone())
+                .set_size(Units::Auto, Units::Em(2.0))
+        });
+    root.layout(&STATE, &mut fonts());
+
+    assert_eq!(
+        (root.children[0].width, root.children[0].height),
+        (24.0, 12.0)
+    );
+    assert_eq!(
+        (root.children[1].width, root.children[1].height),
+        (64.0, 32.0)
+    );
+}
+
+#[test]
+fn test_html_svg() {
+    let path = std::env::temp_dir().join("moxui-svg-test.svg");
+    std::fs::write(&path, ICON).unwrap();
+
+    let document = Document::parse(&format!(
+        r#"<div><img src="{}" style="width: 48px"></div>"#,
+        path.display()
+    ))
+    .unwrap();
+    let mut root = document.build(Node::default());
+    root.layout(&STATE, &mut fonts());
+
+    let img = &root.children[0].children[0];
+    assert!(img.svg.is_some() && img.image.is_none());
+    assert_eq!((img.width, img.height), (48.0, 24.0));
+}
@@ -0,0 +1,790 @@
+use calc_units::Units;
+use glyphon::{cosmic_text::CacheKeyFlags, fontdb, Color, FamilyOwned, FontSystem};
+use moxui::{
+    fonts::Fonts,
+    html::Document,
+    rectangle::Display,
+    signal::Signal,
+    text::{Hyphens, OverflowWrap, TextAlign, TextSpan, WhiteSpace, WordBreak},
+    tree::{node::Node, State},
+};
+use std::rc::Rc;
+
+static STATE: State = State {
+    root_font_size: 16.0,
+    viewport: (800.0, 600.0),
+    scroll: (0.0, 0.0),
+    dpi: 96.0,
+};
+
+// Shaping needs at least one font, so this has the system fonts.
+fn fonts() -> Fonts {
+    Fonts::new(FontSystem::new())
+}
+
+fn block(height: f32) -> impl Fn(Node) -> Node {
+    move |node| node.set_size(Units::Auto, Units::Px(height))
+}
+
+#[test]
+fn test_relayout_after_update() {
+    let mut root = Node::default()
+        .add_child(block(10.0))
+        .add_child(|node| node.add_child(block(20.0)).add_child(block(20.0)));
+    root.layout(&STATE, &mut fonts());
+
+    let first = root.children[0].handle;
+    let nested = &root.children[1].children[1];
+    assert_eq!(nested.y, 30.0);
+    assert!(!root.dirty.node && !root.dirty.descendants);
+
+    assert!(root.update(first, |node| node.set_size(Units::Auto, Units::Px(50.0))));
+    assert!(root.dirty.descendants);
+    assert!(root.children[0].dirty.node);
+    assert!(!root.children[1].dirty.node && !root.children[1].dirty.descendants);
+
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children[1].children[1].y, 70.0);
+    assert_eq!(root.height, 90.0);
+}
+
+#[test]
+fn test_insert_remove_move() {
+    let mut root = Node::default()
+        .add_child(block(10.0))
+        .add_child(|node| node.set_id("list").add_child(block(20.0)));
+    root.layout(&STATE, &mut fonts());
+
+    let list = root.find_by_id("list").unwrap().handle;
+    let inserted = root.insert_child(list, 0, block(5.0)).unwrap();
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children[1].children[1].y, 15.0);
+
+    let first = root.children[0].handle;
+    assert!(root.move_child(first, list, usize::MAX));
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children.len(), 1);
+    assert_eq!(root.children[0].children[2].y, 25.0);
+
+    assert!(!root.move_child(list, inserted, 0));
+    assert!(root.remove_child(inserted).is_some());
+    assert!(root.find(inserted).is_none());
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children[0].children[1].y, 20.0);
+    assert!(root.remove_child(root.handle).is_none());
+}
+
+#[test]
+fn test_update_keeps_handle() {
+    let mut root = Node::default().add_child(block(10.0));
+    let child = root.children[0].handle;
+
+    assert!(root.update(child, |_| Node::default().set_id("rebuilt")));
+    assert_eq!(root.find(child).unwrap().id.as_deref(), Some("rebuilt"));
+    assert!(root.update(child, block(20.0)));
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.height, 20.0);
+}
+
+#[test]
+fn test_children_inherit() {
+    let color = Color::rgb(255, 0, 0);
+    let mut root = Node::default()
+        .set_font_color(color)
+        .set_font_family(FamilyOwned::Monospace)
+        .set_white_space(WhiteSpace::Pre)
+        .add_child(|node| node.set_id("added"));
+
+    let handle = root.handle;
+    let inserted = root
+        .insert_child(handle, 0, |node| node.set_id("inserted"))
+        .unwrap();
+    for child in [
+        root.find(inserted).unwrap(),
+        root.find_by_id("added").unwrap(),
+    ] {
+        assert_eq!(child.style.font_color, color);
+        assert_eq!(child.style.font_family, [FamilyOwned::Monospace]);
+        assert_eq!(child.style.white_space, WhiteSpace::Pre);
+    }
+}
+
+#[test]
+fn test_percentage_box_relayout() {
+    let mut root = Node::default().add_child(|node| {
+        node.set_size(Units::Px(200.0), Units::Auto)
+            .add_child(|node| {
+                let percent = Units::Perc(10.0);
+                node.set_padding(percent.clone(), percent.clone(), percent.clone(), percent)
+            })
+    });
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children[0].children[0].padding, [20.0; 4]);
+
+    // Only the parent changes, but its child's padding follows its width.
+    let parent = root.children[0].handle;
+    assert!(root.update(parent, |node| node.set_size(Units::Px(400.0), Units::Auto)));
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children[0].children[0].padding, [40.0; 4]);
+    assert_eq!(root.children[0].height, 80.0);
+}
+
+fn list(items: &[(&str, f32)]) -> Node {
+    items.iter().fold(Node::default(), |node, (key, height)| {
+        node.add_child(|node| block(*height)(node).set_key(key))
+    })
+}
+
+#[test]
+fn test_reconcile_keeps_matching_nodes() {
+    let mut root = list(&[("a", 10.0), ("b", 20.0)]).add_child(|node| node.set_content("x"));
+    root.layout(&STATE, &mut fonts());
+    let handles = root
+        .children
+        .iter()
+        .map(|child| child.handle)
+        .collect::<Vec<_>>();
+
+    root.reconcile(list(&[("a", 10.0), ("b", 20.0)]).add_child(|node| node.set_content("x")));
+    assert!(!root.dirty.node && !root.dirty.descendants);
+
+    root.reconcile(list(&[("b", 20.0), ("a", 15.0)]).add_child(|node| node.set_content("y")));
+    assert!(!root.dirty.node && root.dirty.descendants);
+    assert_eq!(root.children[0].handle, handles[1]);
+    assert_eq!(root.children[1].handle, handles[0]);
+    assert_eq!(root.children[2].handle, handles[2]);
+    assert!(!root.children[0].dirty.node);
+    assert!(root.children[1].dirty.node);
+    assert_eq!(root.children[2].content.as_deref(), Some("y"));
+
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(root.children[1].y, 20.0);
+
+    root.reconcile(list(&[("c", 5.0)]));
+    assert_eq!(root.children.len(), 1);
+    assert!(!handles.contains(&root.children[0].handle));
+}
+
+#[test]
+fn test_reconcile_reorder_keeps_siblings() {
+    let items = |keys: &[&str]| {
+        keys.iter().fold(Node::default(), |node, key| {
+            node.add_child(|node| node.set_key(key).set_content(key))
+        })
+    };
+    let mut root = items(&["a", "b", "c"]);
+    root.layout(&STATE, &mut fonts());
+    let buffer = root.children[0].text.as_ref().unwrap().buffer.clone();
+    let (b, c) = (root.children[1].y, root.children[2].y);
+
+    root.reconcile(items(&["a", "c", "b"]));
+    assert!(!root.dirty.node && root.dirty.descendants);
+    assert!(!root.children[0].dirty.node);
+    assert!(root.children[1].dirty.node != root.children[2].dirty.node);
+
+    // The sibling that stayed is neither shaped nor sized again, so its buffer isn't copied.
+    root.layout(&STATE, &mut fonts());
+    let text = root.children[0].text.as_ref().unwrap();
+    assert!(Rc::ptr_eq(&text.buffer, &buffer));
+    assert_eq!((root.children[1].y, root.children[2].y), (b, c));
+    assert_eq!(root.children[1].key.as_deref(), Some("c"));
+}
+
+#[test]
+fn test_reconcile_duplicate_keys() {
+    let mut root = list(&[("a", 10.0), ("a", 20.0), ("b", 30.0)]);
+    root.layout(&STATE, &mut fonts());
+    let handles = root
+        .children
+        .iter()
+        .map(|child| child.handle)
+        .collect::<Vec<_>>();
+
+    // Siblings sharing a key keep their order among each other.
+    root.reconcile(list(&[("b", 30.0), ("a", 10.0), ("a", 20.0), ("a", 40.0)]));
+    assert_eq!(root.children[0].handle, handles[2]);
+    assert_eq!(root.children[1].handle, handles[0]);
+    assert_eq!(root.children[2].handle, handles[1]);
+    assert!(!handles.contains(&root.children[3].handle));
+    assert!(!root.children[1].dirty.node && !root.children[2].dirty.node);
+}
+
+#[test]
+fn test_reconcile_painted_span() {
+    let paragraph = |opacity: f32| {
+        Node::default().add_child(move |node| {
+            node.add_child(|node| node.set_display(Display::Inline).set_content("Some "))
+                .add_child(move |node| {
+                    node.set_display(Display::Inline)
+                        .set_content("faded")
+                        .set_opacity(opacity)
+                })
+        })
+    };
+    let mut root = paragraph(1.0);
+    root.layout(&STATE, &mut fonts());
+    assert!(root.children[0].text.is_some());
+    assert!(root.children[0].children[1].text.is_none());
+
+    // Painted, the span has a box of its own and leaves the paragraph.
+    root.reconcile(paragraph(0.5));
+    assert!(root.children[0].dirty.node);
+    assert!(root.children[0].children[1].dirty.node);
+    root.layout(&STATE, &mut fonts());
+    assert!(root.children[0].text.is_none());
+    assert!(root.children[0].children[1].text.is_some());
+
+    root.reconcile(paragraph(1.0));
+    root.layout(&STATE, &mut fonts());
+    assert!(root.children[0].text.is_some());
+    assert!(root.children[0].children[1].text.is_none());
+
+    // Paint-only bindings move spans out of the paragraph too.
+    let alpha = Signal::new(0.0);
+    let mut root = Node::default().add_child(|node| {
+        node.add_child(|node| node.set_display(Display::Inline).set_content("Some "))
+            .add_child(|node| {
+                node.set_display(Display::Inline)
+                    .set_content("painted")
+                    .bind(&alpha, |node, alpha| {
+                        node.set_background_color(1.0, 0.0, 0.0, *alpha)
+                    })
+            })
+    });
+    root.layout(&STATE, &mut fonts());
+    alpha.set(1.0);
+    assert!(root.apply_signals());
+    assert!(root.children[0].dirty.node);
+    root.layout(&STATE, &mut fonts());
+    assert!(root.children[0].children[1].text.is_some());
+}
+
+#[test]
+fn test_hit_test_transforms() {
+    use moxui::transform::Transform;
+
+    let mut root = Node::default()
+        .add_child(|node| {
+            node.set_size(Units::Px(100.0), Units::Px(100.0))
+                .set_transform(vec![Transform::Rotate(45.0)])
+                .add_child(|node| {
+                    node.set_size(Units::Px(20.0), Units::Px(20.0))
+                        .set_transform(vec![Transform::Translate(
+                            Units::Px(40.0),
+                            Units::Perc(200.0),
+                        )])
+                })
+        })
+        .add_child(|node| node.set_size(Units::Px(100.0), Units::Px(10.0)));
+    root.layout(&STATE, &mut fonts());
+
+    let rotated = &root.children[0];
+    let nested = rotated.children[0].handle;
+    // The corners of the rotated square stick out past its layout box.
+    assert_eq!(root.hit_test(102.0, 50.0, &STATE), Some(rotated.handle));
+    assert_eq!(root.hit_test(2.0, 2.0, &STATE), Some(root.handle));
+    // Moved to the middle of the square, then rotated with it.
+    assert_eq!(root.hit_test(50.0, 50.0, &STATE), Some(nested));
+    assert_eq!(root.hit_test(10.0, 10.0, &STATE), Some(root.handle));
+    assert_eq!(
+        root.hit_test(5.0, 105.0, &STATE),
+        Some(root.children[1].handle)
+    );
+    assert_eq!(root.hit_test(900.0, 5.0, &STATE), None);
+}
+
+#[test]
+fn test_hit_test_3d() {
+    use moxui::transform::{BackfaceVisibility, Transform, TransformStyle};
+
+    let face = |node: Node| {
+        node.set_size(Units::Px(200.0), Units::Px(50.0))
+            .set_backface_visibility(BackfaceVisibility::Hidden)
+    };
+    let mut root = Node::default()
+        .set_perspective(Some(Units::Px(500.0)))
+        .add_child(|node| {
+            node.set_size(Units::Px(200.0), Units::Px(100.0))
+                .set_transform_style(TransformStyle::Preserve3d)
+                .set_transform(vec![Transform::Rotate3d([0.0, 1.0, 0.0], 180.0)])
+                .add_child(face)
+                .add_child(|node| {
+                    face(node).set_transform(vec![Transform::Rotate3d([0.0, 1.0, 0.0], 180.0)])
+                })
+        })
+        .add_child(|node| {
+            node.set_size(Units::Px(200.0), Units::Px(100.0))
+                .set_transform(vec![Transform::Rotate3d([1.0, 0.0, 0.0], 60.0)])
+        });
+    root.layout(&STATE, &mut fonts());
+
+    // The front face is turned away, the back face turned back around by the card.
+    let card = &root.children[0];
+    assert_eq!(root.hit_test(100.0, 25.0, &STATE), Some(card.handle));
+    assert_eq!(
+        root.hit_test(100.0, 75.0, &STATE),
+        Some(card.children[1].handle)
+    );
+
+    // Tilted away, the box is foreshortened around its middle.
+    let tilted = root.children[1].handle;
+    assert_eq!(root.hit_test(100.0, 150.0, &STATE), Some(tilted));
+    assert_eq!(root.hit_test(100.0, 105.0, &STATE), Some(root.handle));
+    assert_eq!(root.hit_test(100.0, 195.0, &STATE), Some(root.handle));
+}
+
+#[test]
+fn test_hit_test_clip_path() {
+    use moxui::clip::{ClipPath, FillRule, ShapeRadius};
+
+    let mut root = Node::default()
+        .add_child(|node| {
+            node.set_size(Units::Px(100.0), Units::Px(100.0))
+                .set_clip_path(Some(ClipPath::Circle {
+                    radius: ShapeRadius::ClosestSide,
+                    center: [Units::Perc(50.0), Units::Perc(50.0)],
+                }))
+                .add_child(block(100.0))
+        })
+        .add_child(|node| {
+            node.set_size(Units::Px(200.0), Units::Px(100.0))
+                .set_clip_path(Some(ClipPath::Polygon {
+                    fill_rule: FillRule::NonZero,
+                    points: vec![
+                        [Units::Px(0.0), Units::Px(0.0)],
+                        [Units::Perc(100.0), Units::Px(0.0)],
+                        [Units::Perc(75.0), Units::Perc(100.0)],
+                        [Units::Px(0.0), Units::Perc(100.0)],
+                    ],
+                }))
+        });
+    root.layout(&STATE, &mut fonts());
+
+    // Descendants are clipped along with the node.
+    let avatar = &root.children[0];
+    assert_eq!(
+        root.hit_test(50.0, 50.0, &STATE),
+        Some(avatar.children[0].handle)
+    );
+    assert_eq!(root.hit_test(5.0, 5.0, &STATE), Some(root.handle));
+    assert_eq!(
+        root.hit_test(95.0, 50.0, &STATE),
+        Some(avatar.children[0].handle)
+    );
+
+    // The angled edge of the banner cuts off its bottom right corner.
+    let banner = root.children[1].handle;
+    assert_eq!(root.hit_test(140.0, 190.0, &STATE), Some(banner));
+    assert_eq!(root.hit_test(190.0, 190.0, &STATE), Some(root.handle));
+    assert_eq!(root.hit_test(190.0, 110.0, &STATE), Some(banner));
+}
+
+#[test]
+fn test_font_face_fallback() {
+    // Any installed font will do, registered under the names of the stylesheet.
+    let system = FontSystem::new();
+    let face = system.db().faces().next().unwrap().id;
+    let data = system
+        .db()
+        .with_face_data(face, |data, _| data.to_vec())
+        .unwrap();
+    let path = std::env::temp_dir().join("moxui-font-face-test.ttf");
+    std::fs::write(&path, data).unwrap();
+
+    let document = Document::parse(&format!(
+        r#"<style>
+            @font-face {{ font-family: Brand; src: url({0}); unicode-range: U+41-5A }}
+            @font-face {{ font-family: Icons; src: url(missing.ttf), url({0}) }}
+            @font-face {{ font-family: Broken; src: url(missing.ttf) }}
+        </style>
+        <p style="font-family: Broken, Brand, Icons">Ab</p>"#,
+        path.display()
+    ))
+    .unwrap();
+
+    let mut fonts = Fonts::new(FontSystem::new_with_locale_and_db(
+        "en-US".to_string(),
+        fontdb::Database::new(),
+    ));
+    let loaded = document
+        .font_faces()
+        .iter()
+        .map(|face| fonts.load_font_face(face).is_ok())
+        .collect::<Vec<_>>();
+    assert_eq!(loaded, [true, true, false]);
+
+    let mut root = document.build(Node::default());
+    root.layout(&STATE, &mut fonts);
+
+    // Brand only covers capitals, so the rest falls back to Icons.
+    let text = root.children[0].text.as_ref().unwrap();
+    let families = text
+        .buffer
+        .layout_runs()
+        .flat_map(|run| run.glyphs.iter())
+        .map(|glyph| {
+            let face = fonts.font_system.db().face(glyph.font_id).unwrap();
+            face.families[0].0.split(" #").next().unwrap().to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(families, ["Brand", "Icons"]);
+}
+
+#[test]
+fn test_font_matching() {
+    let system = FontSystem::new();
+    let face = system.db().faces().next().unwrap().id;
+    let data = system
+        .db()
+        .with_face_data(face, |data, _| data.to_vec())
+        .unwrap();
+    let path = std::env::temp_dir().join("moxui-font-matching-test.ttf");
+    std::fs::write(&path, data).unwrap();
+
+    // One upright font declared at two weights.
+    let document = Document::parse(&format!(
+        r#"<style>
+            @font-face {{ font-family: Brand; src: url({0}); font-weight: 400; font-style: normal }}
+            @font-face {{ font-family: Brand; src: url({0}); font-weight: 700; font-style: normal }}
+            p {{ font-family: Brand }}
+        </style>
+        <p style="font-weight: 450">a</p>
+        <p style="font-weight: 600">a</p>
+        <p style="font-style: italic">a</p>
+        <p style="font-variant: small-caps">Ab</p>"#,
+        path.display()
+    ))
+    .unwrap();
+
+    let mut fonts = Fonts::new(FontSystem::new_with_locale_and_db(
+        "en-US".to_string(),
+        fontdb::Database::new(),
+    ));
+    document.font_faces().iter().for_each(|face| {
+        fonts.load_font_face(face).unwrap();
+    });
+
+    let mut root = document.build(Node::default());
+    root.layout(&STATE, &mut fonts);
+
+    let glyphs = |i: usize| {
+        let text = root.children[i].text.as_ref().unwrap();
+        text.buffer
+            .layout_runs()
+            .flat_map(|run| run.glyphs.iter())
+            .map(|glyph| {
+                let face = fonts.font_system.db().face(glyph.font_id).unwrap();
+                (face.weight.0, glyph.font_size, glyph.cache_key_flags)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Up to 500 lighter faces come first, above it heavier ones.
+    assert_eq!(glyphs(0)[0].0, 400);
+    assert_eq!(glyphs(1)[0].0, 700);
+    // Without an italic face the upright one is slanted.
+    assert_eq!(glyphs(2)[0].2, CacheKeyFlags::FAKE_ITALIC);
+    assert_eq!(glyphs(0)[0].2, CacheKeyFlags::empty());
+    // Lowercase letters become shrunken capitals.
+    let sizes = glyphs(3).iter().map(|glyph| glyph.1).collect::<Vec<_>>();
+    assert_eq!(sizes, [16.0, 16.0 * 0.7]);
+}
+
+#[test]
+fn test_rich_text_paragraph() {
+    let document = Document::parse(
+        r#"<p style="width: 150px">Some <b>bold</b> text and <span style="font-size: 2em">big words</span> that wrap</p>"#,
+    )
+    .unwrap();
+    let red = Color::rgb(255, 0, 0);
+    let mut root = document.build(Node::default()).add_child(|node| {
+        node.set_content("Plain ").set_rich_text(vec![TextSpan {
+            color: Some(red),
+            font_size: Some(Units::Px(24.0)),
+            ..TextSpan::new("red")
+        }])
+    });
+    root.layout(&STATE, &mut fonts());
+
+    // Inline children join the paragraph, which wraps at its width.
+    let p = &root.children[0];
+    assert!(p.children.iter().all(|child| child.text.is_none()));
+    let text = p.text.as_ref().unwrap();
+    let lines = text.buffer.layout_runs().count();
+    assert!(lines > 1);
+    assert!(text.buffer.layout_runs().all(|run| run.line_w <= 150.0));
+    let sizes = text
+        .buffer
+        .layout_runs()
+        .flat_map(|run| run.glyphs.iter().map(|glyph| glyph.font_size))
+        .collect::<Vec<_>>();
+    assert!(sizes.contains(&16.0) && sizes.contains(&32.0));
+    assert!(p.height >= text.extents().1);
+
+    let glyphs = root.children[1]
+        .text
+        .as_ref()
+        .unwrap()
+        .buffer
+        .layout_runs()
+        .flat_map(|run| run.glyphs.iter())
+        .map(|glyph| (glyph.font_size, glyph.color_opt))
+        .collect::<Vec<_>>();
+    assert_eq!(glyphs[0], (16.0, Some(Color::rgb(255, 255, 255))));
+    assert_eq!(glyphs[6], (24.0, Some(red)));
+}
+
+#[test]
+fn test_inline_size() {
+    // The block keeps the inline text from joining the paragraph of the root.
+    let mut root = Node::default()
+        .add_child(|node| {
+            node.set_display(Display::Inline)
+                .set_size(Units::Px(500.0), Units::Px(500.0))
+                .set_content("Inline")
+        })
+        .add_child(block(10.0));
+    root.layout(&STATE, &mut fonts());
+
+    // Inline boxes ignore `width` and `height` and take the size of their text.
+    let inline = &root.children[0];
+    let (width, height) = inline.text.as_ref().unwrap().extents();
+    assert!(width > 0.0 && width < 500.0);
+    assert_eq!((inline.width, inline.height), (width, height));
+}
+
+#[test]
+fn test_text_align() {
+    let paragraph = |align: TextAlign| {
+        move |node: Node| {
+            node.set_size(Units::Px(300.0), Units::Auto)
+                .set_text_align(align)
+                .set_content("Short")
+        }
+    };
+    let mut root = Node::default()
+        .add_child(paragraph(TextAlign::Center))
+        .add_child(paragraph(TextAlign::Right))
+        .add_child(|node| {
+            node.set_size(Units::Px(300.0), Units::Auto)
+                .set_text_indent(Units::Px(40.0))
+                .set_content("Indented")
+        })
+        .add_child(|node| {
+            let item = |node: Node| {
+                node.set_display(Display::InlineBlock)
+                    .set_size(Units::Px(50.0), Units::Px(10.0))
+            };
+            node.set_size(Units::Px(300.0), Units::Auto)
+                .set_text_align(TextAlign::Right)
+                .add_child(item)
+                .add_child(item)
+        });
+    root.layout(&STATE, &mut fonts());
+
+    let glyphs = |i: usize| {
+        let text = root.children[i].text.as_ref().unwrap();
+        let run = text.buffer.layout_runs().next().unwrap();
+        run.glyphs
+            .iter()
+            .filter(|glyph| !run.text[glyph.start..glyph.end].trim().is_empty())
+            .map(|glyph| (glyph.x, glyph.x + glyph.w))
+            .collect::<Vec<_>>()
+    };
+
+    let center = glyphs(0);
+    let (left, right) = (center[0].0, center.last().unwrap().1);
+    assert!((left - (300.0 - right)).abs() < 0.5);
+    assert!((glyphs(1).last().unwrap().1 - 300.0).abs() < 0.5);
+    // The indent is made of spaces before the first letter.
+    assert!((glyphs(2)[0].0 - 40.0).abs() < 0.5);
+
+    let row = &root.children[3].children;
+    assert_eq!([row[0].x, row[1].x], [200.0, 250.0]);
+}
+
+#[test]
+fn test_white_space() {
+    let paragraph = |text: &'static str, white_space: WhiteSpace| {
+        move |node: Node| {
+            node.set_size(Units::Px(80.0), Units::Auto)
+                .set_white_space(white_space)
+                .set_content(text)
+        }
+    };
+    let mut root = Node::default()
+        .add_child(paragraph("  one   two\n three  ", WhiteSpace::Normal))
+        .add_child(paragraph("one two three four five", WhiteSpace::Nowrap))
+        .add_child(paragraph("  one\n two", WhiteSpace::Pre))
+        .add_child(paragraph("one  \n  two   three", WhiteSpace::PreLine))
+        .add_child(|node| {
+            node.set_content("one ")
+                .set_rich_text(vec![TextSpan::new(" two\u{ad}three")])
+                .set_hyphens(Hyphens::None)
+        });
+    root.layout(&STATE, &mut fonts());
+
+    let lines = |i: usize| {
+        let buffer = &root.children[i].text.as_ref().unwrap().buffer;
+        buffer
+            .lines
+            .iter()
+            .map(|line| line.text().to_string())
+            .collect::<Vec<_>>()
+    };
+    let runs = |i: usize| {
+        let buffer = &root.children[i].text.as_ref().unwrap().buffer;
+        buffer.layout_runs().count()
+    };
+
+    assert_eq!(lines(0), ["one two three "]);
+    assert_eq!(lines(1), ["one two three four five"]);
+    assert_eq!(runs(1), 1);
+    assert_eq!(lines(2), ["  one", " two"]);
+    assert_eq!(lines(3), ["one", "two three"]);
+    // Spaces collapse across the spans of a paragraph.
+    assert_eq!(lines(4), ["one twothree"]);
+}
+
+#[test]
+fn test_break_spaces() {
+    let paragraph = |white_space: WhiteSpace| {
+        move |node: Node| {
+            node.set_size(Units::Px(60.0), Units::Auto)
+                .set_white_space(white_space)
+                .set_content("one                  two")
+        }
+    };
+    let mut root = Node::default()
+        .add_child(paragraph(WhiteSpace::PreWrap))
+        .add_child(paragraph(WhiteSpace::BreakSpaces));
+    root.layout(&STATE, &mut fonts());
+
+    // Whether every character of the text is drawn on some line.
+    let drawn = |i: usize| {
+        let buffer = &root.children[i].text.as_ref().unwrap().buffer;
+        let mut end = 0;
+        buffer
+            .layout_runs()
+            .flat_map(|run| run.glyphs)
+            .all(|glyph| std::mem::replace(&mut end, glyph.end) == glyph.start)
+    };
+
+    // Spaces hang at the ends of lines where they wrap, unless they take room there.
+    assert!(!drawn(0));
+    assert!(drawn(1));
+    let buffer = &root.children[1].text.as_ref().unwrap().buffer;
+    assert!(buffer.layout_runs().count() > 2);
+    assert!(buffer.layout_runs().all(|run| run.line_w <= 60.0));
+}
+
+#[test]
+fn test_keep_all() {
+    let paragraph = |word_break: WordBreak| {
+        move |node: Node| {
+            node.set_size(Units::Px(40.0), Units::Auto)
+                .set_word_break(word_break)
+                .set_content("日本語の文章です。次の文")
+        }
+    };
+    let mut root = Node::default()
+        .add_child(paragraph(WordBreak::Normal))
+        .add_child(paragraph(WordBreak::KeepAll));
+    root.layout(&STATE, &mut fonts());
+
+    let runs = |i: usize| {
+        let buffer = &root.children[i].text.as_ref().unwrap().buffer;
+        buffer
+            .layout_runs()
+            .map(|run| {
+                let range = run.glyphs[0].start..run.glyphs.last().unwrap().end;
+                run.text[range].replace('\u{2060}', "")
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert!(runs(0).len() > 2);
+    // Only the punctuation ending the sentence is a place to break.
+    assert_eq!(runs(1), ["日本語の文章です。", "次の文"]);
+}
+
+#[test]
+fn test_hyphens() {
+    let paragraph = |text: &'static str, hyphens: Hyphens| {
+        move |node: Node| {
+            node.set_size(Units::Px(80.0), Units::Auto)
+                .set_hyphens(hyphens)
+                .set_content(text)
+        }
+    };
+    let mut root = Node::default()
+        .add_child(paragraph("Incomprehensibilities", Hyphens::Auto))
+        .add_child(paragraph("Incompre\u{ad}hensibilities", Hyphens::Manual))
+        .add_child(paragraph("Incompre\u{ad}hensibilities", Hyphens::None));
+    root.layout(&STATE, &mut fonts());
+
+    let lines = |root: &Node, i: usize| {
+        let buffer = &root.children[i].text.as_ref().unwrap().buffer;
+        buffer
+            .layout_runs()
+            .map(|run| {
+                let end = run.glyphs.last().unwrap().end;
+                (run.text[run.glyphs[0].start..end].to_string(), run.line_w)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Lines broken inside a word end in a hyphen, which fits on them.
+    let auto = lines(&root, 0);
+    assert!(auto.len() > 1);
+    for (text, width) in &auto[..auto.len() - 1] {
+        assert!(text.ends_with("-\u{ad}"), "{text:?}");
+        assert!(*width <= 80.0);
+    }
+    let text = auto
+        .iter()
+        .map(|(text, _)| text.as_str())
+        .collect::<String>();
+    assert_eq!(text.replace(['-', '\u{ad}'], ""), "Incomprehensibilities");
+
+    let manual = lines(&root, 1);
+    assert_eq!(manual[0].0, "Incompre-\u{ad}");
+    assert_eq!(manual[1].0, "hensibilities");
+    assert_eq!(lines(&root, 2).len(), 1);
+
+    // Wrapping at another width puts the hyphens where lines end then.
+    let first = root.children[0].handle;
+    assert!(root.update(first, |node| node.set_size(Units::Px(400.0), Units::Auto)));
+    root.layout(&STATE, &mut fonts());
+    assert_eq!(lines(&root, 0).len(), 1);
+    assert!(!lines(&root, 0)[0].0.contains('-'));
+}
+
+#[test]
+fn test_word_break() {
+    let word = |word_break: WordBreak, overflow_wrap: OverflowWrap| {
+        move |node: Node| {
+            node.set_size(Units::Px(40.0), Units::Auto)
+                .set_word_break(word_break)
+                .set_overflow_wrap(overflow_wrap)
+                .set_content("Incomprehensibilities")
+        }
+    };
+    let mut root = Node::default()
+        .add_child(word(WordBreak::Normal, OverflowWrap::Normal))
+        .add_child(word(WordBreak::BreakAll, OverflowWrap::Normal))
+        .add_child(word(WordBreak::Normal, OverflowWrap::Anywhere))
+        .add_child(word(WordBreak::BreakWord, OverflowWrap::Normal));
+    root.layout(&STATE, &mut fonts());
+
+    let runs = |i: usize| {
+        let buffer = &root.children[i].text.as_ref().unwrap().buffer;
+        buffer.layout_runs().count()
+    };
+
+    assert_eq!(runs(0), 1);
+    assert!(runs(1) > 1);
+    assert!(runs(2) > 1);
+    assert!(runs(3) > 1);
+    assert!(root.children[1].height > root.children[0].height);
+}
@@ -0,0 +1,120 @@
+use calc_macro::ui;
+use calc_units::Units;
+use moxui::{
+    rectangle::{Display, Position},
+    text::{TextAlign, WhiteSpace},
+    tree::node::Node,
+};
+
+#[test]
+fn test_ui_nesting() {
+    let node = ui! {
+        div(class = "row wide", id = "main") {
+            span { "Hello" }
+            span
+        }
+    }(Node::default());
+
+    assert_eq!(node.children.len(), 1);
+    let div = &node.children[0];
+    assert_eq!(div.id.as_deref(), Some("main"));
+    assert_eq!(div.class, ["row", "wide"]);
+    assert!(div.style.display == Display::Block);
+    assert_eq!(div.children.len(), 2);
+    assert!(div.children[0].content.is_some());
+    assert!(div.children[0].style.display == Display::Inline);
+    assert!(div.children[1].content.is_none());
+}
+
+#[test]
+fn test_ui_mixed_text() {
+    let node = ui! {
+        p(style = "color: #f00") { "a " b { "b" } " c" {1} }
+    }(Node::default());
+
+    let p = &node.children[0];
+    assert!(p.content.is_none());
+    let text = p
+        .children
+        .iter()
+        .map(|child| child.content.as_deref().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(text, ["a ", "b", " c1"]);
+    assert!(p.children[0].style.display == Display::Inline);
+    assert_eq!(p.children[2].style.font_color, p.style.font_color);
+}
+
+#[test]
+fn test_ui_style() {
+    let node = ui! {
+        div(style = "padding: 4px 2em; width: 50%; position: absolute; top: 1rem; background-color: #f00; font-weight: bold; font-style: italic; text-align: center; text-indent: 1em; white-space: nowrap")
+    }(Node::default());
+
+    let div = &node.children[0];
+    assert_eq!(
+        div.style.padding,
+        [
+            Units::Px(4.0),
+            Units::Em(2.0),
+            Units::Px(4.0),
+            Units::Em(2.0)
+        ]
+    );
+    assert_eq!(div.style.width, Units::Perc(50.0));
+    assert_eq!(div.style.height, Units::Auto);
+    assert_eq!(div.style.position, Position::Absolute);
+    assert_eq!(div.style.top, Units::Rem(1.0));
+    assert_eq!(div.style.left, Units::Auto);
+    assert_eq!(div.background_color, [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(div.style.font_weight, glyphon::Weight::BOLD);
+    assert_eq!(div.style.font_style, glyphon::Style::Italic);
+    assert_eq!(div.style.text_align, TextAlign::Center);
+    assert_eq!(div.style.text_indent, Units::Em(1.0));
+    assert_eq!(div.style.white_space, WhiteSpace::Nowrap);
+}
+
+// Inline styles take whatever stylesheets take.
+#[test]
+fn test_ui_stylesheet_properties() {
+    let node = ui! {
+        div(style = "border-radius: 4px; border: 1px solid; color: hsl(120, 100%, 50%); outline: 2px red; outline-color: rebeccapurple; font-size: larger; width: 1px !important; width: 2px")
+    }(Node::default());
+
+    let div = &node.children[0];
+    assert_eq!(div.style.border_radius[0], Units::Px(4.0));
+    assert_eq!(div.style.border_size[0], Units::Px(1.0));
+    assert_eq!(div.style.font_color, glyphon::Color::rgb(0, 255, 0));
+    assert_eq!(div.style.outline_width, Units::Px(2.0));
+    assert_eq!(div.style.font_size, Units::Em(1.2));
+    assert_eq!(div.style.width, Units::Px(1.0));
+}
+
+#[test]
+fn test_ui_control_flow() {
+    let items = ["a", "b", "c"];
+    let show = false;
+
+    let node = ui! {
+        ul {
+            for item in &items {
+                li(key = {item}) { "item " {item} }
+            }
+        }
+        if show {
+            p { "shown" }
+        } else {
+            p { "hidden" }
+            p { "also hidden" }
+        }
+    }(Node::default());
+
+    assert_eq!(node.children.len(), 3);
+    assert_eq!(node.children[0].children.len(), 3);
+    assert_eq!(node.children[0].children[1].key.as_deref(), Some("b"));
+}
+
+// Inline styles are checked when the macro expands.
+#[test]
+fn test_ui_compile_errors() {
+    trybuild::TestCases::new().compile_fail("tests/compile_fail/*.rs");
+}
@@ -3,6 +3,8 @@ use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
 use syn::{parse_macro_input, BinOp, Expr, Lit, UnOp};
 
+mod ui;
+
 #[proc_macro]
 pub fn calc(input: TokenStream) -> TokenStream {
     let expr = parse_macro_input!(input as Expr);
@@ -16,6 +18,37 @@ pub fn calc(input: TokenStream) -> TokenStream {
     result.into()
 }
 
+/// Builds a `Node` tree from markup, expanding to a closure that appends the elements as
+/// children of the node it's given, so it can be passed straight to `Tree::new` or `add_child`.
+///
+/// ```ignore
+/// ui! {
+///     div(class = "row", style = "padding: 4px") {
+///         span { "Hello, " {name} }
+///         for item in &items {
+///             p { {item} }
+///         }
+///         if show_footer {
+///             footer { "Bye" }
+///         }
+///     }
+/// }
+/// ```
+///
+/// Inline styles are checked at compile time by the parser stylesheets use, so unknown
+/// properties and malformed values are reported as errors on the literal. Text and `{expr}`
+/// interpolations become the content of the enclosing element, or anonymous inline children
+/// in place when it also has child elements. Loops run inside an `Fn` closure, so iterate by
+/// reference.
+#[proc_macro]
+pub fn ui(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ui::Ui);
+    input
+        .expand()
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
 fn build_calc_expr(expr: &Expr) -> TokenStream2 {
     match expr {
         Expr::Binary(op) => {
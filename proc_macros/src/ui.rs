@@ -0,0 +1,344 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    braced, parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token, Error, Expr, Ident, LitStr, Pat, Result, Token,
+};
+
+pub struct Ui {
+    items: Vec<Item>,
+}
+
+enum Item {
+    Element(Element),
+    Text(LitStr),
+    Interpolation(Expr),
+    For {
+        pat: Pat,
+        expr: Expr,
+        body: Vec<Item>,
+    },
+    If(If),
+}
+
+struct If {
+    cond: Expr,
+    then: Vec<Item>,
+    otherwise: Option<Else>,
+}
+
+enum Else {
+    If(Box<If>),
+    Block(Vec<Item>),
+}
+
+struct Element {
+    tag: Ident,
+    attributes: Vec<Attribute>,
+    children: Vec<Item>,
+}
+
+struct Attribute {
+    name: Ident,
+    value: AttributeValue,
+}
+
+enum AttributeValue {
+    Lit(LitStr),
+    Expr(Expr),
+}
+
+impl Parse for Ui {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Ok(Self {
+            items: parse_items(input)?,
+        })
+    }
+}
+
+fn parse_items(input: ParseStream) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+    while !input.is_empty() {
+        items.push(input.parse()?);
+    }
+    Ok(items)
+}
+
+fn parse_block(input: ParseStream) -> Result<Vec<Item>> {
+    let content;
+    braced!(content in input);
+    parse_items(&content)
+}
+
+impl Parse for Item {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(LitStr) {
+            return Ok(Item::Text(input.parse()?));
+        }
+
+        if input.peek(token::Brace) {
+            let content;
+            braced!(content in input);
+            return Ok(Item::Interpolation(content.parse()?));
+        }
+
+        if input.peek(Token![for]) {
+            input.parse::<Token![for]>()?;
+            let pat = Pat::parse_single(input)?;
+            input.parse::<Token![in]>()?;
+            let expr = Expr::parse_without_eager_brace(input)?;
+            let body = parse_block(input)?;
+            return Ok(Item::For { pat, expr, body });
+        }
+
+        if input.peek(Token![if]) {
+            return Ok(Item::If(input.parse()?));
+        }
+
+        Ok(Item::Element(input.parse()?))
+    }
+}
+
+impl Parse for If {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![if]>()?;
+        let cond = Expr::parse_without_eager_brace(input)?;
+        let then = parse_block(input)?;
+
+        let otherwise = if input.peek(Token![else]) {
+            input.parse::<Token![else]>()?;
+            if input.peek(Token![if]) {
+                Some(Else::If(Box::new(input.parse()?)))
+            } else {
+                Some(Else::Block(parse_block(input)?))
+            }
+        } else {
+            None
+        };
+
+        Ok(Self {
+            cond,
+            then,
+            otherwise,
+        })
+    }
+}
+
+impl Parse for Element {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let tag: Ident = input.parse()?;
+
+        let attributes = if input.peek(token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            Punctuated::<Attribute, Token![,]>::parse_terminated(&content)?
+                .into_iter()
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let children = if input.peek(token::Brace) {
+            parse_block(input)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            tag,
+            attributes,
+            children,
+        })
+    }
+}
+
+impl Parse for Attribute {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let name: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+
+        let value = if input.peek(token::Brace) {
+            let content;
+            braced!(content in input);
+            AttributeValue::Expr(content.parse()?)
+        } else {
+            AttributeValue::Lit(input.parse()?)
+        };
+
+        Ok(Self { name, value })
+    }
+}
+
+impl Ui {
+    pub fn expand(&self) -> Result<TokenStream2> {
+        let body = expand_children(&self.items)?;
+
+        Ok(quote! {
+            |node: ::moxui::tree::node::Node| -> ::moxui::tree::node::Node {
+                #body
+                node
+            }
+        })
+    }
+}
+
+// Emits statements that rebind `node` once per child. Text can only live directly inside an
+// element, since it becomes that element's content or inherits its text properties.
+fn expand_children(items: &[Item]) -> Result<TokenStream2> {
+    items
+        .iter()
+        .map(|item| match item {
+            Item::Element(element) => {
+                let element = element.expand()?;
+                Ok(quote! { let node = node.add_child(|node| { #element }); })
+            }
+            Item::For { pat, expr, body } => {
+                let body = expand_children(body)?;
+                Ok(quote! {
+                    let node = (#expr).into_iter().fold(node, |node, #pat| {
+                        #body
+                        node
+                    });
+                })
+            }
+            Item::If(branch) => {
+                let branch = branch.expand()?;
+                Ok(quote! { let node = #branch; })
+            }
+            Item::Text(lit) => Err(Error::new(lit.span(), "text must be inside an element")),
+            Item::Interpolation(expr) => {
+                Err(Error::new(expr.span(), "text must be inside an element"))
+            }
+        })
+        .collect()
+}
+
+impl If {
+    fn expand(&self) -> Result<TokenStream2> {
+        let cond = &self.cond;
+        let then = expand_children(&self.then)?;
+        let otherwise = match &self.otherwise {
+            Some(Else::If(branch)) => branch.expand()?,
+            Some(Else::Block(items)) => {
+                let items = expand_children(items)?;
+                quote! { { #items node } }
+            }
+            None => quote! { node },
+        };
+
+        Ok(quote! {
+            if #cond {
+                #then
+                node
+            } else {
+                #otherwise
+            }
+        })
+    }
+}
+
+impl Element {
+    fn expand(&self) -> Result<TokenStream2> {
+        let display = display(&self.tag)?;
+        let mut setters = vec![quote! { .set_display(::moxui::rectangle::Display::#display) }];
+
+        for attribute in &self.attributes {
+            match (attribute.name.to_string().as_str(), &attribute.value) {
+                ("class", AttributeValue::Lit(lit)) => setters.push(quote! { .set_class(#lit) }),
+                ("class", AttributeValue::Expr(expr)) => {
+                    setters.push(quote! { .set_class(&(#expr).to_string()) })
+                }
+                ("id", AttributeValue::Lit(lit)) => setters.push(quote! { .set_id(#lit) }),
+                ("id", AttributeValue::Expr(expr)) => {
+                    setters.push(quote! { .set_id(&(#expr).to_string()) })
+                }
+                ("key", AttributeValue::Lit(lit)) => setters.push(quote! { .set_key(#lit) }),
+                ("key", AttributeValue::Expr(expr)) => {
+                    setters.push(quote! { .set_key(&(#expr).to_string()) })
+                }
+                ("style", AttributeValue::Lit(lit)) => setters.push(parse_style(lit)?),
+                ("style", AttributeValue::Expr(expr)) => {
+                    return Err(Error::new(
+                        expr.span(),
+                        "style must be a string literal so it can be checked at compile time",
+                    ))
+                }
+                (name, _) => {
+                    return Err(Error::new(
+                        attribute.name.span(),
+                        format!("unknown attribute `{name}`"),
+                    ))
+                }
+            }
+        }
+
+        // Text-only elements carry the text themselves, text between child elements becomes
+        // anonymous inline children so it stays in place.
+        let text = |item: &Item| match item {
+            Item::Text(lit) => Some(quote! { #lit.to_string() }),
+            Item::Interpolation(expr) => Some(quote! { (#expr).to_string() }),
+            _ => None,
+        };
+        let mut children = TokenStream2::new();
+        if self.children.iter().all(|item| text(item).is_some()) {
+            let text = self.children.iter().filter_map(text).collect::<Vec<_>>();
+            if !text.is_empty() {
+                children.extend(quote! { let node = node.set_content(&[#(#text),*].concat()); });
+            }
+        } else {
+            let mut run = Vec::new();
+            for item in &self.children {
+                match text(item) {
+                    Some(text) => run.push(text),
+                    None => {
+                        children.extend(text_run(&mut run));
+                        children.extend(expand_children(std::slice::from_ref(item))?);
+                    }
+                }
+            }
+            children.extend(text_run(&mut run));
+        }
+
+        Ok(quote! {
+            let node = node #(#setters)*;
+            #children
+            node
+        })
+    }
+}
+
+// Emits the pending run of text between child elements, if any.
+fn text_run(run: &mut Vec<TokenStream2>) -> Option<TokenStream2> {
+    (!run.is_empty()).then(|| {
+        let text = std::mem::take(run);
+        quote! { let node = node.add_text(&[#(#text),*].concat()); }
+    })
+}
+
+fn display(tag: &Ident) -> Result<Ident> {
+    let display = match tag.to_string().as_str() {
+        "div" | "p" | "section" | "header" | "footer" | "nav" | "main" | "article" | "aside"
+        | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "ul" | "ol" | "li" | "form" => "Block",
+        "span" | "a" | "b" | "i" | "em" | "strong" | "small" | "label" | "code" => "Inline",
+        "button" | "input" | "img" => "InlineBlock",
+        name => return Err(Error::new(tag.span(), format!("unknown element `{name}`"))),
+    };
+
+    Ok(Ident::new(display, Span::call_site()))
+}
+
+// Checks an inline style at compile time with the parser stylesheets use, so it accepts what
+// they accept, and applies it as written.
+fn parse_style(lit: &LitStr) -> Result<TokenStream2> {
+    moxui::__private::check_declarations(&lit.value()).map_err(|declaration| {
+        Error::new(
+            lit.span(),
+            format!("unsupported declaration `{declaration}`"),
+        )
+    })?;
+
+    Ok(quote! { .set_style(#lit) })
+}